@@ -1,9 +1,13 @@
 use std::{
-	collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
+	collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+	fmt,
 	hash::Hash,
+	ops::{Bound, RangeBounds},
 };
 
-use btree_range_map::AnyRange;
+use btree_range_map::{AnyRange, RangeMap, RangeSet};
+
+use crate::{nfa::symbolic_alphabet, Automaton, TaggedAutomaton, Token, NFA};
 
 /// Deterministic finite automaton.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -51,6 +55,15 @@ impl<Q, L> DFA<Q, L> {
 		&self.transitions.0
 	}
 
+	/// Returns an iterator over the transitions of the automaton, as
+	/// `(source, label, target)` triples.
+	pub fn edges(&self) -> impl '_ + Iterator<Item = (&Q, &L, &Q)> {
+		self.transitions.0.iter().flat_map(|(source, map)| {
+			map.iter()
+				.map(move |(label, target)| (source, label, target))
+		})
+	}
+
 	/// Returns an iterator over all the states reachable from the given
 	/// starting state `q`.
 	pub fn reachable_states_from<'a>(&'a self, q: &'a Q) -> ReachableStates<'a, Q, L> {
@@ -71,6 +84,38 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 		self.final_states.insert(q)
 	}
 
+	/// Returns the final states that are not reachable from the initial
+	/// state.
+	pub fn unreachable_final_states(&self) -> BTreeSet<&Q>
+	where
+		Q: Hash,
+	{
+		let reachable: HashSet<_> = self.reachable_states_from(&self.initial_state).collect();
+		self.final_states
+			.iter()
+			.filter(|q| !reachable.contains(q))
+			.collect()
+	}
+
+	/// Returns the states from which a final state can be reached.
+	pub fn productive_states(&self) -> BTreeSet<&Q> {
+		let mut result: BTreeSet<_> = self.final_states.iter().collect();
+
+		loop {
+			let len = result.len();
+
+			for (source, _, target) in self.edges() {
+				if result.contains(target) {
+					result.insert(source);
+				}
+			}
+
+			if result.len() == len {
+				break result;
+			}
+		}
+	}
+
 	pub fn declare_state(&mut self, q: Q) {
 		self.transitions.0.entry(q).or_default();
 	}
@@ -79,7 +124,7 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 		self.transitions.0.get(q).into_iter().flatten()
 	}
 
-	pub fn successors(&self, q: &Q) -> DetSuccessors<Q, L> {
+	pub fn successors(&self, q: &Q) -> DetSuccessors<'_, Q, L> {
 		DetSuccessors::new(self.transitions.0.get(q))
 	}
 
@@ -182,12 +227,90 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 	}
 
 	/// Minimizes the automaton.
+	///
+	/// The result only depends on the automaton and the given partition, not
+	/// on the order in which the members of the partition are given.
+	pub fn minimize<'a, P>(&'a self, partition: P) -> DFA<BTreeSet<&'a Q>, &'a L>
+	where
+		Q: Hash,
+		P: Iterator<Item = BTreeSet<&'a Q>>,
+	{
+		let partition = self.refine_partition(partition);
+
+		let mut map = HashMap::new();
+		for member in partition {
+			for q in &member {
+				map.insert(*q, member.clone());
+			}
+		}
+
+		let mut result = DFA::new(map[&self.initial_state].clone());
+		for (source, transitions) in &self.transitions.0 {
+			for (range, target) in transitions {
+				result.add(map[source].clone(), range, map[target].clone());
+			}
+		}
+
+		for q in &self.final_states {
+			if let Some(r) = map.get(q) {
+				result.add_final_state(r.clone());
+			}
+		}
+
+		result
+	}
+
+	/// Minimizes the automaton, returning a self-contained DFA.
+	///
+	/// Unlike [`Self::minimize`], the result does not borrow `self`: labels
+	/// are cloned and states are numbered, the initial state being `0`.
+	pub fn minimize_owned<'a, P>(&'a self, partition: P) -> DFA<u32, L>
+	where
+		Q: Hash,
+		L: Clone + Hash,
+		P: Iterator<Item = BTreeSet<&'a Q>>,
+	{
+		let minimal = self.minimize(partition);
+
+		let mut ids = HashMap::new();
+		ids.insert(minimal.initial_state(), 0);
+		for q in minimal.states() {
+			let id = ids.len() as u32;
+			ids.entry(q).or_insert(id);
+		}
+
+		minimal.map(|q| ids[q], |label| (*label).clone())
+	}
+
+	/// Computes the state equivalence classes used by [`Self::minimize`].
+	///
+	/// Maps each state of the given initial partition to the index of its
+	/// equivalence class. Two states are mapped to the same index if and only
+	/// if they are merged into the same state by [`Self::minimize`] with the
+	/// same initial partition. This can be used to translate auxiliary data
+	/// attached to the states of this automaton into the minimized one.
+	pub fn equivalence_classes<'a, P>(&'a self, partition: P) -> HashMap<&'a Q, usize>
+	where
+		Q: Hash,
+		P: Iterator<Item = BTreeSet<&'a Q>>,
+	{
+		let mut map = HashMap::new();
+
+		for (i, member) in self.refine_partition(partition).into_iter().enumerate() {
+			for q in member {
+				map.insert(q, i);
+			}
+		}
+
+		map
+	}
+
+	/// Refines the given partition until equivalent states are in the same
+	/// member.
 	// Hopcroft's algorithm.
 	// https://en.wikipedia.org/wiki/DFA_minimization
-	pub fn minimize<'a, P>(&'a self, partition: P) -> DFA<BTreeSet<&Q>, &L>
+	fn refine_partition<'a, P>(&'a self, partition: P) -> BTreeSet<BTreeSet<&'a Q>>
 	where
-		Q: Hash,
-		L: Hash,
 		P: Iterator<Item = BTreeSet<&'a Q>>,
 	{
 		let mut partition: BTreeSet<_> = partition.collect();
@@ -195,19 +318,13 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 		let mut working = partition.clone();
 
 		while let Some(a) = working.pop_first() {
-			let mut sources_by_label: HashMap<&L, BTreeSet<&Q>> = HashMap::new();
+			// Ordered, so that the members are split in a deterministic order.
+			let mut sources_by_label: BTreeMap<&L, BTreeSet<&Q>> = BTreeMap::new();
 
 			for (source, targets) in &self.transitions.0 {
 				for (label, target) in targets {
 					if a.contains(target) {
-						if sources_by_label.contains_key(label) {
-							let sources = sources_by_label.get_mut(label).unwrap();
-							sources.insert(source);
-						} else {
-							let mut sources = BTreeSet::new();
-							sources.insert(source);
-							sources_by_label.insert(label, sources);
-						}
+						sources_by_label.entry(label).or_default().insert(source);
 					}
 				}
 			}
@@ -238,21 +355,7 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 			}
 		}
 
-		let mut map = HashMap::new();
-		for member in partition {
-			for q in &member {
-				map.insert(*q, member.clone());
-			}
-		}
-
-		let mut result = DFA::new(map[&self.initial_state].clone());
-		for (source, transitions) in &self.transitions.0 {
-			for (range, target) in transitions {
-				result.add(map[source].clone(), range, map[target].clone());
-			}
-		}
-
-		result
+		partition
 	}
 
 	pub fn map<P, M>(&self, mut f: impl FnMut(&Q) -> P, mut g: impl FnMut(&L) -> M) -> DFA<P, M>
@@ -329,10 +432,29 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 	}
 
 	pub fn product<'a, 'b, R, S, M, N>(
+		&'a self,
+		other: &'b DFA<R, M>,
+		f: impl FnMut(&'a Q, &'b R) -> S,
+		g: impl FnMut(&'a L, &'b M) -> Option<N>,
+	) -> DFA<S, N>
+	where
+		R: Ord,
+		S: Clone + Ord + Hash,
+		M: Ord,
+		N: Ord,
+	{
+		self.product_by(other, f, g, |a_final, b_final| a_final && b_final)
+	}
+
+	/// Computes the product of this automaton with the `other` one, where
+	/// `is_final` decides if a pair of states is final from the finality of
+	/// each state.
+	fn product_by<'a, 'b, R, S, M, N>(
 		&'a self,
 		other: &'b DFA<R, M>,
 		mut f: impl FnMut(&'a Q, &'b R) -> S,
 		mut g: impl FnMut(&'a L, &'b M) -> Option<N>,
+		is_final: impl Fn(bool, bool) -> bool,
 	) -> DFA<S, N>
 	where
 		R: Ord,
@@ -352,7 +474,7 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 		let mut visited = HashSet::new();
 		while let Some((q, a, b)) = stack.pop() {
 			if visited.insert(q.clone()) {
-				if self.is_final_state(a) && other.is_final_state(b) {
+				if is_final(self.is_final_state(a), other.is_final_state(b)) {
 					result.add_final_state(q.clone());
 				}
 
@@ -435,6 +557,774 @@ impl<Q: Ord, L: Ord> DFA<Q, L> {
 	}
 }
 
+impl<Q: Clone + Ord, T: Token> DFA<Q, RangeSet<T>> {
+	/// Returns the reverse of this automaton, recognizing the mirror image of
+	/// its language.
+	pub fn reverse(&self) -> NFA<Q, T> {
+		let mut result = NFA::new();
+
+		result.add_state(self.initial_state.clone());
+		result.add_final_state(self.initial_state.clone());
+
+		for q in &self.final_states {
+			result.add_state(q.clone());
+			result.add_initial_state(q.clone());
+		}
+
+		for (source, transitions) in &self.transitions.0 {
+			for (label, target) in transitions {
+				result.add(target.clone(), Some(label.clone()), source.clone());
+			}
+		}
+
+		result
+	}
+
+	/// Splits every transition label into its ranges.
+	///
+	/// Transitions of the resulting automaton are found with a range search
+	/// (see [`DFA::step`]) instead of scanning every label of a state.
+	pub fn split_labels(&self) -> DFA<Q, AnyRange<T>> {
+		let mut result = DFA::new(self.initial_state.clone());
+
+		for (source, transitions) in &self.transitions.0 {
+			result.declare_state(source.clone());
+			for (label, target) in transitions {
+				for range in label.iter() {
+					result.add(source.clone(), *range, target.clone());
+				}
+			}
+		}
+
+		for q in &self.final_states {
+			result.add_final_state(q.clone());
+		}
+
+		result
+	}
+}
+
+impl<Q: Ord + Hash, T: Token + Hash> DFA<Q, RangeSet<T>> {
+	/// Computes the canonical form of this automaton.
+	///
+	/// The automaton is trimmed (unreachable and unproductive states are
+	/// removed) and minimized. States are then numbered in breadth-first
+	/// order from the initial state `0`, visiting the successors of each
+	/// state by increasing label. Two automata recognize the same language if
+	/// and only if their canonical forms are equal.
+	pub fn canonicalize(&self) -> DFA<u32, RangeSet<T>> {
+		let reachable: HashSet<_> = self.reachable_states_from(&self.initial_state).collect();
+		let productive = self.productive_states();
+
+		let mut trimmed = DFA::new(&self.initial_state);
+		for (source, label, target) in self.edges() {
+			if reachable.contains(source) && productive.contains(target) {
+				for range in label.iter() {
+					trimmed.add(source, *range, target);
+				}
+			}
+		}
+
+		for q in &self.final_states {
+			if reachable.contains(q) {
+				trimmed.add_final_state(q);
+			}
+		}
+
+		let atomized = trimmed.atomized();
+		let partition = atomized.partition(|q| atomized.is_final_state(q));
+		let minimal = atomized.minimize(partition.into_values());
+
+		let mut labels: BTreeMap<_, BTreeMap<_, RangeSet<T>>> = BTreeMap::new();
+		for (source, range, target) in minimal.edges() {
+			labels
+				.entry(source)
+				.or_default()
+				.entry(target)
+				.or_default()
+				.insert(**range);
+		}
+
+		let mut ids = HashMap::new();
+		let mut queue = VecDeque::new();
+		ids.insert(minimal.initial_state(), 0);
+		queue.push_back(minimal.initial_state());
+
+		let mut result = DFA::new(0);
+		while let Some(q) = queue.pop_front() {
+			let mut successors: Vec<_> = labels
+				.get(q)
+				.into_iter()
+				.flatten()
+				.map(|(target, label)| (label, target))
+				.collect();
+			successors.sort_unstable();
+
+			for (label, target) in successors {
+				let id = ids.len() as u32;
+				let target_id = *ids.entry(target).or_insert_with(|| {
+					queue.push_back(target);
+					id
+				});
+				result.add(ids[q], label.clone(), target_id);
+			}
+		}
+
+		for q in minimal.final_states() {
+			result.add_final_state(ids[q]);
+		}
+
+		result
+	}
+}
+
+impl<Q: Ord> DFA<Q, RangeSet<char>> {
+	/// Checks that the whole given string is accepted by the automaton.
+	pub fn validate(&self, s: &str) -> bool {
+		self.contains(s.chars())
+	}
+}
+
+impl<Q: Ord> DFA<Q, AnyRange<char>> {
+	/// Checks that the whole given string is accepted by the automaton.
+	///
+	/// The string is read one character at a time with [`Self::step`],
+	/// stopping at the first character without transition.
+	pub fn matches_str(&self, s: &str) -> bool {
+		self.contains(s.chars())
+	}
+
+	/// Checks that the whole given string is accepted by the automaton.
+	///
+	/// Same as [`Self::matches_str`].
+	pub fn validate(&self, s: &str) -> bool {
+		self.matches_str(s)
+	}
+}
+
+impl<Q: Ord, T: Token> DFA<Q, RangeSet<T>> {
+	/// Follows the transition from `q` labeled by the given token.
+	///
+	/// Returns [`Step::Dead`] if there is no such transition, meaning that the
+	/// automaton went into the implicit dead state.
+	pub fn step(&self, q: &Q, token: T) -> Step<&Q> {
+		self.successors(q)
+			.find_map(|(label, target)| label.contains(token).then_some(target))
+			.into()
+	}
+
+	/// Returns an iterator over the words recognized by this automaton, in
+	/// length order.
+	///
+	/// The iteration is lazy, so it may be used on infinite languages.
+	pub fn words(&self) -> Words<'_, Q, T> {
+		Words::new(self)
+	}
+
+	/// Follows the transition from the given step result labeled by the given
+	/// token.
+	///
+	/// The dead state is a sink: stepping from [`Step::Dead`] always returns
+	/// [`Step::Dead`].
+	pub fn step_from(&self, state: Step<&Q>, token: T) -> Step<&Q> {
+		match state {
+			Step::To(q) => self.step(q, token),
+			Step::Dead => Step::Dead,
+		}
+	}
+
+	/// Returns the transition function of this automaton as a closure.
+	///
+	/// The closure returns the target of the transition from the given state
+	/// labeled by the given token, or `None` if the automaton goes into the
+	/// implicit dead state. This allows driving the automaton by hand, for
+	/// instance in a custom search loop.
+	pub fn transition_fn<'a>(&'a self) -> impl 'a + Fn(&Q, T) -> Option<&'a Q> {
+		move |q: &Q, token| self.step(q, token).state()
+	}
+
+	/// Returns a human-readable description of the automaton.
+	///
+	/// The first two lines give the initial and final states, followed by one
+	/// line per state with outgoing transitions, listing them.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex_automata::{DFA, RangeSet};
+	/// let mut dfa = DFA::new(0);
+	/// dfa.add(0, RangeSet::from_iter(['a'..='c', 'x'..='x']), 1);
+	/// dfa.add_final_state(1);
+	///
+	/// assert_eq!(
+	///     dfa.debug_dump(),
+	///     "initial: 0\nfinal: {1}\n0: ['a'-'c' 'x'] -> 1\n"
+	/// );
+	/// ```
+	pub fn debug_dump(&self) -> String
+	where
+		Q: fmt::Debug,
+		T: fmt::Debug,
+	{
+		let mut result = String::new();
+		self.debug_dump_fmt(&mut result).unwrap();
+		result
+	}
+
+	fn debug_dump_fmt(&self, f: &mut impl fmt::Write) -> fmt::Result
+	where
+		Q: fmt::Debug,
+		T: fmt::Debug,
+	{
+		writeln!(f, "initial: {:?}", self.initial_state)?;
+		writeln!(f, "final: {:?}", self.final_states)?;
+
+		for (q, transitions) in &self.transitions.0 {
+			write!(f, "{q:?}:")?;
+
+			for (i, (label, target)) in transitions.iter().enumerate() {
+				f.write_str(if i == 0 { " [" } else { ", [" })?;
+				fmt_label(f, label)?;
+				write!(f, "] -> {target:?}")?;
+			}
+
+			writeln!(f)?;
+		}
+
+		Ok(())
+	}
+
+	/// Computes the coarsest partition of the tokens appearing in the
+	/// transition labels such that every label is a union of partition
+	/// blocks.
+	///
+	/// See [`NFA::symbolic_alphabet`].
+	pub fn symbolic_alphabet(&self) -> Vec<RangeSet<T>> {
+		symbolic_alphabet(
+			self.transitions
+				.0
+				.values()
+				.flat_map(|transitions| transitions.keys()),
+		)
+	}
+
+	/// Renders the transition table of the automaton as a Markdown table.
+	///
+	/// There is one row per state and one column per block of the
+	/// [symbolic alphabet](Self::symbolic_alphabet), each cell giving the
+	/// target of the transition, if any. The initial state is marked with
+	/// `->` and final states with `*`.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex_automata::{DFA, RangeSet};
+	/// let mut dfa = DFA::new(0);
+	/// dfa.add(0, RangeSet::from_iter(['a'..='c']), 1);
+	/// dfa.add_final_state(1);
+	///
+	/// assert_eq!(
+	///     dfa.to_table(),
+	///     "| | 'a'-'c' |\n|---|---|\n| -> 0 | 1 |\n| * 1 | |\n"
+	/// );
+	/// ```
+	pub fn to_table(&self) -> String
+	where
+		Q: fmt::Debug,
+		T: fmt::Debug,
+	{
+		let mut result = String::new();
+		self.to_table_fmt(&mut result).unwrap();
+		result
+	}
+
+	fn to_table_fmt(&self, f: &mut impl fmt::Write) -> fmt::Result
+	where
+		Q: fmt::Debug,
+		T: fmt::Debug,
+	{
+		let mut columns = self.symbolic_alphabet();
+		columns
+			.sort_unstable_by_key(|block| block.iter().next().map(|range| range.first().unwrap()));
+
+		let mut states: BTreeSet<&Q> = self.final_states.iter().collect();
+		states.insert(&self.initial_state);
+		for (source, transitions) in &self.transitions.0 {
+			states.insert(source);
+			states.extend(transitions.values());
+		}
+
+		f.write_str("| |")?;
+		for block in &columns {
+			let mut label = String::new();
+			fmt_label(&mut label, block)?;
+			write!(f, " {} |", label.replace('|', "\\|"))?;
+		}
+		writeln!(f)?;
+
+		f.write_str("|---|")?;
+		for _ in &columns {
+			f.write_str("---|")?;
+		}
+		writeln!(f)?;
+
+		for q in states {
+			f.write_str("| ")?;
+			if *q == self.initial_state {
+				f.write_str("-> ")?;
+			}
+			if self.final_states.contains(q) {
+				f.write_str("* ")?;
+			}
+			write!(f, "{q:?} |")?;
+
+			for block in &columns {
+				let token = block.iter().next().unwrap().first().unwrap();
+				match self.step(q, token) {
+					Step::To(r) => write!(f, " {r:?} |")?,
+					Step::Dead => f.write_str(" |")?,
+				}
+			}
+
+			writeln!(f)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Writes the ranges of the given label, separated by spaces.
+fn fmt_label<T: fmt::Debug + Token>(f: &mut impl fmt::Write, label: &RangeSet<T>) -> fmt::Result {
+	for (i, range) in label.iter().enumerate() {
+		if i > 0 {
+			f.write_char(' ')?;
+		}
+
+		let (first, last) = (range.first().unwrap(), range.last().unwrap());
+		if first == last {
+			write!(f, "{first:?}")?;
+		} else {
+			write!(f, "{first:?}-{last:?}")?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Result of a [`DFA`] step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Step<Q> {
+	/// The automaton went into the given state.
+	To(Q),
+
+	/// The automaton went into the implicit dead state, from which no final
+	/// state can be reached.
+	Dead,
+}
+
+impl<Q> Step<Q> {
+	pub fn is_dead(&self) -> bool {
+		matches!(self, Self::Dead)
+	}
+
+	pub fn state(self) -> Option<Q> {
+		match self {
+			Self::To(q) => Some(q),
+			Self::Dead => None,
+		}
+	}
+}
+
+impl<Q> From<Option<Q>> for Step<Q> {
+	fn from(value: Option<Q>) -> Self {
+		match value {
+			Some(q) => Self::To(q),
+			None => Self::Dead,
+		}
+	}
+}
+
+impl<Q: Ord, T: Token> Automaton<T> for DFA<Q, RangeSet<T>> {
+	type State<'a> = &'a Q where Self: 'a;
+
+	fn initial_state(&self) -> Option<Self::State<'_>> {
+		Some(&self.initial_state)
+	}
+
+	fn next_state<'a>(
+		&'a self,
+		current_state: Self::State<'a>,
+		token: T,
+	) -> Option<Self::State<'a>> {
+		self.step(current_state, token).state()
+	}
+
+	fn is_final_state<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		self.final_states.contains(*state)
+	}
+
+	/// Checks if the state is final and loops on every token.
+	fn accepts_any_continuation<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		if !self.final_states.contains(*state) {
+			return false;
+		}
+
+		let mut missing = T::all();
+		for (label, r) in self.successors(state) {
+			if r == *state {
+				for range in label.iter() {
+					missing.remove(*range);
+				}
+			}
+		}
+
+		missing.is_empty()
+	}
+}
+
+impl<Q: Ord, T: Token> DFA<Q, AnyRange<T>> {
+	/// Follows the transition from `q` whose label contains the given token.
+	///
+	/// Returns [`Step::Dead`] if there is no such transition. Since the labels
+	/// of a state are disjoint, the transition is found with a range search
+	/// on the label map rather than by scanning every label.
+	pub fn step(&self, q: &Q, token: T) -> Step<&Q> {
+		self.transitions
+			.0
+			.get(q)
+			.and_then(|transitions| {
+				let probe = AnyRange::new(Bound::Included(token), Bound::Included(token));
+				transitions
+					.range(..=probe)
+					.next_back()
+					.into_iter()
+					.chain(transitions.range(probe..).next())
+					.find_map(|(label, target)| label.contains(&token).then_some(target))
+			})
+			.into()
+	}
+}
+
+impl<Q: Ord, T: Token> Automaton<T> for DFA<Q, AnyRange<T>> {
+	type State<'a> = &'a Q where Self: 'a;
+
+	fn initial_state(&self) -> Option<Self::State<'_>> {
+		Some(&self.initial_state)
+	}
+
+	fn next_state<'a>(
+		&'a self,
+		current_state: Self::State<'a>,
+		token: T,
+	) -> Option<Self::State<'a>> {
+		self.step(current_state, token).state()
+	}
+
+	fn is_final_state<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		self.final_states.contains(*state)
+	}
+
+	/// Checks if the state is final and loops on every token.
+	fn accepts_any_continuation<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		if !self.final_states.contains(*state) {
+			return false;
+		}
+
+		let mut missing = T::all();
+		for (range, r) in self.transitions_from(state) {
+			if r == *state {
+				missing.remove(*range);
+			}
+		}
+
+		missing.is_empty()
+	}
+}
+
+impl<Q: Clone + Ord, T: Token> DFA<Q, AnyRange<T>> {
+	/// Merges the transition labels leading to the same state.
+	///
+	/// This is the inverse of [`DFA::split_labels`].
+	pub fn merge_labels(&self) -> DFA<Q, RangeSet<T>> {
+		let mut result = DFA::new(self.initial_state.clone());
+
+		for (source, transitions) in &self.transitions.0 {
+			result.declare_state(source.clone());
+			let mut labels: BTreeMap<&Q, RangeSet<T>> = BTreeMap::new();
+			for (range, target) in transitions {
+				labels.entry(target).or_default().insert(*range);
+			}
+
+			for (target, label) in labels {
+				result.add(source.clone(), label, target.clone());
+			}
+		}
+
+		for q in &self.final_states {
+			result.add_final_state(q.clone());
+		}
+
+		result
+	}
+
+	/// Checks that every state has a transition for every token of the
+	/// given alphabet.
+	pub fn is_total(&self, alphabet: &RangeSet<T>) -> bool {
+		self.transitions.0.values().all(|transitions| {
+			let mut missing = alphabet.clone();
+			for range in transitions.keys() {
+				missing.remove(*range);
+			}
+			missing.is_empty()
+		})
+	}
+
+	/// Makes the automaton total over the given alphabet, sending every
+	/// missing transition to the `sink` state.
+	///
+	/// The `sink` state is only added if a transition is missing, with a
+	/// transition to itself for every token of the alphabet. Returns whether
+	/// or not it has been added.
+	///
+	/// # Panics
+	///
+	/// Panics if `sink` is already a state of the automaton.
+	pub fn complete(&mut self, alphabet: &RangeSet<T>, sink: Q) -> bool {
+		let states = self.owned_states();
+		assert!(!states.contains(&sink), "the sink state is not fresh");
+
+		let mut sink_added = false;
+		for q in states {
+			let mut missing = alphabet.clone();
+			for range in self
+				.transitions
+				.0
+				.get(&q)
+				.into_iter()
+				.flat_map(BTreeMap::keys)
+			{
+				missing.remove(*range);
+			}
+
+			for range in missing.iter() {
+				self.add(q.clone(), *range, sink.clone());
+				sink_added = true;
+			}
+		}
+
+		if sink_added {
+			for range in alphabet.iter() {
+				self.add(sink.clone(), *range, sink.clone());
+			}
+		}
+
+		sink_added
+	}
+
+	/// Computes the complement of this automaton over the given alphabet.
+	///
+	/// The automaton is first made total with [`Self::complete`], then its
+	/// final and non-final states are swapped. States are wrapped in `Some`,
+	/// leaving `None` free to be used as sink. The result accepts exactly the
+	/// words over `alphabet` that are not accepted by `self`, provided every
+	/// transition label is included in `alphabet`.
+	pub fn complement(self, alphabet: &RangeSet<T>) -> DFA<Option<Q>, AnyRange<T>> {
+		let mut result = self.with_optional_states();
+		result.complete(alphabet, None);
+
+		let final_states = result
+			.owned_states()
+			.into_iter()
+			.filter(|q| !result.final_states.contains(q))
+			.collect();
+		result.final_states = final_states;
+
+		result
+	}
+
+	/// Computes the intersection of this automaton with the `other` one.
+	///
+	/// This is the [product](Self::product) of the two automata, where each
+	/// state is the pair of the original states, and each label is the
+	/// overlap of the original labels.
+	pub fn intersection<R>(&self, other: &DFA<R, AnyRange<T>>) -> DFA<(Q, R), AnyRange<T>>
+	where
+		Q: Hash,
+		R: Clone + Ord + Hash,
+	{
+		self.product(
+			other,
+			|a, b| (a.clone(), b.clone()),
+			|a, b| a.intersects(b).then(|| a.intersection(b)),
+		)
+	}
+
+	/// Computes the union of this automaton with the `other` one.
+	///
+	/// The [product](Self::product) of two automata only follows the tokens
+	/// accepted by both, while the union must keep reading a word when only
+	/// one automaton still accepts it. Both automata are hence first made
+	/// total over `alphabet` with [`Self::complete`], the sink state being
+	/// `None`. The union is then only defined for the words over `alphabet`.
+	pub fn union<R>(
+		&self,
+		other: &DFA<R, AnyRange<T>>,
+		alphabet: &RangeSet<T>,
+	) -> DFA<(Option<Q>, Option<R>), AnyRange<T>>
+	where
+		Q: Hash,
+		R: Clone + Ord + Hash,
+	{
+		let mut a = self.with_optional_states();
+		a.complete(alphabet, None);
+
+		let mut b = other.with_optional_states();
+		b.complete(alphabet, None);
+
+		a.product_by(
+			&b,
+			|a, b| (a.clone(), b.clone()),
+			|a, b| a.intersects(b).then(|| a.intersection(b)),
+			|a_final, b_final| a_final || b_final,
+		)
+	}
+
+	/// Wraps every state in `Some`, leaving `None` free to be used as sink.
+	fn with_optional_states(&self) -> DFA<Option<Q>, AnyRange<T>> {
+		let transitions: BTreeMap<_, _> = self
+			.transitions
+			.0
+			.iter()
+			.map(|(q, transitions)| {
+				let transitions = transitions
+					.iter()
+					.map(|(label, r)| (*label, Some(r.clone())))
+					.collect();
+				(Some(q.clone()), transitions)
+			})
+			.collect();
+
+		DFA::from_parts(
+			Some(self.initial_state.clone()),
+			self.final_states.iter().cloned().map(Some).collect(),
+			DetTransitions(transitions),
+		)
+	}
+
+	/// Returns every state of the automaton, including the ones without
+	/// outgoing transitions.
+	fn owned_states(&self) -> BTreeSet<Q> {
+		let mut states: BTreeSet<Q> = self.final_states.clone();
+		states.insert(self.initial_state.clone());
+		for (source, transitions) in &self.transitions.0 {
+			states.insert(source.clone());
+			states.extend(transitions.values().cloned());
+		}
+
+		states
+	}
+
+	/// Splits the transition labels so that any two labels are either equal
+	/// or disjoint.
+	///
+	/// This is required for [`Self::minimize`] to merge every pair of
+	/// equivalent states, since it compares labels by equality.
+	pub(crate) fn atomized(&self) -> Self {
+		let mut atoms: RangeMap<T, BTreeSet<usize>> = RangeMap::new();
+		let mut labels = Vec::new();
+		for (source, transitions) in &self.transitions.0 {
+			for (range, target) in transitions {
+				let i = labels.len();
+				atoms.update(*range, |ids: Option<&BTreeSet<usize>>| {
+					let mut ids = ids.cloned().unwrap_or_default();
+					ids.insert(i);
+					Some(ids)
+				});
+				labels.push((source, target));
+			}
+		}
+
+		let mut result = DFA::from_parts(
+			self.initial_state.clone(),
+			self.final_states.clone(),
+			DetTransitions(BTreeMap::new()),
+		);
+
+		for (atom, ids) in atoms {
+			for i in ids {
+				let (source, target) = labels[i];
+				result.add(source.clone(), atom, target.clone());
+			}
+		}
+
+		result
+	}
+}
+
+impl<T: Token> DFA<u32, AnyRange<T>> {
+	/// Makes the automaton total over the given alphabet, using a fresh
+	/// state (one plus the greatest state) as sink.
+	///
+	/// Returns the sink state, if it has been added.
+	/// See [`Self::complete`].
+	///
+	/// # Panics
+	///
+	/// Panics if `u32::MAX` is already a state of the automaton.
+	pub fn complete_with_fresh_sink(&mut self, alphabet: &RangeSet<T>) -> Option<u32> {
+		let max = self
+			.transitions
+			.0
+			.iter()
+			.flat_map(|(source, transitions)| std::iter::once(source).chain(transitions.values()))
+			.chain(&self.final_states)
+			.fold(self.initial_state, |max, q| max.max(*q));
+
+		let sink = max.checked_add(1).expect("no fresh state available");
+		self.complete(alphabet, sink).then_some(sink)
+	}
+}
+
+/// DFA whose final states are tagged with the index of the pattern they
+/// recognize.
+///
+/// See [`NFA::to_tagged_dfa`].
+#[derive(Debug, Clone)]
+pub struct TaggedDFA<T> {
+	pub untagged: DFA<u32, RangeSet<T>>,
+	pub tags: BTreeMap<u32, usize>,
+}
+
+impl<T: Token> Automaton<T> for TaggedDFA<T> {
+	type State<'a> = &'a u32 where Self: 'a;
+
+	fn initial_state(&self) -> Option<Self::State<'_>> {
+		Automaton::initial_state(&self.untagged)
+	}
+
+	fn next_state<'a>(
+		&'a self,
+		current_state: Self::State<'a>,
+		token: T,
+	) -> Option<Self::State<'a>> {
+		Automaton::next_state(&self.untagged, current_state, token)
+	}
+
+	fn is_final_state<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		Automaton::is_final_state(&self.untagged, state)
+	}
+
+	fn accepts_any_continuation<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		Automaton::accepts_any_continuation(&self.untagged, state)
+	}
+}
+
+impl<T: Token> TaggedAutomaton<T, usize> for TaggedDFA<T> {
+	fn get_tag<'a>(&'a self, state: &Self::State<'a>) -> Option<usize> {
+		self.tags.get(*state).copied()
+	}
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DetTransitions<Q, L>(BTreeMap<Q, BTreeMap<L, Q>>);
 
@@ -474,6 +1364,83 @@ impl<'a, Q, L> Iterator for DetSuccessors<'a, Q, L> {
 	}
 }
 
+type Successors<'a, Q, T> = Box<dyn 'a + Iterator<Item = (T, &'a Q)>>;
+
+/// Iterator over the words recognized by a [`DFA`].
+///
+/// See [`DFA::words`].
+pub struct Words<'a, Q, T> {
+	aut: &'a DFA<Q, RangeSet<T>>,
+	productive: BTreeSet<&'a Q>,
+	empty: bool,
+	queue: VecDeque<(Vec<T>, Successors<'a, Q, T>)>,
+}
+
+impl<'a, Q: Ord, T: Token> Words<'a, Q, T> {
+	fn new(aut: &'a DFA<Q, RangeSet<T>>) -> Self {
+		let productive = aut.productive_states();
+		let mut queue = VecDeque::new();
+
+		if productive.contains(&aut.initial_state) {
+			queue.push_back((Vec::new(), Self::successors(aut, &aut.initial_state)));
+		}
+
+		Self {
+			aut,
+			productive,
+			empty: aut.is_final_state(&aut.initial_state),
+			queue,
+		}
+	}
+
+	fn successors(aut: &'a DFA<Q, RangeSet<T>>, q: &'a Q) -> Successors<'a, Q, T> {
+		Box::new(aut.successors(q).flat_map(|(label, target)| {
+			label
+				.iter()
+				.flat_map(|range| {
+					let last = range.last();
+					std::iter::successors(range.first(), move |t| {
+						if Some(*t) == last {
+							None
+						} else {
+							t.succ()
+						}
+					})
+				})
+				.map(move |t| (t, target))
+		}))
+	}
+}
+
+impl<'a, Q: Ord, T: Token> Iterator for Words<'a, Q, T> {
+	type Item = Vec<T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if std::mem::take(&mut self.empty) {
+			return Some(Vec::new());
+		}
+
+		loop {
+			let (word, successors) = self.queue.front_mut()?;
+			let Some((token, target)) = successors.next() else {
+				self.queue.pop_front();
+				continue;
+			};
+
+			if self.productive.contains(target) {
+				let mut next = word.clone();
+				next.push(token);
+				self.queue
+					.push_back((next.clone(), Self::successors(self.aut, target)));
+
+				if self.aut.is_final_state(target) {
+					break Some(next);
+				}
+			}
+		}
+	}
+}
+
 pub struct ReachableStates<'a, Q, L = AnyRange<char>> {
 	aut: &'a DFA<Q, L>,
 	visited: HashSet<&'a Q>,
@@ -515,3 +1482,462 @@ where
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::BTreeSet, ops::RangeBounds};
+
+	use btree_range_map::{AnyRange, RangeSet};
+
+	use super::{DetTransitions, Step, DFA};
+	use crate::{Automaton, TaggedAutomaton, NFA};
+
+	#[test]
+	fn dead_step() {
+		let mut dfa: DFA<u32, RangeSet<char>> = DFA::new(0);
+		dfa.add(0, ['a'].into_iter().collect(), 1);
+		dfa.add(1, ['b'].into_iter().collect(), 0);
+		dfa.add_final_state(1);
+
+		assert_eq!(dfa.step(&0, 'a'), Step::To(&1));
+		assert_eq!(dfa.step(&0, 'b'), Step::Dead);
+
+		let dead = dfa.step(&1, 'a');
+		assert!(dead.is_dead());
+		assert_eq!(dfa.step_from(dead, 'b'), Step::Dead);
+		assert_eq!(dfa.step_from(dead, 'a'), Step::Dead);
+	}
+
+	#[test]
+	fn total_determinization() {
+		let mut nfa: NFA<u32, char> = NFA::new();
+		nfa.add_initial_state(0);
+		nfa.add(0, Some(RangeSet::from_iter(['a'])), 1);
+		nfa.add(1, Some(RangeSet::from_iter(['b'])), 2);
+		nfa.add_final_state(2);
+
+		let mut alphabet = RangeSet::new();
+		alphabet.insert('a'..='c');
+
+		let partial = nfa.determinize(|qs| qs.clone());
+		assert!(!partial.is_total(&alphabet));
+
+		let total = nfa.determinize_total(&alphabet, |qs| qs.clone());
+		assert!(total.is_total(&alphabet));
+		assert!(total.states().contains(&BTreeSet::new()));
+		assert_eq!(total.final_states().len(), 1);
+	}
+
+	#[test]
+	fn pattern_priority() {
+		let keyword = NFA::singleton("if".chars(), |i| i.map_or(0, |i| i as u32 + 1));
+		let mut ident = NFA::new();
+		let mut letters = RangeSet::new();
+		letters.insert('a'..='z');
+		ident.add_initial_state(0);
+		ident.add(0, Some(letters.clone()), 1);
+		ident.add(1, Some(letters), 1);
+		ident.add_final_state(1);
+
+		let dfa = NFA::from_patterns([keyword, ident]).to_tagged_dfa();
+		let tag = |word: &str| {
+			let q = dfa.run(word.chars())?;
+			dfa.get_tag(&q)
+		};
+
+		assert_eq!(tag("if"), Some(0));
+		assert_eq!(tag("iff"), Some(1));
+		assert_eq!(tag("i"), Some(1));
+		assert_eq!(tag(""), None);
+		assert_eq!(tag("1"), None);
+	}
+
+	#[test]
+	fn edges() {
+		let mut dfa: DFA<u32, char> = DFA::new(0);
+		dfa.add(0, 'a', 1);
+		dfa.add(0, 'b', 2);
+		dfa.add(1, 'b', 2);
+		dfa.declare_state(2);
+
+		let edges: Vec<_> = dfa.edges().collect();
+		assert_eq!(edges, [(&0, &'a', &1), (&0, &'b', &2), (&1, &'b', &2)]);
+		assert_eq!(
+			edges.len(),
+			DetTransitions::from(dfa.transitions().clone()).len()
+		);
+	}
+
+	#[test]
+	fn unreachable_final_states() {
+		let mut dfa: DFA<u32, char> = DFA::new(0);
+		dfa.add(0, 'a', 1);
+		dfa.add(2, 'a', 3);
+		dfa.add_final_state(1);
+		dfa.add_final_state(3);
+
+		assert_eq!(
+			dfa.unreachable_final_states()
+				.into_iter()
+				.collect::<Vec<_>>(),
+			[&3]
+		);
+	}
+
+	#[test]
+	fn equivalence_classes() {
+		// `ab|cb`, where `1` and `2` are equivalent.
+		let mut dfa: DFA<u32, char> = DFA::new(0);
+		dfa.add(0, 'a', 1);
+		dfa.add(0, 'c', 2);
+		dfa.add(1, 'b', 3);
+		dfa.add(2, 'b', 3);
+		dfa.add_final_state(3);
+
+		let partition = dfa.partition(|q| dfa.is_final_state(q));
+		let classes = dfa.equivalence_classes(partition.into_values());
+
+		assert_eq!(classes[&1], classes[&2]);
+		assert_ne!(classes[&0], classes[&1]);
+		assert_ne!(classes[&1], classes[&3]);
+		assert_eq!(
+			dfa.minimize(dfa.partition(|q| dfa.is_final_state(q)).into_values())
+				.states()
+				.len(),
+			3
+		);
+	}
+
+	#[test]
+	fn words() {
+		let digits: RangeSet<char> = ['0', '1'].into_iter().collect();
+
+		// `[01]+`
+		let mut dfa = DFA::new(0);
+		dfa.add(0, digits.clone(), 1);
+		dfa.add(1, digits, 1);
+		dfa.add_final_state(1);
+
+		let words: Vec<String> = dfa
+			.words()
+			.take(6)
+			.map(|w| w.into_iter().collect())
+			.collect();
+		assert_eq!(words, ["0", "1", "00", "01", "10", "11"]);
+
+		// `a|bc`, with a dead state.
+		let mut dfa = DFA::new(0);
+		dfa.add(0, RangeSet::from_iter(['a']), 1);
+		dfa.add(0, RangeSet::from_iter(['b']), 2);
+		dfa.add(2, RangeSet::from_iter(['c']), 1);
+		dfa.add(0, RangeSet::from_iter(['d']), 3);
+		dfa.add(3, RangeSet::from_iter(['d']), 3);
+		dfa.add_final_state(1);
+
+		let words: Vec<String> = dfa.words().map(|w| w.into_iter().collect()).collect();
+		assert_eq!(words, ["a", "bc"]);
+	}
+
+	#[test]
+	fn minimize_owned() {
+		fn minimal() -> DFA<u32, RangeSet<char>> {
+			// `a+|b+` with redundant states.
+			let mut dfa = DFA::new(0);
+			dfa.add(0, RangeSet::from_iter(['a']), 1);
+			dfa.add(0, RangeSet::from_iter(['b']), 2);
+			dfa.add(1, RangeSet::from_iter(['a']), 3);
+			dfa.add(3, RangeSet::from_iter(['a']), 1);
+			dfa.add(2, RangeSet::from_iter(['b']), 2);
+			dfa.add_final_state(1);
+			dfa.add_final_state(2);
+			dfa.add_final_state(3);
+
+			let partition = dfa.partition(|q| dfa.is_final_state(q));
+			dfa.minimize_owned(partition.into_values())
+		}
+
+		let dfa = minimal();
+		assert_eq!(*dfa.initial_state(), 0);
+		assert_eq!(dfa.states().len(), 3);
+		assert!(dfa.contains("aaa".chars()));
+		assert!(dfa.contains("bb".chars()));
+		assert!(!dfa.contains("ab".chars()));
+		assert!(!dfa.contains("".chars()));
+	}
+
+	#[test]
+	fn canonicalize() {
+		let a = || RangeSet::from_iter(['a']);
+		let b = || RangeSet::from_iter(['b']);
+
+		// `a(ba)*`
+		let mut left = DFA::new(0);
+		left.add(0, a(), 1);
+		left.add(1, b(), 2);
+		left.add(2, a(), 1);
+		left.add_final_state(1);
+
+		// `(ab)*a`, with an unreachable state.
+		let mut right = DFA::new(5);
+		right.add(5, a(), 3);
+		right.add(3, b(), 5);
+		right.add(4, a(), 3);
+		right.add_final_state(3);
+
+		assert_eq!(left.canonicalize(), right.canonicalize());
+
+		// `[a-c]`, with split labels and a dead state.
+		let mut left = DFA::new(0);
+		left.add(0, a(), 1);
+		left.add(0, RangeSet::from_iter(['b', 'c']), 2);
+		left.add(0, RangeSet::from_iter(['d']), 3);
+		left.add(3, RangeSet::from_iter(['d']), 3);
+		left.add_final_state(1);
+		left.add_final_state(2);
+
+		let mut right = DFA::new(0);
+		right.add(0, RangeSet::from_iter(['a', 'b', 'c']), 1);
+		right.add_final_state(1);
+
+		let canonical = right.canonicalize();
+		assert_eq!(left.canonicalize(), canonical);
+		assert_eq!(canonical.states().len(), 2);
+
+		right.add_final_state(0);
+		assert_ne!(left.canonicalize(), right.canonicalize());
+	}
+
+	#[test]
+	fn minimize_deterministic() {
+		// `(a|b)*c` with redundant states.
+		let mut dfa = DFA::new(0);
+		for q in 0..4 {
+			dfa.add(q, RangeSet::from_iter(['a']), (q + 1) % 4);
+			dfa.add(q, RangeSet::from_iter(['b']), (q + 2) % 4);
+			dfa.add(q, RangeSet::from_iter(['c']), 4 + q % 2);
+		}
+		dfa.add_final_state(4);
+		dfa.add_final_state(5);
+
+		let minimize = |reverse: bool| {
+			let mut partition: Vec<_> = dfa
+				.partition(|q| dfa.is_final_state(q))
+				.into_values()
+				.collect();
+			if reverse {
+				partition.reverse()
+			}
+
+			dfa.minimize_owned(partition.into_iter())
+		};
+
+		let expected = minimize(false);
+		assert_eq!(expected.states().len(), 2);
+		for i in 0..8 {
+			assert_eq!(minimize(i % 2 == 0).transitions(), expected.transitions());
+		}
+	}
+
+	#[test]
+	fn debug_dump() {
+		let mut dfa = DFA::new(0);
+		dfa.add(0, RangeSet::from_iter(['a']), 1);
+		dfa.add(1, RangeSet::from_iter(['b']), 0);
+		dfa.add_final_state(1);
+
+		let dump = dfa.debug_dump();
+		assert!(dump.starts_with("initial: 0\n"));
+		assert!(dump.contains("0: ['a'] -> 1\n"));
+		assert!(dump.contains("1: ['b'] -> 0\n"));
+	}
+
+	#[test]
+	fn range_automaton() {
+		let mut dfa: DFA<u32, AnyRange<char>> = DFA::new(0);
+		dfa.add(0, AnyRange::from('a'..='c'), 1);
+		dfa.add(0, AnyRange::from('d'..='d'), 2);
+		dfa.add(0, AnyRange::from('x'..='z'), 0);
+		dfa.add(1, AnyRange::from('a'..), 2);
+		dfa.add_final_state(2);
+
+		assert_eq!(dfa.step(&0, 'b'), Step::To(&1));
+		assert_eq!(dfa.step(&0, 'd'), Step::To(&2));
+		assert_eq!(dfa.step(&0, 'e'), Step::Dead);
+		assert_eq!(dfa.step(&2, 'a'), Step::Dead);
+
+		assert!(dfa.contains("d".chars()));
+		assert!(dfa.contains("xyaz".chars()));
+		assert!(dfa.contains("ca".chars()));
+		assert!(!dfa.contains("".chars()));
+		assert!(!dfa.contains("e".chars()));
+		assert!(!dfa.contains("dd".chars()));
+
+		assert!(dfa.matches_str("xd"));
+		assert!(!dfa.matches_str("xe"));
+		assert!(dfa.validate("xd"));
+
+		let nfa: NFA<u32, char> = NFA::singleton("abc".chars(), |i| i.map_or(0, |i| i as u32 + 1));
+		let determinized = nfa.determinize(BTreeSet::clone);
+		assert!(determinized.contains("abc".chars()));
+		assert!(!determinized.contains("ab".chars()));
+		assert!(determinized.matches_str("abc"));
+		assert!(!determinized.matches_str("abcd"));
+	}
+
+	#[test]
+	fn complement() {
+		let mut dfa: DFA<u32, AnyRange<char>> = DFA::new(0);
+		dfa.add(0, AnyRange::from('a'..='a'), 1);
+		dfa.add(1, AnyRange::from('b'..='b'), 2);
+		dfa.add(2, AnyRange::from('a'..='b'), 2);
+		dfa.add_final_state(2);
+
+		let mut alphabet = RangeSet::new();
+		alphabet.insert('a'..='c');
+
+		let complement = dfa.clone().complement(&alphabet);
+		assert!(complement.is_total(&alphabet));
+		assert!(complement.is_final_state(&None));
+
+		let mut words = vec![String::new()];
+		for _ in 0..4 {
+			for word in std::mem::take(&mut words) {
+				for c in 'a'..='c' {
+					let mut word = word.clone();
+					word.push(c);
+					words.push(word);
+				}
+
+				assert_ne!(complement.validate(&word), dfa.validate(&word), "{word}");
+			}
+		}
+
+		assert!(!complement.validate("d"));
+	}
+
+	#[test]
+	fn intersection() {
+		let mut a_m: DFA<u32, AnyRange<char>> = DFA::new(0);
+		a_m.add(0, AnyRange::from('a'..='m'), 0);
+		a_m.add_final_state(0);
+
+		let mut h_z: DFA<u32, AnyRange<char>> = DFA::new(0);
+		h_z.add(0, AnyRange::from('h'..='z'), 0);
+		h_z.add_final_state(0);
+
+		let dfa = a_m.intersection(&h_z);
+		assert_eq!(dfa.initial_state(), &(0, 0));
+
+		for word in ["", "h", "m", "hijklm"] {
+			assert!(dfa.validate(word), "{word}")
+		}
+
+		for word in ["a", "g", "n", "z", "hn", "ah"] {
+			assert!(!dfa.validate(word), "{word}")
+		}
+	}
+
+	#[test]
+	fn union() {
+		let mut a: DFA<u32, AnyRange<char>> = DFA::new(0);
+		a.add(0, AnyRange::from('a'..='a'), 1);
+		a.add_final_state(1);
+
+		let mut bc: DFA<u32, AnyRange<char>> = DFA::new(0);
+		bc.add(0, AnyRange::from('b'..='b'), 1);
+		bc.add(1, AnyRange::from('c'..='c'), 2);
+		bc.add_final_state(2);
+
+		let mut alphabet = RangeSet::new();
+		alphabet.insert('a'..='c');
+
+		let dfa = a.union(&bc, &alphabet);
+		assert!(dfa.validate("a"));
+		assert!(dfa.validate("bc"));
+
+		for word in ["", "b", "c", "ab", "abc", "bca", "bcc", "aa"] {
+			assert!(!dfa.validate(word), "{word}")
+		}
+	}
+
+	#[test]
+	fn complete_with_fresh_sink() {
+		let mut dfa: DFA<u32, AnyRange<char>> = DFA::new(0);
+		dfa.add(0, AnyRange::from('a'..='a'), 1);
+		dfa.add(1, AnyRange::from('b'..='b'), 2);
+		dfa.add_final_state(2);
+
+		let mut alphabet = RangeSet::new();
+		alphabet.insert('a'..='c');
+
+		assert_eq!(dfa.complete_with_fresh_sink(&alphabet), Some(3));
+		assert_eq!(dfa.states(), BTreeSet::from([&0, &1, &2, &3]));
+		assert!(dfa.is_total(&alphabet));
+
+		for q in [0, 1, 2, 3] {
+			for c in 'a'..='c' {
+				assert!(dfa.successors(&q).any(|(range, _)| range.contains(&c)));
+			}
+		}
+
+		for c in 'a'..='c' {
+			assert!(dfa
+				.successors(&3)
+				.any(|(range, r)| range.contains(&c) && *r == 3));
+		}
+
+		assert_eq!(dfa.complete_with_fresh_sink(&alphabet), None);
+	}
+
+	#[test]
+	#[should_panic]
+	fn complete_with_existing_sink() {
+		let mut dfa: DFA<u32, AnyRange<char>> = DFA::new(0);
+		dfa.add(0, AnyRange::from('a'..='a'), 1);
+
+		let mut alphabet = RangeSet::new();
+		alphabet.insert('a'..='c');
+		dfa.complete(&alphabet, 1);
+	}
+
+	#[test]
+	fn transition_fn() {
+		let mut dfa: DFA<u32, RangeSet<char>> = DFA::new(0);
+		dfa.add(0, ['a'].into_iter().collect(), 1);
+		dfa.add(1, ['b'].into_iter().collect(), 2);
+		dfa.add(2, ['c'].into_iter().collect(), 3);
+		dfa.add_final_state(3);
+
+		let delta = dfa.transition_fn();
+		let mut q = dfa.initial_state();
+		for c in "abc".chars() {
+			q = delta(q, c).unwrap();
+		}
+
+		assert!(dfa.is_final_state(q));
+		assert_eq!(delta(&0, 'b'), None);
+	}
+
+	#[test]
+	fn to_table() {
+		let mut dfa: DFA<u32, RangeSet<char>> = DFA::new(0);
+		dfa.add(0, RangeSet::from_iter(['a']), 1);
+		dfa.add(0, RangeSet::from_iter(['b']), 2);
+		dfa.add(1, RangeSet::from_iter(['a'..='b']), 2);
+		dfa.add(2, RangeSet::from_iter(['c'..='z']), 2);
+		dfa.add_final_state(2);
+
+		let table = dfa.to_table();
+		let rows: Vec<_> = table.lines().collect();
+		assert_eq!(
+			rows,
+			[
+				"| | 'a' | 'b' | 'c'-'z' |",
+				"|---|---|---|---|",
+				"| -> 0 | 1 | 2 | |",
+				"| 1 | 2 | 2 | |",
+				"| * 2 | | | 2 |",
+			]
+		);
+	}
+}
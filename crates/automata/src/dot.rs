@@ -1,11 +1,11 @@
-use std::{fmt, ops::Bound};
+use std::{collections::BTreeMap, fmt, ops::Bound};
 
 use btree_range_map::{AnyRange, Directed, RangeSet};
 
-use crate::NFA;
+use crate::{ControlEscape, NFA};
 
 pub trait DotDisplay {
-	fn dot(&self) -> DotDisplayed<Self> {
+	fn dot(&self) -> DotDisplayed<'_, Self> {
 		DotDisplayed(self)
 	}
 
@@ -27,19 +27,22 @@ impl<'a, T: ?Sized + DotDisplay> fmt::Display for DotDisplayed<'a, T> {
 }
 
 pub trait DotLabelDisplay {
-	fn dot_label(&self) -> DotLabelDisplayed<Self> {
+	fn dot_label(&self) -> DotLabelDisplayed<'_, Self> {
 		DotLabelDisplayed(self)
 	}
 
 	fn dot_label_fmt(&self, f: &mut fmt::Formatter) -> fmt::Result;
 }
 
+/// Characters are displayed as in regular expressions, with backslashes
+/// escaped for DOT.
 impl DotLabelDisplay for char {
 	fn dot_label_fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		if self.is_ascii_graphic() {
-			fmt::Display::fmt(self, f)
-		} else {
-			write!(f, "\\\\u{{{:x}}}", *self as u32)
+		match ControlEscape::new(*self) {
+			Some(escape) => write!(f, "\\{escape}"),
+			None if matches!(self, '"' | '\\') => write!(f, "\\{self}"),
+			None if self.is_ascii_graphic() => fmt::Display::fmt(self, f),
+			None => write!(f, "\\\\u{{{:x}}}", *self as u32),
 		}
 	}
 }
@@ -56,13 +59,13 @@ impl DotLabelDisplay for u32 {
 	}
 }
 
-impl<'a, T: DotLabelDisplay> DotLabelDisplay for &'a T {
+impl<T: DotLabelDisplay> DotLabelDisplay for &T {
 	fn dot_label_fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		T::dot_label_fmt(*self, f)
 	}
 }
 
-impl<'a, T: DotLabelDisplay> DotLabelDisplay for Directed<&'a Bound<T>> {
+impl<T: DotLabelDisplay> DotLabelDisplay for Directed<&Bound<T>> {
 	fn dot_label_fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match self {
 			Directed::Start(Bound::Unbounded) => Ok(()),
@@ -122,20 +125,100 @@ impl<T: DotLabelDisplay, Q: DotDisplay + DotLabelDisplay> DotDisplay for NFA<Q,
 			writeln!(f, "\t{} [label = \"{}\"]", q.dot(), q.dot_label())?;
 		}
 
-		for (q, transitions) in self.transitions() {
-			for (label, targets) in transitions {
-				for r in targets {
-					writeln!(
-						f,
-						"\t{} -> {} [label = \"{}\"]",
-						q.dot(),
-						r.dot(),
-						label.dot_label()
-					)?;
-				}
-			}
+		for (q, label, r) in self.iter_edges() {
+			writeln!(
+				f,
+				"\t{} -> {} [label = \"{}\"]",
+				q.dot(),
+				r.dot(),
+				label.dot_label()
+			)?;
 		}
 
 		write!(f, "}}")
 	}
 }
+
+impl<Q: Ord, T> NFA<Q, T> {
+	/// Returns a DOT representation of the automaton, using the given
+	/// function to label states.
+	///
+	/// Unlike [`DotDisplay`], this does not require `Q` to implement
+	/// [`DotDisplay`] or [`DotLabelDisplay`]. Nodes are identified by the
+	/// rank of their state.
+	pub fn dot_with_labels<F>(&self, label: F) -> DotWithLabels<'_, Q, T, F>
+	where
+		F: Fn(&Q) -> String,
+	{
+		DotWithLabels { aut: self, label }
+	}
+}
+
+/// DOT representation of an NFA with custom state labels.
+///
+/// See [`NFA::dot_with_labels`].
+pub struct DotWithLabels<'a, Q, T, F> {
+	aut: &'a NFA<Q, T>,
+	label: F,
+}
+
+impl<'a, Q, T, F> fmt::Display for DotWithLabels<'a, Q, T, F>
+where
+	Q: Ord,
+	T: DotLabelDisplay,
+	F: Fn(&Q) -> String,
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let ids: BTreeMap<&Q, u32> = self.aut.states().zip(0..).collect();
+
+		writeln!(f, "digraph {{")?;
+
+		for (q, id) in &ids {
+			writeln!(f, "\t{} [label = \"{}\"]", id.dot(), (self.label)(q))?;
+		}
+
+		for (q, label, r) in self.aut.iter_edges() {
+			writeln!(
+				f,
+				"\t{} -> {} [label = \"{}\"]",
+				ids[q].dot(),
+				ids[r].dot(),
+				label.dot_label()
+			)?;
+		}
+
+		write!(f, "}}")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::DotDisplay;
+	use crate::NFA;
+
+	#[test]
+	fn dot_with_labels() {
+		let a = NFA::<u32, char>::singleton("ab".chars(), |i| i.map_or(0, |i| i as u32 + 1));
+		let b = NFA::<u32, char>::singleton("ab".chars(), |i| i.map_or(0, |i| i as u32 + 1));
+		let product = a.product(&b, |p, q| (*p, *q));
+
+		let dot = product
+			.dot_with_labels(|(p, q)| format!("({p}, {q})"))
+			.to_string();
+
+		assert!(dot.starts_with("digraph {"));
+		assert!(dot.contains("[label = \"(2, 2)\"]"));
+		assert!(dot.contains("q0 -> q1 [label = \"a..=a\"]"));
+	}
+
+	#[test]
+	fn control_chars() {
+		let aut =
+			NFA::<u32, char>::singleton("\n\x01\"".chars(), |i| i.map_or(0, |i| i as u32 + 1));
+		let dot = aut.dot().to_string();
+
+		assert!(dot.contains(r#"q0 -> q1 [label = "\\n..=\\n"]"#));
+		assert!(dot.contains(r#"q1 -> q2 [label = "\\u{1}..=\\u{1}"]"#));
+		assert!(dot.contains(r#"q2 -> q3 [label = "\"..=\""]"#));
+	}
+}
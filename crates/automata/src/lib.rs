@@ -6,6 +6,7 @@
 //! [`iregex`]: <https://github.com/timothee-haudebourg/iregex-rs>
 use btree_range_map::RangePartialOrd;
 pub use btree_range_map::{AnyRange, RangeSet};
+use std::fmt;
 
 pub mod nfa;
 use mown::Mown;
@@ -18,6 +19,20 @@ use range_traits::{Bounded, Measure, PartialEnum};
 #[cfg(feature = "dot")]
 pub mod dot;
 
+#[cfg(feature = "regex-automata")]
+mod regex_automata;
+#[cfg(feature = "regex-automata")]
+pub use regex_automata::ExportError;
+
+/// Number of Unicode scalar values, the [`char`] values.
+///
+/// This is the cardinality of [`any_char`]: every code point up to
+/// `U+10FFFF`, except the `0x800` surrogate code points `U+D800..=U+DFFF`.
+pub const CHAR_COUNT: u64 = 0x110000 - 0x800;
+
+/// Returns the set of all characters.
+///
+/// This set has [`CHAR_COUNT`] elements.
 pub fn any_char() -> RangeSet<char> {
 	let mut set = RangeSet::new();
 	set.insert('\u{0}'..='\u{d7ff}');
@@ -25,6 +40,73 @@ pub fn any_char() -> RangeSet<char> {
 	set
 }
 
+/// Returns the set of all characters not in the given set.
+///
+/// Unlike the gaps of `set`, the result never includes the surrogate code
+/// points `U+D800..=U+DFFF`, which are not characters.
+pub fn negate_char_set(set: &RangeSet<char>) -> RangeSet<char> {
+	let mut result = any_char();
+
+	for range in set {
+		result.remove(*range);
+	}
+
+	result
+}
+
+/// Escape sequence of a control character.
+///
+/// Displays as the common escape sequence of the character when it has one
+/// (`\n`, `\t`, `\e`, etc.), or as its code point (`\u{1}`). Every renderer
+/// uses it, so that they all agree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlEscape(char);
+
+impl ControlEscape {
+	/// Returns the escape sequence of the given character, if it is a control
+	/// character.
+	pub fn new(c: char) -> Option<Self> {
+		c.is_control().then_some(Self(c))
+	}
+}
+
+impl fmt::Display for ControlEscape {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.0 {
+			'\0' => f.write_str("\\0"),
+			'\x07' => f.write_str("\\a"),
+			'\x08' => f.write_str("\\b"),
+			'\t' => f.write_str("\\t"),
+			'\n' => f.write_str("\\n"),
+			'\x0b' => f.write_str("\\v"),
+			'\x0c' => f.write_str("\\f"),
+			'\r' => f.write_str("\\r"),
+			'\x1b' => f.write_str("\\e"),
+			c => write!(f, "\\u{{{:x}}}", c as u32),
+		}
+	}
+}
+
+/// Character as it appears in a regular expression.
+///
+/// Displays metacharacters escaped with a backslash, and control characters
+/// as their [`ControlEscape`]. Other characters are displayed as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscapedChar(pub char);
+
+impl fmt::Display for EscapedChar {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.0 {
+			c @ ('\\' | '(' | ')' | '[' | ']' | '{' | '}' | '?' | '*' | '+' | '|' | '^' | '$'
+			| '.' | '-') => write!(f, "\\{c}"),
+			c => match ControlEscape::new(c) {
+				Some(escape) => escape.fmt(f),
+				None => fmt::Display::fmt(&c, f),
+			},
+		}
+	}
+}
+
 /// Computes the intersection of two character sets.
 pub fn token_set_intersection<T>(a: &RangeSet<T>, b: &RangeSet<T>) -> RangeSet<T>
 where
@@ -83,8 +165,113 @@ impl Token for char {
 	}
 }
 
+#[doc(hidden)]
+pub mod __private {
+	pub use btree_range_map;
+	pub use range_traits;
+}
+
+/// Implements [`Token`] for a newtype over an unsigned integer type.
+///
+/// `impl_token!(Type(Int), len)` implements [`Token`], along with the range
+/// traits it requires, for the tuple struct `Type(Int)`, which must already
+/// implement `Copy` and `Ord`. Every value of `Int` is a token, and `len` is
+/// the (byte) length of each token, as returned by [`Token::len`].
+///
+/// # Example
+///
+/// ```
+/// # use iregex_automata::{impl_token, Automaton, NFA};
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// struct Symbol(u16);
+///
+/// impl_token!(Symbol(u16), 2);
+///
+/// let aut = NFA::singleton([Symbol(1), Symbol(2)], |q| q);
+/// assert!(aut.contains([Symbol(1), Symbol(2)]));
+/// ```
+#[macro_export]
+macro_rules! impl_token {
+	($ty:ident($int:ty), $len:expr) => {
+		impl $crate::__private::range_traits::Bounded for $ty {
+			fn min() -> Self {
+				Self(<$int>::MIN)
+			}
+
+			fn max() -> Self {
+				Self(<$int>::MAX)
+			}
+		}
+
+		impl $crate::__private::range_traits::PartialEnum for $ty {
+			fn pred(&self) -> Option<Self> {
+				$crate::__private::range_traits::PartialEnum::pred(&self.0).map(Self)
+			}
+
+			fn succ(&self) -> Option<Self> {
+				$crate::__private::range_traits::PartialEnum::succ(&self.0).map(Self)
+			}
+		}
+
+		impl $crate::__private::range_traits::Measure for $ty {
+			type Len = <$int as $crate::__private::range_traits::Measure>::Len;
+
+			fn len(&self) -> Self::Len {
+				<$int as $crate::__private::range_traits::Measure>::len(&self.0)
+			}
+
+			fn distance(&self, other: &Self) -> Self::Len {
+				<$int as $crate::__private::range_traits::Measure>::distance(&self.0, &other.0)
+			}
+		}
+
+		impl $crate::__private::btree_range_map::AsRange for $ty {
+			type Item = Self;
+
+			fn start(&self) -> ::std::ops::Bound<&Self> {
+				::std::ops::Bound::Included(self)
+			}
+
+			fn end(&self) -> ::std::ops::Bound<&Self> {
+				::std::ops::Bound::Included(self)
+			}
+		}
+
+		impl $crate::__private::btree_range_map::IntoRange for $ty {
+			fn into_range(self) -> $crate::AnyRange<Self> {
+				$crate::AnyRange::new(
+					::std::ops::Bound::Included(self),
+					::std::ops::Bound::Included(self),
+				)
+			}
+		}
+
+		impl $crate::Token for $ty {
+			fn all() -> $crate::RangeSet<Self> {
+				let mut set = $crate::RangeSet::new();
+				set.insert(Self(<$int>::MIN)..=Self(<$int>::MAX));
+				set
+			}
+
+			fn len(&self) -> usize {
+				$len
+			}
+
+			fn is_one(len: Self::Len) -> bool {
+				len == 1
+			}
+		}
+	};
+}
+
 /// Token class.
 pub trait Class<T = char>: MapSource {
+	/// Whether or not [`Self::next_class`] always returns the current class,
+	/// as for the `()` class.
+	///
+	/// Matching then skips the per-token class computation.
+	const CONSTANT: bool = false;
+
 	/// Classify the given token set.
 	///
 	/// The output is a partition of the input set, where each member of the
@@ -156,6 +343,8 @@ impl MapSource for () {
 }
 
 impl<T> Class<T> for () {
+	const CONSTANT: bool = true;
+
 	fn classify<'a>(&self, set: &'a RangeSet<T>) -> Self::Map<Mown<'a, RangeSet<T>>> {
 		Unmapped(Some(Mown::Borrowed(set)))
 	}
@@ -244,28 +433,101 @@ pub trait Automaton<T> {
 		&'a self,
 		current_state: Self::State<'a>,
 		token: T,
-	) -> Option<Self::State<'_>>;
+	) -> Option<Self::State<'a>>;
 
 	fn is_final_state<'a>(&'a self, state: &Self::State<'a>) -> bool;
 
-	fn contains(&self, tokens: impl IntoIterator<Item = T>) -> bool {
-		match self.initial_state() {
-			Some(mut q) => {
-				for token in tokens {
-					match self.next_state(q, token) {
-						Some(r) => q = r,
-						None => return false,
-					}
-				}
-
-				self.is_final_state(&q)
-			}
-			None => false,
+	/// Checks if every continuation is accepted from the given state.
+	///
+	/// This check is conservative, allowing matchers to stop reading early:
+	/// it may return `false` even if every continuation is accepted. The
+	/// default implementation always returns `false`.
+	fn accepts_any_continuation<'a>(&'a self, _state: &Self::State<'a>) -> bool {
+		false
+	}
+
+	/// Runs the automaton on the given tokens and returns the reached state,
+	/// or `None` if the tokens are rejected before the end.
+	fn run(&self, tokens: impl IntoIterator<Item = T>) -> Option<Self::State<'_>> {
+		let mut q = self.initial_state()?;
+
+		for token in tokens {
+			q = self.next_state(q, token)?;
 		}
+
+		Some(q)
+	}
+
+	fn contains(&self, tokens: impl IntoIterator<Item = T>) -> bool {
+		self.run(tokens).map_or(false, |q| self.is_final_state(&q))
 	}
 }
 
-/// Deterministic or non-deterministic automaton.
+/// Deterministic or non-deterministic automaton with tagged states.
 pub trait TaggedAutomaton<T, G>: Automaton<T> {
-	fn get_tag(&self, state: &G) -> Option<usize>;
+	/// Returns the tag of the given state, if any.
+	fn get_tag<'a>(&'a self, state: &Self::State<'a>) -> Option<G>;
+}
+
+#[cfg(test)]
+mod tests {
+	use btree_range_map::RangeSet;
+
+	use crate::{any_char, Automaton, Token, CHAR_COUNT, NFA};
+
+	#[test]
+	fn char_count() {
+		assert_eq!(CHAR_COUNT, 0x110000 - 0x800);
+		assert_eq!(
+			(0..=0x10ffff).filter_map(char::from_u32).count() as u64,
+			CHAR_COUNT
+		);
+		assert_eq!(
+			any_char()
+				.iter()
+				.map(|range| (range.first().unwrap()..=range.last().unwrap()).count() as u64)
+				.sum::<u64>(),
+			CHAR_COUNT
+		);
+	}
+
+	#[test]
+	fn negate_char_set() {
+		let count = |set: &RangeSet<char>| {
+			set.iter()
+				.map(|range| (range.first().unwrap()..=range.last().unwrap()).count() as u64)
+				.sum::<u64>()
+		};
+
+		let negated = super::negate_char_set(&RangeSet::from_iter(['a']));
+		assert!(!negated.contains('a'));
+		assert!(negated.contains('b'));
+		assert!(negated.contains('\u{d7ff}'));
+		assert!(negated.contains('\u{e000}'));
+		assert!(negated.contains(char::MAX));
+		assert_eq!(count(&negated), CHAR_COUNT - 1);
+
+		assert!(super::negate_char_set(&any_char()).is_empty());
+		assert_eq!(super::negate_char_set(&RangeSet::new()), any_char());
+	}
+
+	#[test]
+	fn impl_token() {
+		#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+		struct Letter(u32);
+
+		crate::impl_token!(Letter(u32), 4);
+
+		let aut: NFA<usize, Letter> = NFA::singleton([Letter(1), Letter(2), Letter(3)], |q| {
+			q.map_or(0, |i| i + 1)
+		});
+		assert!(aut.contains([Letter(1), Letter(2), Letter(3)]));
+		assert!(!aut.contains([Letter(1), Letter(2)]));
+		assert!(!aut.contains([Letter(1), Letter(2), Letter(4)]));
+
+		let dfa = aut.to_minimal_dfa();
+		assert!(dfa.contains([Letter(1), Letter(2), Letter(3)]));
+		assert_eq!(Token::len(&Letter(0)), 4);
+		assert!(Letter::all().contains(Letter(u32::MAX)));
+	}
 }
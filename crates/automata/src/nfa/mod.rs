@@ -2,19 +2,27 @@ use btree_range_map::{AnyRange, RangeMap, RangeSet};
 use educe::Educe;
 use range_traits::{Enum, Measure};
 use std::{
-	collections::{BTreeMap, BTreeSet, HashSet},
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet},
 	hash::Hash,
 	ops::Bound,
 };
 
-use crate::{dfa::DetTransitions, Automaton, Class, Map, Token, DFA};
+use crate::{
+	dfa::{DetTransitions, TaggedDFA},
+	Automaton, Class, Map, Token, DFA,
+};
 
 use super::token_set_intersection;
 
 mod tags;
 pub use tags::{TaggedNFA, Tags};
 
-#[derive(Debug)]
+mod utf8;
+#[cfg(feature = "regex-automata")]
+pub(crate) use utf8::utf8_sequences;
+
+#[derive(Debug, thiserror::Error)]
+#[error("too many states")]
 pub struct TooManyStates;
 
 /// State builder.
@@ -26,7 +34,7 @@ pub trait StateBuilder<T, Q, C = ()> {
 	fn class_of(&self, q: &Q) -> Option<&C>;
 }
 
-impl<'a, T, Q, C, S: StateBuilder<T, Q, C>> StateBuilder<T, Q, C> for &'a mut S {
+impl<T, Q, C, S: StateBuilder<T, Q, C>> StateBuilder<T, Q, C> for &mut S {
 	type Error = S::Error;
 
 	fn next_state(&mut self, nfa: &mut NFA<Q, T>, class: C) -> Result<Q, Self::Error> {
@@ -88,17 +96,20 @@ where
 		mut state_builder: S,
 		class: C,
 	) -> Result<TaggedNFA<Q, T, G>, S::Error> {
-		let mut nfa = NFA::new();
 		let mut tags = Tags::new();
-		let (a, bs) = self.build_nfa_from(&mut state_builder, &mut nfa, &mut tags, &class)?;
-		nfa.add_initial_state(a);
-		for (_, b) in bs.into_entries() {
-			nfa.add_final_state(b);
-		}
-
+		let nfa = build_nfa_with_tags(self, &mut state_builder, &mut tags, &class)?;
 		Ok(TaggedNFA::new(nfa, tags))
 	}
 
+	/// Builds the NFA without constructing its tags.
+	fn build_untagged_nfa<S: StateBuilder<T, Q, C>>(
+		&self,
+		mut state_builder: S,
+		class: C,
+	) -> Result<NFA<Q, T>, S::Error> {
+		build_nfa_with_tags(self, &mut state_builder, &mut Tags::ignored(), &class)
+	}
+
 	fn build_nfa_from<S: StateBuilder<T, Q, C>>(
 		&self,
 		state_builder: &mut S,
@@ -108,6 +119,28 @@ where
 	) -> Result<(Q, C::Map<Q>), S::Error>;
 }
 
+fn build_nfa_with_tags<T, Q, C, G, S>(
+	value: &(impl BuildNFA<T, Q, C, G> + ?Sized),
+	state_builder: &mut S,
+	tags: &mut Tags<Q, G>,
+	class: &C,
+) -> Result<NFA<Q, T>, S::Error>
+where
+	T: Clone,
+	Q: Ord,
+	C: Class<T>,
+	S: StateBuilder<T, Q, C>,
+{
+	let mut nfa = NFA::new();
+	let (a, bs) = value.build_nfa_from(state_builder, &mut nfa, tags, class)?;
+	nfa.add_initial_state(a);
+	for (_, b) in bs.into_entries() {
+		nfa.add_final_state(b);
+	}
+
+	Ok(nfa)
+}
+
 /// Nondeterministic state transitions.
 pub type Transitions<T, Q> = BTreeMap<Option<RangeSet<T>>, BTreeSet<Q>>;
 
@@ -153,14 +186,28 @@ impl<T, Q> NFA<Q, T> {
 	}
 
 	/// Returns an iterator over the transitions.
-	pub fn transitions(&self) -> std::collections::btree_map::Iter<Q, Transitions<T, Q>> {
+	pub fn transitions(&self) -> std::collections::btree_map::Iter<'_, Q, Transitions<T, Q>> {
 		self.transitions.iter()
 	}
+
+	/// Returns an iterator over the edges of the automaton, as
+	/// `(source, label, target)` triples.
+	///
+	/// Epsilon transitions have no label.
+	pub fn iter_edges(&self) -> impl '_ + Iterator<Item = (&Q, Option<&RangeSet<T>>, &Q)> {
+		self.transitions.iter().flat_map(|(source, transitions)| {
+			transitions.iter().flat_map(move |(label, targets)| {
+				targets
+					.iter()
+					.map(move |target| (source, label.as_ref(), target))
+			})
+		})
+	}
 }
 
 impl<T, Q: Ord> NFA<Q, T> {
 	/// Get the successors of the given state.
-	pub fn successors(&self, q: &Q) -> Successors<T, Q> {
+	pub fn successors(&self, q: &Q) -> Successors<'_, T, Q> {
 		Successors::new(self.transitions.get(q))
 	}
 
@@ -189,9 +236,101 @@ impl<T, Q: Ord> NFA<Q, T> {
 	pub fn add_final_state(&mut self, q: Q) -> bool {
 		self.final_states.insert(q)
 	}
+
+	/// Returns the set of states reachable from an initial state.
+	pub fn reachable_states(&self) -> BTreeSet<&Q> {
+		let mut stack: Vec<_> = self.initial_states.iter().collect();
+		let mut visited = BTreeSet::new();
+
+		while let Some(q) = stack.pop() {
+			if visited.insert(q) {
+				if let Some(transitions) = self.transitions.get(q) {
+					stack.extend(transitions.values().flatten())
+				}
+			}
+		}
+
+		visited
+	}
+
+	/// Returns the set of states from which a final state is reachable.
+	pub fn productive_states(&self) -> BTreeSet<&Q> {
+		let mut result: BTreeSet<&Q> = self.final_states.iter().collect();
+
+		loop {
+			let mut changed = false;
+
+			for (q, transitions) in &self.transitions {
+				if !result.contains(q) && transitions.values().flatten().any(|r| result.contains(r))
+				{
+					result.insert(q);
+					changed = true;
+				}
+			}
+
+			if !changed {
+				break result;
+			}
+		}
+	}
+
+	/// Returns the final states that are not reachable from any initial
+	/// state.
+	///
+	/// Such states usually indicate a construction bug, since they cannot
+	/// contribute to the recognized language.
+	pub fn unreachable_final_states(&self) -> BTreeSet<&Q> {
+		let reachable = self.reachable_states();
+		self.final_states
+			.iter()
+			.filter(|q| !reachable.contains(q))
+			.collect()
+	}
 }
 
 impl<T: Token, Q: Ord> NFA<Q, T> {
+	/// Creates an automaton recognizing no word at all.
+	pub fn empty_language() -> Self {
+		Self::new()
+	}
+
+	/// Checks if this automaton recognizes no word at all, not even the empty
+	/// word.
+	///
+	/// Transitions labeled with an empty token set are ignored, since they
+	/// can never be taken.
+	pub fn is_empty_language(&self) -> bool {
+		let mut stack: Vec<_> = self.initial_states.iter().collect();
+		let mut visited = BTreeSet::new();
+
+		while let Some(q) = stack.pop() {
+			if visited.insert(q) {
+				if self.final_states.contains(q) {
+					return false;
+				}
+
+				if let Some(transitions) = self.transitions.get(q) {
+					for (label, targets) in transitions {
+						if label.as_ref().map_or(true, |label| !label.is_empty()) {
+							stack.extend(targets)
+						}
+					}
+				}
+			}
+		}
+
+		true
+	}
+
+	/// Creates an automaton recognizing every word over the given alphabet,
+	/// using `Q::default()` as unique state.
+	pub fn universal(alphabet: RangeSet<T>) -> Self
+	where
+		Q: Clone + Default,
+	{
+		Self::simple_loop(Q::default(), alphabet)
+	}
+
 	pub fn singleton(
 		list: impl IntoIterator<Item = T>,
 		mut next_state: impl FnMut(Option<usize>) -> Q,
@@ -242,6 +381,123 @@ impl<T: Token, Q: Ord> NFA<Q, T> {
 			.insert(target);
 	}
 
+	/// Adds an epsilon transition from `source` to `target`.
+	pub fn insert_epsilon(&mut self, source: Q, target: Q)
+	where
+		Q: Clone,
+	{
+		self.add(source, None, target)
+	}
+
+	/// Removes the given transition from the automaton.
+	///
+	/// The source and target states are kept, even if they are not the
+	/// source or destination of any other transition. Returns `true` if the
+	/// transition was present.
+	pub fn remove_transition(
+		&mut self,
+		source: &Q,
+		label: &Option<RangeSet<T>>,
+		target: &Q,
+	) -> bool {
+		let Some(transitions) = self.transitions.get_mut(source) else {
+			return false;
+		};
+
+		let Some(targets) = transitions.get_mut(label) else {
+			return false;
+		};
+
+		let removed = targets.remove(target);
+		if targets.is_empty() {
+			transitions.remove(label);
+		}
+
+		removed
+	}
+
+	/// Removes the given state from the automaton, along with every
+	/// transition from or to it.
+	///
+	/// Returns `true` if the state was present.
+	pub fn remove_state(&mut self, q: &Q) -> bool {
+		let mut removed = self.transitions.remove(q).is_some();
+		removed |= self.initial_states.remove(q);
+		removed |= self.final_states.remove(q);
+
+		for transitions in self.transitions.values_mut() {
+			transitions.retain(|_, targets| {
+				removed |= targets.remove(q);
+				!targets.is_empty()
+			});
+		}
+
+		removed
+	}
+
+	/// Maps the label of every transition through `g`.
+	///
+	/// States and epsilon transitions are preserved. Transitions between the
+	/// same states whose labels are mapped to the same set are merged.
+	pub fn map_labels<U: Token>(&self, mut g: impl FnMut(&RangeSet<T>) -> RangeSet<U>) -> NFA<Q, U>
+	where
+		Q: Clone,
+	{
+		let transitions = self
+			.transitions
+			.iter()
+			.map(|(q, transitions)| {
+				let mut mapped: Transitions<U, Q> = BTreeMap::new();
+
+				for (label, targets) in transitions {
+					mapped
+						.entry(label.as_ref().map(&mut g))
+						.or_default()
+						.extend(targets.iter().cloned());
+				}
+
+				(q.clone(), mapped)
+			})
+			.collect();
+
+		NFA {
+			transitions,
+			initial_states: self.initial_states.clone(),
+			final_states: self.final_states.clone(),
+		}
+	}
+
+	/// Returns the set of tokens that can be read first, from an initial
+	/// state modulo epsilon transitions.
+	pub fn first_set(&self) -> RangeSet<T> {
+		let mut result = RangeSet::new();
+
+		for q in self.modulo_epsilon_state(&self.initial_states) {
+			for (label, _) in self.successors(q) {
+				if let Some(label) = label {
+					result.extend(label.iter().copied());
+				}
+			}
+		}
+
+		result
+	}
+
+	/// Computes the coarsest partition of the tokens appearing in the
+	/// transition labels such that every label is a union of partition
+	/// blocks.
+	///
+	/// Two tokens belong to the same block if and only if they appear in
+	/// exactly the same labels. Tokens appearing in no label are not part of
+	/// the partition.
+	pub fn symbolic_alphabet(&self) -> Vec<RangeSet<T>> {
+		symbolic_alphabet(
+			self.transitions
+				.values()
+				.flat_map(|transitions| transitions.keys().flatten()),
+		)
+	}
+
 	/// Checks if this automaton can recognize the empty string.
 	pub fn recognizes_empty(&self) -> bool {
 		let mut stack: Vec<_> = self.initial_states.iter().collect();
@@ -264,6 +520,22 @@ impl<T: Token, Q: Ord> NFA<Q, T> {
 		false
 	}
 
+	/// Checks if the empty string is the only word recognized by this
+	/// automaton.
+	pub fn is_only_empty(&self) -> bool {
+		if !self.recognizes_empty() {
+			return false;
+		}
+
+		let productive = self.productive_states();
+		self.reachable_states().into_iter().all(|q| {
+			self.successors(q).all(|(label, targets)| match label {
+				Some(label) => label.is_empty() || !targets.iter().any(|r| productive.contains(r)),
+				None => true,
+			})
+		})
+	}
+
 	/// Checks if this automaton recognizes exactly one string.
 	pub fn is_singleton(&self) -> bool
 	where
@@ -532,10 +804,57 @@ impl<T: Token, Q: Ord> NFA<Q, T> {
 	}
 
 	/// Turns this NFA into a DFA.
-	pub fn determinize<'a, R>(
+	///
+	/// The output DFA is partial: a token without successor in a given state
+	/// has no transition.
+	pub fn determinize<'a, R>(&'a self, f: impl FnMut(&BTreeSet<&'a Q>) -> R) -> DFA<R, AnyRange<T>>
+	where
+		R: Clone + Ord + Hash,
+	{
+		self.determinize_with(None, None, f)
+			.expect("unbounded determinization")
+	}
+
+	/// Turns this NFA into a DFA, like [`Self::determinize`], failing if
+	/// the DFA would have more than `limit` states.
+	///
+	/// The subset construction may produce exponentially many states. This
+	/// function aborts as soon as the limit is exceeded instead of exhausting
+	/// memory.
+	pub fn determinize_bounded<'a, R>(
 		&'a self,
-		mut f: impl FnMut(&BTreeSet<&'a Q>) -> R,
+		limit: usize,
+		f: impl FnMut(&BTreeSet<&'a Q>) -> R,
+	) -> Result<DFA<R, AnyRange<T>>, TooManyStates>
+	where
+		R: Clone + Ord + Hash,
+	{
+		self.determinize_with(None, Some(limit), f)
+	}
+
+	/// Turns this NFA into a DFA whose transitions are total over the given
+	/// alphabet.
+	///
+	/// Tokens of the alphabet without successor lead to an explicit sink state,
+	/// the image of the empty set of states by `f`.
+	pub fn determinize_total<'a, R>(
+		&'a self,
+		alphabet: &RangeSet<T>,
+		f: impl FnMut(&BTreeSet<&'a Q>) -> R,
 	) -> DFA<R, AnyRange<T>>
+	where
+		R: Clone + Ord + Hash,
+	{
+		self.determinize_with(Some(alphabet), None, f)
+			.expect("unbounded determinization")
+	}
+
+	fn determinize_with<'a, R>(
+		&'a self,
+		alphabet: Option<&RangeSet<T>>,
+		limit: Option<usize>,
+		mut f: impl FnMut(&BTreeSet<&'a Q>) -> R,
+	) -> Result<DFA<R, AnyRange<T>>, TooManyStates>
 	where
 		R: Clone + Ord + Hash,
 	{
@@ -550,27 +869,239 @@ impl<T: Token, Q: Ord> NFA<Q, T> {
 		while let Some(det_q) = stack.pop() {
 			let r = f(&det_q);
 			if visited_states.insert(r.clone()) {
+				if limit.map_or(false, |limit| visited_states.len() > limit) {
+					return Err(TooManyStates);
+				}
+
 				if det_q.iter().any(|q| self.final_states.contains(q)) {
 					final_states.insert(r.clone());
 				}
 
 				let map = self.determinize_transitions_for(&det_q);
+				let mut missing = alphabet.cloned().unwrap_or_default();
 
 				let mut r_map = BTreeMap::new();
 				for (label, next_det_q) in map {
+					missing.remove(label);
 					r_map.insert(label, f(&next_det_q));
 					stack.push(next_det_q)
 				}
 
+				if !missing.is_empty() {
+					let sink = BTreeSet::new();
+					for range in &missing {
+						r_map.insert(*range, f(&sink));
+					}
+					stack.push(sink)
+				}
+
 				transitions.insert(r, r_map);
 			}
 		}
 
-		DFA::from_parts(
+		Ok(DFA::from_parts(
 			f(&initial_state),
 			final_states,
 			DetTransitions::from(transitions),
-		)
+		))
+	}
+
+	/// Turns this NFA into a DFA by iterating over the blocks of its
+	/// [symbolic alphabet](Self::symbolic_alphabet) rather than over
+	/// individual ranges.
+	///
+	/// Since every label is a union of blocks, the successors of a set of
+	/// states only need to be computed once per block. All the blocks leading
+	/// to the same state are merged into a single label.
+	pub fn determinize_symbolic<'a, R>(
+		&'a self,
+		mut f: impl FnMut(&BTreeSet<&'a Q>) -> R,
+	) -> DFA<R, RangeSet<T>>
+	where
+		R: Clone + Ord + Hash,
+	{
+		let blocks: Vec<_> = self
+			.symbolic_alphabet()
+			.into_iter()
+			.filter_map(|block| {
+				let token = block.iter().next()?.first()?;
+				Some((token, block))
+			})
+			.collect();
+
+		let initial_state = self.modulo_epsilon_state(&self.initial_states);
+		let mut result = DFA::new(f(&initial_state));
+
+		let mut visited_states = HashSet::new();
+		let mut stack = vec![initial_state];
+		while let Some(det_q) = stack.pop() {
+			let r = f(&det_q);
+			if visited_states.insert(r.clone()) {
+				if det_q.iter().any(|q| self.final_states.contains(q)) {
+					result.add_final_state(r.clone());
+				}
+
+				let mut labels: BTreeMap<BTreeSet<&Q>, RangeSet<T>> = BTreeMap::new();
+				for (token, block) in &blocks {
+					let targets = det_q.iter().flat_map(|q| {
+						self.successors(q)
+							.filter(|(label, _)| {
+								label.as_ref().map_or(false, |l| l.contains(*token))
+							})
+							.flat_map(|(_, targets)| targets)
+					});
+
+					let next_det_q = self.modulo_epsilon_state(targets);
+					if !next_det_q.is_empty() {
+						labels
+							.entry(next_det_q)
+							.or_default()
+							.extend(block.iter().copied());
+					}
+				}
+
+				result.declare_state(r.clone());
+				for (next_det_q, label) in labels {
+					result.add(r.clone(), label, f(&next_det_q));
+					stack.push(next_det_q)
+				}
+			}
+		}
+
+		result
+	}
+
+	/// Lazily determinizes this NFA, yielding the states and transitions of
+	/// the DFA as they are discovered.
+	///
+	/// Deterministic states are numbered from `0`, the initial state, in the
+	/// order they are discovered. Each state is yielded (see
+	/// [`DeterminizeItem::State`]) before its transitions, the initial state
+	/// first. Unlike [`Self::determinize`], the DFA is never materialized:
+	/// only the set of visited states is kept in memory.
+	pub fn determinize_streaming(&self) -> DeterminizeStreaming<'_, Q, T>
+	where
+		Q: Hash,
+	{
+		let initial_state = self.modulo_epsilon_state(&self.initial_states);
+		let mut ids = HashMap::new();
+		ids.insert(initial_state.clone(), 0);
+
+		DeterminizeStreaming {
+			aut: self,
+			ids,
+			stack: vec![(0, initial_state)],
+			pending: Vec::new(),
+		}
+	}
+
+	/// Turns this NFA into a minimal DFA.
+	///
+	/// States of the output automaton are numbered from `0`, the initial
+	/// state. All the transitions going from one state to another are merged
+	/// into a single label.
+	pub fn to_minimal_dfa(&self) -> DFA<u32, RangeSet<T>>
+	where
+		T: Hash,
+		Q: Hash,
+	{
+		self.to_minimal_dfa_by(|_| ()).0
+	}
+
+	/// Turns this NFA into a minimal DFA, never merging two deterministic
+	/// states with different keys.
+	///
+	/// Returns the minimal DFA, numbered as in [`Self::to_minimal_dfa`],
+	/// along with the key of each of its states.
+	pub fn to_minimal_dfa_by<K>(
+		&self,
+		key: impl Fn(&BTreeSet<&Q>) -> K,
+	) -> (DFA<u32, RangeSet<T>>, BTreeMap<u32, K>)
+	where
+		T: Hash,
+		Q: Hash,
+		K: Clone + Eq + Hash,
+	{
+		let dfa = self.determinize(|qs| qs.clone()).atomized();
+		let partition = dfa.partition(|q| (dfa.is_final_state(q), key(q)));
+		let minimal = dfa.minimize(partition.into_values());
+
+		let mut ids = HashMap::new();
+		ids.insert(minimal.initial_state(), 0);
+		for q in minimal.states() {
+			let id = ids.len() as u32;
+			ids.entry(q).or_insert(id);
+		}
+
+		let mut result = DFA::new(0);
+		for (source, transitions) in minimal.transitions() {
+			let mut labels: BTreeMap<u32, RangeSet<T>> = BTreeMap::new();
+			for (range, target) in transitions {
+				labels.entry(ids[target]).or_default().insert(**range);
+			}
+
+			for (target, label) in labels {
+				result.add(ids[source], label, target);
+			}
+		}
+
+		for q in minimal.final_states() {
+			result.add_final_state(ids[q]);
+		}
+
+		let keys = ids
+			.into_iter()
+			.filter_map(|(q, id)| Some((id, key(q.iter().next()?))))
+			.collect();
+
+		(result, keys)
+	}
+
+	/// Computes a DFA recognizing the words recognized by this automaton but
+	/// not by the `other` one.
+	///
+	/// This is the [intersection](DFA::intersection) of this automaton with
+	/// the [complement](DFA::complement) of the `other` one, both
+	/// determinized. The resulting DFA is [canonical](DFA::canonicalize).
+	pub fn difference<R>(&self, other: &NFA<R, T>) -> DFA<u32, RangeSet<T>>
+	where
+		T: Hash,
+		Q: Hash,
+		R: Ord + Hash,
+	{
+		let a = self.determinize(|qs| qs.clone());
+		let not_b = other.determinize(|qs| qs.clone()).complement(&T::all());
+		a.intersection(&not_b).merge_labels().canonicalize()
+	}
+
+	/// Checks if this automaton and the `other` one recognize a common word.
+	pub fn intersects<R>(&self, other: &NFA<R, T>) -> bool
+	where
+		Q: Hash,
+		R: Ord + Hash,
+	{
+		let a = self.determinize(|qs| qs.clone());
+		let b = other.determinize(|qs| qs.clone());
+
+		let mut stack = vec![(a.initial_state(), b.initial_state())];
+		let mut visited = HashSet::new();
+		while let Some((p, q)) = stack.pop() {
+			if visited.insert((p, q)) {
+				if a.is_final_state(p) && b.is_final_state(q) {
+					return true;
+				}
+
+				for (p_range, p2) in a.transitions_from(p) {
+					for (q_range, q2) in b.transitions_from(q) {
+						if p_range.intersects(q_range) {
+							stack.push((p2, q2))
+						}
+					}
+				}
+			}
+		}
+
+		false
 	}
 
 	/// Adds the given `other` automaton to `self`, mapping the other automaton
@@ -712,6 +1243,123 @@ where
 	}
 }
 
+impl<T: Token, Q: Ord> NFA<(usize, Q), T> {
+	/// Creates the union of the given patterns, where each state is tagged
+	/// with the index of the pattern it comes from.
+	pub fn from_patterns(patterns: impl IntoIterator<Item = NFA<Q, T>>) -> Self {
+		let mut result = Self::new();
+
+		for (i, pattern) in patterns.into_iter().enumerate() {
+			result.mapped_union(pattern, |q| (i, q))
+		}
+
+		result
+	}
+
+	/// Turns this union of patterns into a minimal DFA where each final state
+	/// is tagged with the pattern it recognizes.
+	///
+	/// When a word is recognized by more than one pattern, the pattern with
+	/// the lowest index has priority.
+	pub fn to_tagged_dfa(&self) -> TaggedDFA<T>
+	where
+		T: Hash,
+		Q: Hash,
+	{
+		let (untagged, keys) =
+			self.to_minimal_dfa_by(|qs| self.final_patterns(qs).first().copied());
+
+		let tags = keys
+			.into_iter()
+			.filter_map(|(q, tag)| Some((q, tag?)))
+			.collect();
+
+		TaggedDFA { untagged, tags }
+	}
+
+	/// Returns the indexes of the patterns having a final state among the
+	/// given states.
+	pub fn final_patterns(&self, states: &BTreeSet<&(usize, Q)>) -> BTreeSet<usize> {
+		states
+			.iter()
+			.filter(|q| self.is_final_state(q))
+			.map(|(i, _)| *i)
+			.collect()
+	}
+
+	/// Turns this union of patterns into a DFA, also reporting the ambiguous
+	/// final states.
+	///
+	/// A deterministic state is ambiguous when its
+	/// [final patterns](Self::final_patterns) are not unique, meaning that the
+	/// same input is recognized by more than one pattern. The returned map
+	/// associates each ambiguous state to these patterns.
+	///
+	/// See [`NFA::determinize`].
+	pub fn determinize_with_conflicts<'a, R>(
+		&'a self,
+		mut f: impl FnMut(&BTreeSet<&'a (usize, Q)>) -> R,
+	) -> (DFA<R, AnyRange<T>>, Conflicts<R>)
+	where
+		R: Clone + Ord + Hash,
+	{
+		let mut conflicts = BTreeMap::new();
+
+		let dfa = self.determinize(|det_q| {
+			let r = f(det_q);
+
+			let patterns = self.final_patterns(det_q);
+			if patterns.len() > 1 {
+				conflicts.insert(r.clone(), patterns);
+			}
+
+			r
+		});
+
+		(dfa, conflicts)
+	}
+}
+
+/// Ambiguous states of a determinized union of patterns, with the indexes of
+/// the patterns they recognize.
+///
+/// See [`NFA::determinize_with_conflicts`].
+pub type Conflicts<R> = BTreeMap<R, BTreeSet<usize>>;
+
+/// Computes the coarsest partition of the tokens appearing in the given
+/// labels such that every label is a union of partition blocks.
+///
+/// See [`NFA::symbolic_alphabet`].
+pub(crate) fn symbolic_alphabet<'a, T: 'a + Token>(
+	labels: impl IntoIterator<Item = &'a RangeSet<T>>,
+) -> Vec<RangeSet<T>> {
+	let labels: BTreeSet<&RangeSet<T>> = labels.into_iter().collect();
+
+	let mut signatures: RangeMap<T, BTreeSet<usize>> = RangeMap::new();
+	for (i, label) in labels.into_iter().enumerate() {
+		for range in label {
+			signatures.update(*range, |ids: Option<&BTreeSet<usize>>| {
+				let mut ids = ids.cloned().unwrap_or_default();
+				ids.insert(i);
+				Some(ids)
+			});
+		}
+	}
+
+	let mut blocks: BTreeMap<BTreeSet<usize>, RangeSet<T>> = BTreeMap::new();
+	for (range, ids) in signatures {
+		blocks.entry(ids).or_default().insert(range);
+	}
+
+	blocks.into_values().collect()
+}
+
+impl<Q, T> AsRef<NFA<Q, T>> for NFA<Q, T> {
+	fn as_ref(&self) -> &NFA<Q, T> {
+		self
+	}
+}
+
 /// Iterator over the successors of a given state in a [`NFA`].
 pub struct Successors<'a, T, Q> {
 	inner: Option<std::collections::btree_map::Iter<'a, Option<RangeSet<T>>, BTreeSet<Q>>>,
@@ -733,59 +1381,44 @@ impl<'a, T, Q> Iterator for Successors<'a, T, Q> {
 	}
 }
 
-impl<T: Token, Q: Ord + Hash> Automaton<T> for NFA<Q, T> {
-	type State<'a> = VisitingState<'a, Q> where Self: 'a;
-
-	fn initial_state(&self) -> Option<Self::State<'_>> {
-		let mut stack = Vec::new();
-		let mut states = HashSet::new();
+impl<T: Token, Q: Ord + Hash> NFA<Q, T> {
+	/// Resets the given state to the initial state of the automaton, reusing
+	/// its allocations.
+	///
+	/// Returns `false` if the automaton has no initial state.
+	pub fn initial_state_into<'a>(&'a self, state: &mut VisitingState<'a, Q>) -> bool {
+		state.states.clear();
+		state.stack.clear();
 
 		for r in &self.initial_states {
-			states.insert(r);
-			stack.push(r);
-		}
-
-		// epsilon-closure.
-		while let Some(q) = stack.pop() {
-			if let Some(q_transitions) = self.transitions.get(q) {
-				if let Some(targets) = q_transitions.get(&None) {
-					for r in targets {
-						if states.insert(r) {
-							stack.push(r);
-						}
-					}
-				}
+			if state.states.insert(r) {
+				state.stack.push(r);
 			}
 		}
 
-		if states.is_empty() {
-			None
-		} else {
-			Some(VisitingState {
-				states,
-				next_states: HashSet::new(),
-				stack,
-			})
-		}
+		self.epsilon_closure(&mut state.states, &mut state.stack);
+		!state.states.is_empty()
 	}
 
-	fn next_state<'a>(
-		&'a self,
-		VisitingState {
-			mut states,
-			mut next_states,
-			mut stack,
-		}: Self::State<'a>,
-		token: T,
-	) -> Option<Self::State<'_>> {
-		for &q in &states {
+	/// Follows the transitions labeled by the given token from the given
+	/// state, in place.
+	///
+	/// Unlike [`Automaton::next_state`], this does not consume the state,
+	/// which can hence be reused across matches without allocating.
+	///
+	/// Returns `false` if no state is reachable, in which case the state is
+	/// left empty.
+	pub fn next_state_into<'a>(&'a self, state: &mut VisitingState<'a, Q>, token: T) -> bool {
+		state.next_states.clear();
+
+		for &q in &state.states {
 			if let Some(q_transitions) = self.transitions.get(q) {
 				for (label, targets) in q_transitions {
 					if let Some(label) = label {
 						if label.contains(token) {
 							for r in targets {
-								if next_states.insert(r) {
-									stack.push(r);
+								if state.next_states.insert(r) {
+									state.stack.push(r);
 								}
 							}
 						}
@@ -794,29 +1427,38 @@ impl<T: Token, Q: Ord + Hash> Automaton<T> for NFA<Q, T> {
 			}
 		}
 
-		// epsilon-closure.
+		self.epsilon_closure(&mut state.next_states, &mut state.stack);
+		std::mem::swap(&mut state.states, &mut state.next_states);
+		!state.states.is_empty()
+	}
+
+	/// Adds to `states` every state reachable from the `stack` states
+	/// through epsilon transitions.
+	fn epsilon_closure<'a>(&'a self, states: &mut HashSet<&'a Q>, stack: &mut Vec<&'a Q>) {
 		while let Some(q) = stack.pop() {
 			if let Some(q_transitions) = self.transitions.get(q) {
 				if let Some(targets) = q_transitions.get(&None) {
 					for r in targets {
-						if next_states.insert(r) {
+						if states.insert(r) {
 							stack.push(r);
 						}
 					}
 				}
 			}
 		}
+	}
+}
 
-		if next_states.is_empty() {
-			None
-		} else {
-			states.clear();
-			Some(VisitingState {
-				states: next_states,
-				next_states: states,
-				stack,
-			})
-		}
+impl<T: Token, Q: Ord + Hash> Automaton<T> for NFA<Q, T> {
+	type State<'a> = VisitingState<'a, Q> where Self: 'a;
+
+	fn initial_state(&self) -> Option<Self::State<'_>> {
+		let mut state = VisitingState::default();
+		self.initial_state_into(&mut state).then_some(state)
+	}
+
+	fn next_state<'a>(&'a self, mut state: Self::State<'a>, token: T) -> Option<Self::State<'a>> {
+		self.next_state_into(&mut state, token).then_some(state)
 	}
 
 	fn is_final_state<'a>(&'a self, VisitingState { states, .. }: &Self::State<'a>) -> bool {
@@ -828,6 +1470,134 @@ impl<T: Token, Q: Ord + Hash> Automaton<T> for NFA<Q, T> {
 
 		false
 	}
+
+	/// Checks if one of the visited states loops on every token back to
+	/// itself, through a final state.
+	///
+	/// Epsilon transitions are taken into account.
+	fn accepts_any_continuation<'a>(
+		&'a self,
+		VisitingState { states, .. }: &Self::State<'a>,
+	) -> bool {
+		let closure = |q| {
+			let mut states = HashSet::from([q]);
+			self.epsilon_closure(&mut states, &mut vec![q]);
+			states
+		};
+
+		states.iter().any(|&p| {
+			let p_closure = closure(p);
+			if !p_closure.iter().any(|q| self.final_states.contains(*q)) {
+				return false;
+			}
+
+			let mut missing = T::all();
+			for &q in &p_closure {
+				for (label, targets) in self.successors(q) {
+					if let Some(label) = label {
+						if targets.iter().any(|r| closure(r).contains(p)) {
+							for range in label.iter() {
+								missing.remove(*range);
+							}
+						}
+					}
+				}
+			}
+
+			missing.is_empty()
+		})
+	}
+}
+
+/// Item of a lazy determinization.
+///
+/// See [`NFA::determinize_streaming`].
+#[derive(Debug, Clone)]
+pub enum DeterminizeItem<T> {
+	/// Deterministic state.
+	State {
+		id: u32,
+		is_initial: bool,
+		is_final: bool,
+	},
+
+	/// Transition between two deterministic states.
+	Transition(u32, AnyRange<T>, u32),
+}
+
+impl<T: Token> PartialEq for DeterminizeItem<T> {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(
+				Self::State {
+					id: a,
+					is_initial: a_initial,
+					is_final: a_final,
+				},
+				Self::State {
+					id: b,
+					is_initial: b_initial,
+					is_final: b_final,
+				},
+			) => a == b && a_initial == b_initial && a_final == b_final,
+			(Self::Transition(a, a_label, a_target), Self::Transition(b, b_label, b_target)) => {
+				a == b && a_label == b_label && a_target == b_target
+			}
+			_ => false,
+		}
+	}
+}
+
+impl<T: Token> Eq for DeterminizeItem<T> {}
+
+/// Lazy determinization of an NFA.
+///
+/// See [`NFA::determinize_streaming`].
+pub struct DeterminizeStreaming<'a, Q, T> {
+	aut: &'a NFA<Q, T>,
+	ids: HashMap<BTreeSet<&'a Q>, u32>,
+	stack: Vec<(u32, BTreeSet<&'a Q>)>,
+	pending: Vec<DeterminizeItem<T>>,
+}
+
+impl<'a, Q: Ord + Hash, T: Token> Iterator for DeterminizeStreaming<'a, Q, T> {
+	type Item = DeterminizeItem<T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(item) = self.pending.pop() {
+				break Some(item);
+			}
+
+			let (id, det_q) = self.stack.pop()?;
+			let mut stack = Vec::new();
+
+			self.pending.push(DeterminizeItem::State {
+				id,
+				is_initial: id == 0,
+				is_final: det_q.iter().any(|q| self.aut.is_final_state(q)),
+			});
+
+			for (label, next_det_q) in self.aut.determinize_transitions_for(&det_q) {
+				let next_id = match self.ids.get(&next_det_q) {
+					Some(next_id) => *next_id,
+					None => {
+						let next_id = self.ids.len() as u32;
+						self.ids.insert(next_det_q.clone(), next_id);
+						stack.push((next_id, next_det_q));
+						next_id
+					}
+				};
+
+				self.pending
+					.push(DeterminizeItem::Transition(id, label, next_id));
+			}
+
+			// Yield the state, then its transitions in label order.
+			self.pending.reverse();
+			self.stack.extend(stack.into_iter().rev());
+		}
+	}
 }
 
 pub struct VisitingState<'a, Q> {
@@ -836,8 +1606,26 @@ pub struct VisitingState<'a, Q> {
 	stack: Vec<&'a Q>,
 }
 
+impl<'a, Q> Default for VisitingState<'a, Q> {
+	fn default() -> Self {
+		Self {
+			states: HashSet::new(),
+			next_states: HashSet::new(),
+			stack: Vec::new(),
+		}
+	}
+}
+
 impl<'a, Q: Ord> VisitingState<'a, Q> {
-	pub fn labels<'b, T>(&'b self, aut: &'b NFA<Q, T>) -> impl 'b + Iterator<Item = &RangeSet<T>> {
+	/// Returns the set of states currently visited.
+	pub fn states(&self) -> impl '_ + Iterator<Item = &'a Q> {
+		self.states.iter().copied()
+	}
+
+	pub fn labels<'b, T>(
+		&'b self,
+		aut: &'b NFA<Q, T>,
+	) -> impl 'b + Iterator<Item = &'b RangeSet<T>> {
 		self.states.iter().flat_map(|q| {
 			aut.transitions
 				.get(*q)
@@ -852,8 +1640,32 @@ impl<'a, Q: Ord> VisitingState<'a, Q> {
 mod tests {
 	use btree_range_map::generic::RangeSet;
 
-	use super::NFA;
-	use crate::any_char;
+	use std::{collections::BTreeSet, ops::RangeBounds};
+
+	use super::{DeterminizeItem, TooManyStates, NFA};
+	use crate::{any_char, Automaton, DFA};
+
+	#[test]
+	fn determinize_with_conflicts() {
+		let keyword = NFA::singleton("if".chars(), |i| i.map_or(0, |i| i as u32 + 1));
+		let mut ident = NFA::new();
+		let mut letters = RangeSet::new();
+		letters.insert('a'..='z');
+		ident.add_initial_state(0);
+		ident.add(0, Some(letters.clone()), 1);
+		ident.add(1, Some(letters), 1);
+		ident.add_final_state(1);
+
+		let nfa = NFA::from_patterns([keyword, ident]);
+		let (dfa, conflicts) = nfa.determinize_with_conflicts(|q| q.clone());
+
+		// Only the state reached after reading `if` is ambiguous.
+		assert_eq!(conflicts.len(), 1);
+		let (ambiguous, patterns) = conflicts.first_key_value().unwrap();
+		assert_eq!(dfa.run("if".chars()), Some(ambiguous));
+		assert!(dfa.is_final_state(ambiguous));
+		assert_eq!(*patterns, BTreeSet::from_iter([0, 1]));
+	}
 
 	#[test]
 	fn is_finite() {
@@ -879,4 +1691,296 @@ mod tests {
 		let aut2 = NFA::singleton("foo".chars(), |q| q);
 		assert!(!aut2.is_universal(any_char()))
 	}
+
+	#[test]
+	fn empty_and_universal_languages() {
+		let empty: NFA<u32, char> = NFA::empty_language();
+		let universal: NFA<u32, char> = NFA::universal(any_char());
+
+		for word in ["", "a", "abc", "\u{10ffff}"] {
+			assert!(!empty.contains(word.chars()));
+			assert!(universal.contains(word.chars()));
+		}
+	}
+
+	#[test]
+	fn is_empty_language() {
+		let empty: NFA<u32, char> = NFA::empty_language();
+		assert!(empty.is_empty_language());
+
+		let mut dead_end: NFA<u32, char> = NFA::new();
+		dead_end.add_initial_state(0);
+		dead_end.add(0, Some(RangeSet::from_iter(['a'])), 1);
+		dead_end.add_final_state(2);
+		assert!(dead_end.is_empty_language());
+
+		dead_end.add(0, Some(RangeSet::new()), 2);
+		assert!(dead_end.is_empty_language());
+
+		dead_end.add(1, None, 2);
+		assert!(!dead_end.is_empty_language());
+		assert!(!NFA::<u32, char>::universal(any_char()).is_empty_language());
+	}
+
+	#[test]
+	fn unreachable_final_states() {
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add_initial_state(0);
+		aut.add(0, None, 1);
+		aut.add(2, Some(RangeSet::from_iter(['a'])), 3);
+		aut.add_final_state(1);
+		aut.add_final_state(3);
+
+		assert_eq!(aut.unreachable_final_states(), BTreeSet::from([&3]));
+	}
+
+	#[test]
+	fn determinize_streaming() {
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add_initial_state(0);
+		aut.add(0, Some(RangeSet::from_iter(['a', 'b'])), 0);
+		aut.add(0, Some(RangeSet::from_iter(['a'])), 1);
+		aut.add(1, Some(RangeSet::from_iter(['b'])), 2);
+		aut.add(1, None, 3);
+		aut.add(3, Some(RangeSet::from_iter(['c'])), 2);
+		aut.add_final_state(2);
+
+		let mut items = aut.determinize_streaming().peekable();
+		assert_eq!(
+			items.peek(),
+			Some(&DeterminizeItem::State {
+				id: 0,
+				is_initial: true,
+				is_final: false
+			})
+		);
+
+		let mut streamed = DFA::new(0);
+		for item in items {
+			match item {
+				DeterminizeItem::State { id, is_final, .. } => {
+					streamed.declare_state(id);
+					if is_final {
+						streamed.add_final_state(id);
+					}
+				}
+				DeterminizeItem::Transition(q, label, r) => {
+					let mut label_set = RangeSet::new();
+					label_set.insert(label);
+					streamed.add(q, label_set, r)
+				}
+			}
+		}
+
+		assert_eq!(streamed.canonicalize(), aut.to_minimal_dfa().canonicalize());
+	}
+
+	#[test]
+	fn symbolic_alphabet() {
+		let mut a_c = RangeSet::new();
+		a_c.insert('a'..='c');
+		let mut b_d = RangeSet::new();
+		b_d.insert('b'..='d');
+
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add_initial_state(0);
+		aut.add(0, Some(a_c), 1);
+		aut.add(0, Some(b_d), 1);
+		aut.add_final_state(1);
+
+		let mut b_c = RangeSet::new();
+		b_c.insert('b'..='c');
+
+		let partition: BTreeSet<_> = aut.symbolic_alphabet().into_iter().collect();
+		assert_eq!(
+			partition,
+			BTreeSet::from([RangeSet::from_iter(['a']), b_c, RangeSet::from_iter(['d'])])
+		);
+	}
+
+	#[test]
+	fn determinize_symbolic() {
+		let mut not_a = any_char();
+		not_a.remove('a');
+		let mut greek = RangeSet::new();
+		greek.insert('\u{370}'..='\u{3ff}');
+
+		// `([^a]|[α-ω])*a`
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add_initial_state(0);
+		aut.add(0, Some(not_a), 0);
+		aut.add(0, Some(greek), 1);
+		aut.add(1, None, 0);
+		aut.add(0, Some(RangeSet::from_iter(['a'])), 2);
+		aut.add_final_state(2);
+
+		let symbolic = aut.determinize_symbolic(|qs| qs.clone());
+		let eager = aut.determinize(|qs| qs.clone());
+
+		for word in ["", "a", "ba", "λa", "λ", "aλ", "\u{10ffff}a", "aa"] {
+			let mut q = Some(eager.initial_state());
+			for c in word.chars() {
+				q = q.and_then(|q| {
+					eager
+						.successors(q)
+						.find(|(range, _)| RangeBounds::contains(*range, &c))
+						.map(|(_, r)| r)
+				});
+			}
+
+			let expected = q.map_or(false, |q| eager.is_final_state(q));
+			assert_eq!(symbolic.contains(word.chars()), expected, "{word}");
+			assert_eq!(aut.contains(word.chars()), expected, "{word}");
+		}
+	}
+
+	#[test]
+	fn run() {
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add_initial_state(0);
+		aut.add(0, Some(RangeSet::from_iter(['a'])), 1);
+		aut.add(0, Some(RangeSet::from_iter(['a'])), 2);
+		aut.add(1, Some(RangeSet::from_iter(['b'])), 3);
+		aut.add(2, Some(RangeSet::from_iter(['b'])), 4);
+		aut.add(2, None, 5);
+		aut.add_final_state(4);
+
+		let q = aut.run("a".chars()).unwrap();
+		assert_eq!(
+			q.states().collect::<BTreeSet<_>>(),
+			BTreeSet::from([&1, &2, &5])
+		);
+
+		let q = aut.run("ab".chars()).unwrap();
+		assert_eq!(
+			q.states().collect::<BTreeSet<_>>(),
+			BTreeSet::from([&3, &4])
+		);
+		assert!(Automaton::is_final_state(&aut, &q));
+
+		assert!(aut.run("b".chars()).is_none());
+		assert!(aut.contains("ab".chars()));
+	}
+
+	#[test]
+	fn iter_edges() {
+		let a = RangeSet::from_iter(['a']);
+
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add(0, Some(a.clone()), 1);
+		aut.add(0, Some(a.clone()), 2);
+		aut.add(0, None, 2);
+		aut.add(2, Some(a.clone()), 2);
+		aut.add_final_state(2);
+
+		let edges: Vec<_> = aut.iter_edges().collect();
+		assert_eq!(
+			edges,
+			[
+				(&0, None, &2),
+				(&0, Some(&a), &1),
+				(&0, Some(&a), &2),
+				(&2, Some(&a), &2)
+			]
+		);
+		assert_eq!(aut.iter_edges().filter(|(_, l, _)| l.is_none()).count(), 1);
+	}
+
+	#[test]
+	fn map_labels() {
+		let mut lower = RangeSet::new();
+		lower.insert('a'..='z');
+
+		// `[a-z]+`
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add_initial_state(0);
+		aut.add(0, Some(lower.clone()), 1);
+		aut.add(1, None, 0);
+		aut.add_final_state(1);
+
+		let upper = aut.map_labels(|label| {
+			let mut result = RangeSet::new();
+			for range in label.iter() {
+				for c in range.first().unwrap()..=range.last().unwrap() {
+					result.extend(c.to_uppercase());
+				}
+			}
+
+			result
+		});
+
+		let mut expected = RangeSet::new();
+		expected.insert('A'..='Z');
+
+		assert_eq!(
+			upper.iter_edges().collect::<Vec<_>>(),
+			[(&0, Some(&expected), &1), (&1, None, &0)]
+		);
+		assert!(upper.is_initial_state(&0));
+		assert!(upper.is_final_state(&1));
+		assert!(upper.contains("ABC".chars()));
+		assert!(!upper.contains("abc".chars()));
+	}
+
+	#[test]
+	fn remove_transition() {
+		let mut aut = NFA::singleton("ab".chars(), |q| q.map_or(0, |i| i as u32 + 1));
+		aut.add(1, Some(RangeSet::from_iter(['c'])), 2);
+		aut.insert_epsilon(1, 2);
+		assert!(aut.contains("ab".chars()));
+		assert!(aut.contains("ac".chars()));
+		assert!(aut.contains("a".chars()));
+
+		assert!(aut.remove_transition(&1, &Some(RangeSet::from_iter(['b'])), &2));
+		assert!(!aut.remove_transition(&1, &Some(RangeSet::from_iter(['b'])), &2));
+		assert!(!aut.contains("ab".chars()));
+		assert!(aut.contains("ac".chars()));
+
+		assert!(aut.remove_transition(&1, &None, &2));
+		assert!(!aut.contains("a".chars()));
+		assert!(aut.contains("ac".chars()));
+	}
+
+	#[test]
+	fn remove_state() {
+		let mut aut = NFA::singleton("ab".chars(), |q| q.map_or(0, |i| i as u32 + 1));
+		aut.add(0, Some(RangeSet::from_iter(['c'])), 3);
+		aut.add_final_state(3);
+		assert!(aut.contains("ab".chars()));
+
+		assert!(aut.remove_state(&1));
+		assert!(!aut.remove_state(&1));
+		assert!(!aut.contains("ab".chars()));
+		assert!(aut.contains("c".chars()));
+		assert!(aut.iter_edges().all(|(q, _, r)| *q != 1 && *r != 1));
+
+		assert!(aut.remove_state(&0));
+		assert!(aut.initial_states().is_empty());
+		assert!(!aut.contains("c".chars()));
+	}
+
+	#[test]
+	fn determinize_bounded() {
+		// `(a|b)*a(a|b){n}` needs `2^(n+1)` deterministic states.
+		let n = 10;
+		let mut aut: NFA<u32, char> = NFA::new();
+		aut.add_initial_state(0);
+		let a_or_b: crate::RangeSet<char> = ['a', 'b'].into_iter().collect();
+		aut.add(0, Some(a_or_b.clone()), 0);
+		aut.add(0, Some(['a'].into_iter().collect()), 1);
+		for i in 1..=n {
+			aut.add(i, Some(a_or_b.clone()), i + 1);
+		}
+		aut.add_final_state(n + 1);
+
+		assert!(matches!(
+			aut.determinize_bounded(100, BTreeSet::clone),
+			Err(TooManyStates)
+		));
+
+		let dfa = aut
+			.determinize_bounded(1 << (n + 1), BTreeSet::clone)
+			.unwrap();
+		assert_eq!(dfa.states().len(), 1 << (n + 1));
+	}
 }
@@ -4,16 +4,24 @@ use std::{
 	ops::Deref,
 };
 
-use crate::{Automaton, Token, NFA};
+use crate::{Automaton, TaggedAutomaton, Token, NFA};
 
 use super::VisitingState;
 
 /// NFA tags.
-pub struct Tags<Q, T>(BTreeMap<(Q, Q), BTreeSet<T>>);
+pub struct Tags<Q, T> {
+	map: BTreeMap<(Q, Q), BTreeSet<T>>,
+
+	/// Whether insertions are ignored.
+	ignored: bool,
+}
 
 impl<Q, T> Default for Tags<Q, T> {
 	fn default() -> Self {
-		Self(BTreeMap::new())
+		Self {
+			map: BTreeMap::new(),
+			ignored: false,
+		}
 	}
 }
 
@@ -21,15 +29,30 @@ impl<Q, T> Tags<Q, T> {
 	pub fn new() -> Self {
 		Self::default()
 	}
+
+	/// Creates a tag sink ignoring every insertion.
+	///
+	/// Used to build an NFA without constructing its tags, see
+	/// [`BuildNFA::build_untagged_nfa`](super::BuildNFA::build_untagged_nfa).
+	pub fn ignored() -> Self {
+		Self {
+			map: BTreeMap::new(),
+			ignored: true,
+		}
+	}
 }
 
 impl<Q: Ord, T: Ord> Tags<Q, T> {
+	/// Tags the transition from `source` to `target`.
+	///
+	/// Returns `false` if the transition already had this tag, or if the tags
+	/// are [ignored](Self::ignored).
 	pub fn insert(&mut self, source: Q, tag: T, target: Q) -> bool {
-		self.0.entry((source, target)).or_default().insert(tag)
+		!self.ignored && self.map.entry((source, target)).or_default().insert(tag)
 	}
 
 	pub fn get(&self, source: Q, target: Q) -> impl Iterator<Item = &T> {
-		self.0.get(&(source, target)).into_iter().flatten()
+		self.map.get(&(source, target)).into_iter().flatten()
 	}
 }
 
@@ -56,6 +79,12 @@ impl<Q, T, G> Deref for TaggedNFA<Q, T, G> {
 	}
 }
 
+impl<Q, T, G> AsRef<NFA<Q, T>> for TaggedNFA<Q, T, G> {
+	fn as_ref(&self) -> &NFA<Q, T> {
+		&self.untagged
+	}
+}
+
 impl<Q: Ord + Hash, T: Token, G> Automaton<T> for TaggedNFA<Q, T, G> {
 	type State<'a> = VisitingState<'a, Q>
 		where
@@ -69,11 +98,55 @@ impl<Q: Ord + Hash, T: Token, G> Automaton<T> for TaggedNFA<Q, T, G> {
 		&'a self,
 		current_state: Self::State<'a>,
 		token: T,
-	) -> Option<Self::State<'_>> {
+	) -> Option<Self::State<'a>> {
 		Automaton::next_state(&self.untagged, current_state, token)
 	}
 
 	fn is_final_state<'a>(&'a self, state: &Self::State<'a>) -> bool {
 		Automaton::is_final_state(&self.untagged, state)
 	}
+
+	fn accepts_any_continuation<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		Automaton::accepts_any_continuation(&self.untagged, state)
+	}
+}
+
+/// The tag of a set of states is the least tag of the transitions between two
+/// states of the set leading to a final state.
+///
+/// This is the tag priority rule: when the same input is accepted with
+/// different tags, the least tag according to `G: Ord` wins. For instance, a lexer tagging keywords
+/// with a lower tag than identifiers reports `if` as a keyword.
+impl<Q: Ord + Hash, T: Token, G: Clone + Ord> TaggedAutomaton<T, G> for TaggedNFA<Q, T, G> {
+	fn get_tag<'a>(&'a self, state: &Self::State<'a>) -> Option<G> {
+		let states: BTreeSet<_> = state.states().collect();
+
+		self.tags
+			.map
+			.iter()
+			.filter(|((source, target), _)| {
+				self.untagged.is_final_state(target)
+					&& states.contains(source)
+					&& states.contains(target)
+			})
+			.flat_map(|(_, tags)| tags)
+			.min()
+			.cloned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Tags;
+
+	#[test]
+	fn ignored() {
+		let mut tags = Tags::ignored();
+		assert!(!tags.insert(0, 0, 1));
+		assert_eq!(tags.get(0, 1).next(), None);
+
+		let mut tags = Tags::new();
+		assert!(tags.insert(0, 0, 1));
+		assert_eq!(tags.get(0, 1).collect::<Vec<_>>(), [&0]);
+	}
 }
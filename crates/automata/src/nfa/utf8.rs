@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+
+use btree_range_map::RangeSet;
+
+use super::NFA;
+
+/// Sequence of byte ranges matching the UTF-8 encoding of a range of
+/// characters.
+pub(crate) type Utf8Sequence = Vec<(u8, u8)>;
+
+impl<Q: Ord> NFA<Q, char> {
+	/// Converts this automaton over characters into an automaton over the
+	/// bytes of their UTF-8 encoding.
+	///
+	/// Each character transition is replaced by sequences of byte transitions
+	/// through fresh intermediate states, so that the resulting automaton
+	/// recognizes exactly the UTF-8 encoding of the words recognized by
+	/// `self`.
+	pub fn to_utf8(&self) -> NFA<u32, u8> {
+		let mut ids = BTreeMap::new();
+		for q in self
+			.transitions
+			.keys()
+			.chain(&self.initial_states)
+			.chain(&self.final_states)
+		{
+			let id = ids.len() as u32;
+			ids.entry(q).or_insert(id);
+		}
+
+		let mut result = NFA::new();
+		for (q, id) in &ids {
+			result.add_state(*id);
+
+			if self.is_initial_state(q) {
+				result.add_initial_state(*id);
+			}
+
+			if self.is_final_state(q) {
+				result.add_final_state(*id);
+			}
+		}
+
+		let mut next_id = ids.len() as u32;
+		for (q, transitions) in &self.transitions {
+			let a = ids[q];
+			for (label, targets) in transitions {
+				let sequences = label.as_ref().map(|set| {
+					let mut sequences = Vec::new();
+					for range in set.iter() {
+						if let (Some(first), Some(last)) = (range.first(), range.last()) {
+							utf8_sequences(first as u32, last as u32, &mut sequences)
+						}
+					}
+					sequences
+				});
+
+				for r in targets {
+					let b = ids[r];
+					match &sequences {
+						Some(sequences) => {
+							for sequence in sequences {
+								let mut c = a;
+								for (i, (start, end)) in sequence.iter().enumerate() {
+									let d = if i + 1 == sequence.len() {
+										b
+									} else {
+										next_id += 1;
+										next_id - 1
+									};
+
+									let mut label = RangeSet::new();
+									label.insert(*start..=*end);
+									result.add(c, Some(label), d);
+									c = d;
+								}
+							}
+						}
+						None => result.add(a, None, b),
+					}
+				}
+			}
+		}
+
+		result
+	}
+}
+
+/// Computes the byte range sequences matching the UTF-8 encoding of the
+/// characters between `start` and `end` (included).
+///
+/// Surrogate code points are skipped.
+pub(crate) fn utf8_sequences(start: u32, end: u32, sequences: &mut Vec<Utf8Sequence>) {
+	if start > end {
+		return;
+	}
+
+	// Skip surrogates.
+	if start <= 0xdfff && end >= 0xd800 {
+		utf8_sequences(start, 0xd7ff, sequences);
+		utf8_sequences(0xe000, end, sequences);
+		return;
+	}
+
+	// Split at encoding length changes.
+	for max in [0x7f, 0x7ff, 0xffff] {
+		if start <= max && end > max {
+			utf8_sequences(start, max, sequences);
+			utf8_sequences(max + 1, end, sequences);
+			return;
+		}
+	}
+
+	// Split until every continuation byte spans its whole range.
+	let len = char::from_u32(start).unwrap().len_utf8();
+	for i in 1..len {
+		let mask = (1u32 << (6 * i)) - 1;
+		if start & !mask != end & !mask {
+			if start & mask != 0 {
+				utf8_sequences(start, start | mask, sequences);
+				utf8_sequences((start | mask) + 1, end, sequences);
+				return;
+			}
+
+			if end & mask != mask {
+				utf8_sequences(start, (end & !mask) - 1, sequences);
+				utf8_sequences(end & !mask, end, sequences);
+				return;
+			}
+		}
+	}
+
+	let mut start_bytes = [0; 4];
+	let mut end_bytes = [0; 4];
+	let start_bytes = char::from_u32(start)
+		.unwrap()
+		.encode_utf8(&mut start_bytes)
+		.as_bytes();
+	let end_bytes = char::from_u32(end)
+		.unwrap()
+		.encode_utf8(&mut end_bytes)
+		.as_bytes();
+
+	sequences.push(
+		start_bytes
+			.iter()
+			.copied()
+			.zip(end_bytes.iter().copied())
+			.collect(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use btree_range_map::RangeSet;
+
+	use crate::{any_char, Automaton, NFA};
+
+	#[test]
+	fn to_utf8() {
+		let mut nfa = NFA::new();
+		nfa.add(0, Some(any_char()), 1);
+		nfa.add_initial_state(0);
+		nfa.add_final_state(1);
+
+		let utf8 = nfa.to_utf8();
+
+		for c in ('\0'..=char::MAX)
+			.step_by(97)
+			.chain(['\u{d7ff}', '\u{e000}', char::MAX])
+		{
+			let mut bytes = [0; 4];
+			assert!(utf8.contains(c.encode_utf8(&mut bytes).bytes()));
+		}
+
+		assert!(!utf8.contains([]));
+		assert!(!utf8.contains([b'a', b'b']));
+		assert!(!utf8.contains([0xc3]));
+		// Overlong encoding of `/`.
+		assert!(!utf8.contains([0xc0, 0xaf]));
+		// Encoded surrogate.
+		assert!(!utf8.contains([0xed, 0xa0, 0x80]));
+		// Out of the Unicode range.
+		assert!(!utf8.contains([0xf4, 0x90, 0x80, 0x80]));
+	}
+
+	#[test]
+	fn to_utf8_range() {
+		let nfa = NFA::singleton(['é', 'a'], |q| q.map_or(0, |i| i as u32 + 1));
+		let utf8 = nfa.to_utf8();
+
+		assert!(utf8.contains("éa".bytes()));
+		assert!(!utf8.contains("ea".bytes()));
+		assert!(!utf8.contains("é".bytes()));
+
+		let mut set = RangeSet::new();
+		set.insert('\u{7ab}'..='\u{10f0a}');
+		let mut nfa = NFA::new();
+		nfa.add(0, Some(set.clone()), 1);
+		nfa.add_initial_state(0);
+		nfa.add_final_state(1);
+		let utf8 = nfa.to_utf8();
+
+		for c in ('\u{700}'..='\u{11000}').step_by(7) {
+			let mut bytes = [0; 4];
+			assert_eq!(
+				utf8.contains(c.encode_utf8(&mut bytes).bytes()),
+				set.contains(c)
+			);
+		}
+	}
+}
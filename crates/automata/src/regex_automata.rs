@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use ::regex_automata::{
+	dfa::dense,
+	nfa::thompson::{self, Transition},
+	MatchKind,
+};
+use crate::{nfa::utf8_sequences, RangeSet, DFA};
+
+/// Error returned by [`DFA::to_regex_automata`].
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+	#[error(transparent)]
+	Nfa(#[from] thompson::BuildError),
+
+	#[error(transparent)]
+	Dfa(#[from] dense::BuildError),
+}
+
+impl DFA<u32, RangeSet<char>> {
+	/// Exports this automaton into a [`regex-automata`] dense DFA running on
+	/// the UTF-8 encoding of the input.
+	///
+	/// The resulting DFA reports every match ([`MatchKind::All`]). Unanchored
+	/// searches are supported.
+	///
+	/// [`regex-automata`]: <https://crates.io/crates/regex-automata>
+	pub fn to_regex_automata(&self) -> Result<dense::DFA<Vec<u32>>, ExportError> {
+		let mut builder = thompson::Builder::new();
+		builder.start_pattern()?;
+
+		let mut ids = BTreeMap::new();
+		for q in self.states() {
+			ids.insert(*q, builder.add_union(Vec::new())?);
+		}
+
+		let match_id = builder.add_match()?;
+		for q in self.final_states() {
+			builder.patch(ids[q], match_id)?;
+		}
+
+		for (source, label, target) in self.edges() {
+			for range in label.iter() {
+				let (Some(start), Some(end)) = (range.first(), range.last()) else {
+					continue;
+				};
+
+				let mut sequences = Vec::new();
+				utf8_sequences(start as u32, end as u32, &mut sequences);
+
+				for sequence in sequences {
+					let mut next = ids[target];
+					for (start, end) in sequence.into_iter().rev() {
+						next = builder.add_range(Transition { start, end, next })?;
+					}
+
+					builder.patch(ids[source], next)?;
+				}
+			}
+		}
+
+		let start = ids[self.initial_state()];
+		builder.finish_pattern(start)?;
+
+		// Non-greedy `(?s-u:.)*?` prefix.
+		let unanchored = builder.add_union(vec![start])?;
+		let any = builder.add_range(Transition {
+			start: 0x00,
+			end: 0xff,
+			next: unanchored,
+		})?;
+		builder.patch(unanchored, any)?;
+
+		let nfa = builder.build(start, unanchored)?;
+
+		Ok(dense::Builder::new()
+			.configure(dense::Config::new().match_kind(MatchKind::All))
+			.build_from_nfa(&nfa)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ::regex_automata::{
+		dfa::{dense, Automaton},
+		Anchored, Input,
+	};
+
+	use crate::{Automaton as _, RangeSet, DFA};
+
+	fn accepts(dfa: &dense::DFA<Vec<u32>>, s: &str) -> bool {
+		let input = Input::new(s).anchored(Anchored::Yes);
+		let mut q = dfa.start_state_forward(&input).unwrap();
+		for b in s.bytes() {
+			q = dfa.next_state(q, b);
+		}
+
+		dfa.is_match_state(dfa.next_eoi_state(q))
+	}
+
+	#[test]
+	fn to_regex_automata() {
+		let mut letters = RangeSet::new();
+		letters.insert('a'..='z');
+		letters.insert('é');
+		letters.insert('€'..='\u{1f600}');
+
+		// `[a-zé€-😀]+!?`
+		let mut dfa = DFA::new(0);
+		dfa.add(0, letters.clone(), 1);
+		dfa.add(1, letters, 1);
+		dfa.add(1, RangeSet::from_iter(['!']), 2);
+		dfa.add_final_state(1);
+		dfa.add_final_state(2);
+
+		let exported = dfa.to_regex_automata().unwrap();
+		for s in [
+			"a",
+			"abc!",
+			"é€",
+			"z\u{1f600}!",
+			"",
+			"!",
+			"A",
+			"ab!!",
+			"ê",
+			"\u{1f601}",
+		] {
+			assert_eq!(accepts(&exported, s), dfa.contains(s.chars()), "{s:?}");
+		}
+
+		let m = exported.try_search_fwd(&Input::new("AB é!")).unwrap();
+		assert_eq!(m.map(|m| m.offset()), Some(6));
+	}
+}
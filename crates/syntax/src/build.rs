@@ -1,57 +1,276 @@
-use iregex::automata::{any_char, AnyRange, RangeSet};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	sync::{Mutex, PoisonError},
+};
+
+use iregex::automata::{
+	any_char, negate_char_set, nfa::U32StateBuilder, token_set_intersection, RangeSet, DFA,
+};
 
 use crate::{Ast, Atom, Charset, Class, Classes, Disjunction, Repeat, Sequence};
 
+/// Error raised when building a regular expression that cannot be
+/// represented by the intermediate representation.
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+	/// A repetition bound exceeds [`iregex::Repeat::MAX_BOUND`].
+	#[error("repetition bound {0} is too large (maximum is {max})", max = iregex::Repeat::MAX_BOUND)]
+	RepeatTooLarge(u32),
+}
+
+impl From<BuildError> for iregex::CompileError {
+	fn from(value: BuildError) -> Self {
+		match value {
+			BuildError::RepeatTooLarge(bound) => Self::RepeatTooLarge(bound),
+		}
+	}
+}
+
 impl Ast {
-	pub fn build(&self) -> iregex::IRegEx {
-		let root = self.disjunction.build();
+	/// Builds the regular expression.
+	///
+	/// Fails if the regular expression uses a construct that cannot be
+	/// represented, such as a repetition bound greater than
+	/// [`iregex::Repeat::MAX_BOUND`].
+	pub fn build(&self) -> Result<iregex::IRegEx, BuildError> {
+		self.build_over(&any_char())
+	}
 
-		iregex::IRegEx {
+	/// Builds the regular expression over the given alphabet.
+	///
+	/// Every character set, including `.`, negated sets and the implicit
+	/// unanchored prefix and suffix, is restricted to the alphabet.
+	pub fn build_over(&self, alphabet: &RangeSet<char>) -> Result<iregex::IRegEx, BuildError> {
+		let root = self.disjunction.build_over(alphabet)?;
+
+		Ok(iregex::IRegEx {
 			root,
 			prefix: if self.start_anchor {
-				iregex::Affix::Anchor
+				line_affix(alphabet, self.multiline_start, true)
 			} else {
-				iregex::Affix::Any
+				iregex::Affix::any_over(alphabet.clone())
 			},
 			suffix: if self.end_anchor {
-				iregex::Affix::Anchor
+				line_affix(alphabet, self.multiline_end, false)
 			} else {
-				iregex::Affix::Any
+				iregex::Affix::any_over(alphabet.clone())
 			},
+		})
+	}
+
+	/// Compiles this regular expression into a minimal DFA.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex_syntax::Ast;
+	/// let ast = Ast::parse("^a+b$".chars()).unwrap();
+	/// let dfa = ast.compile_dfa().unwrap();
+	///
+	/// let mut q = dfa.initial_state();
+	/// for c in "aaab".chars() {
+	///     let (_, r) = dfa.successors(q).find(|(label, _)| label.contains(c)).unwrap();
+	///     q = r;
+	/// }
+	///
+	/// assert!(dfa.is_final_state(q));
+	/// ```
+	pub fn compile_dfa(&self) -> Result<DFA<u32, RangeSet<char>>, iregex::CompileError> {
+		let nfa = self.build()?.compile_nfa(U32StateBuilder::new())?;
+		Ok(nfa.to_minimal_dfa())
+	}
+
+	/// Compiles the negation of this regular expression over the given
+	/// alphabet into a minimal DFA.
+	///
+	/// The resulting automaton accepts exactly the strings over `alphabet`
+	/// that are not entirely matched by `self`. Negation is only well defined
+	/// for full matches, so the expression is anchored at both ends whatever
+	/// its `^` and `$` anchors, like with [`Self::full_match_dfa`].
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex::automata::any_char;
+	/// # use iregex_syntax::Ast;
+	/// let ast = Ast::parse("^foo$".chars()).unwrap();
+	/// let dfa = ast.negate(&any_char()).unwrap();
+	/// assert!(dfa.validate("bar"));
+	/// assert!(!dfa.validate("foo"));
+	/// ```
+	pub fn negate(
+		&self,
+		alphabet: &RangeSet<char>,
+	) -> Result<DFA<u32, RangeSet<char>>, iregex::CompileError> {
+		let nfa = Self {
+			start_anchor: true,
+			end_anchor: true,
+			multiline_start: false,
+			multiline_end: false,
+			disjunction: self.disjunction.clone(),
+		}
+		.build_over(alphabet)?
+		.compile_nfa(U32StateBuilder::new())?;
+
+		Ok(nfa
+			.determinize(BTreeSet::clone)
+			.complement(alphabet)
+			.merge_labels()
+			.canonicalize())
+	}
+
+	/// Compiles this regular expression into a minimal DFA over the bytes of
+	/// the UTF-8 encoding of the input.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex::automata::Automaton;
+	/// # use iregex_syntax::Ast;
+	/// let ast = Ast::parse("^[é]+$".chars()).unwrap();
+	/// let dfa = ast.compile_bytes().unwrap();
+	/// assert!(dfa.contains("éé".bytes()));
+	/// assert!(!dfa.contains("ee".bytes()));
+	/// ```
+	pub fn compile_bytes(&self) -> Result<DFA<u32, RangeSet<u8>>, iregex::CompileError> {
+		let nfa = self.build()?.compile_nfa(U32StateBuilder::new())?;
+		Ok(nfa.to_utf8().to_minimal_dfa())
+	}
+
+	/// Compiles this regular expression, anchored at both ends, into a
+	/// minimal DFA.
+	///
+	/// The resulting automaton only accepts strings entirely matching the
+	/// expression, whatever the `^` and `$` anchors of `self`. Use
+	/// [`DFA::validate`] to check a string.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex_syntax::Ast;
+	/// let ast = Ast::parse("[0-9]+".chars()).unwrap();
+	/// let dfa = ast.full_match_dfa().unwrap();
+	/// assert!(dfa.validate("123"));
+	/// assert!(!dfa.validate("a123"));
+	/// ```
+	pub fn full_match_dfa(&self) -> Result<DFA<u32, RangeSet<char>>, iregex::CompileError> {
+		Self {
+			start_anchor: true,
+			end_anchor: true,
+			multiline_start: false,
+			multiline_end: false,
+			disjunction: self.disjunction.clone(),
 		}
+		.compile_dfa()
 	}
 }
 
 impl Disjunction {
-	pub fn build(&self) -> iregex::Alternation {
-		self.iter().map(Sequence::build).collect()
+	pub fn build(&self) -> Result<iregex::Alternation, BuildError> {
+		self.build_over(&any_char())
+	}
+
+	/// Builds the disjunction over the given alphabet.
+	///
+	/// Capture groups are numbered from `0` in the order of their opening
+	/// parenthesis (see [`Ast::group_optionality`]).
+	pub fn build_over(&self, alphabet: &RangeSet<char>) -> Result<iregex::Alternation, BuildError> {
+		self.build_with(alphabet, &mut 0)
+	}
+
+	fn build_with(
+		&self,
+		alphabet: &RangeSet<char>,
+		groups: &mut u32,
+	) -> Result<iregex::Alternation, BuildError> {
+		self.iter()
+			.map(|s| s.build_with(alphabet, groups))
+			.collect()
 	}
 }
 
 impl Sequence {
-	pub fn build(&self) -> iregex::Concatenation {
-		self.iter().map(Atom::build).collect()
+	pub fn build(&self) -> Result<iregex::Concatenation, BuildError> {
+		self.build_over(&any_char())
+	}
+
+	/// Builds the sequence over the given alphabet.
+	///
+	/// Capture groups are numbered as in [`Disjunction::build_over`].
+	pub fn build_over(
+		&self,
+		alphabet: &RangeSet<char>,
+	) -> Result<iregex::Concatenation, BuildError> {
+		self.build_with(alphabet, &mut 0)
+	}
+
+	fn build_with(
+		&self,
+		alphabet: &RangeSet<char>,
+		groups: &mut u32,
+	) -> Result<iregex::Concatenation, BuildError> {
+		self.iter()
+			.map(|a| a.build_with(alphabet, groups))
+			.collect()
 	}
 }
 
 impl Atom {
-	pub fn build(&self) -> iregex::Atom {
-		match self {
-			Self::Any => iregex::Atom::Token(any_char()),
-			Self::Char(c) => iregex::Atom::Token(RangeSet::from_iter([*c])),
-			Self::Set(set) => iregex::Atom::Token(set.build()),
-			Self::Group(g) => iregex::Atom::alternation(g.build()),
-			Self::Repeat(atom, repeat) => iregex::Atom::Repeat(atom.build().into(), repeat.build()),
-		}
+	pub fn build(&self) -> Result<iregex::Atom, BuildError> {
+		self.build_over(&any_char())
+	}
+
+	/// Builds the atom over the given alphabet.
+	///
+	/// Capture groups are numbered as in [`Disjunction::build_over`].
+	pub fn build_over(&self, alphabet: &RangeSet<char>) -> Result<iregex::Atom, BuildError> {
+		self.build_with(alphabet, &mut 0)
+	}
+
+	fn build_with(
+		&self,
+		alphabet: &RangeSet<char>,
+		groups: &mut u32,
+	) -> Result<iregex::Atom, BuildError> {
+		Ok(match self {
+			Self::Any => iregex::Atom::Token(alphabet.clone()),
+			Self::Char(c) => {
+				iregex::Atom::Token(token_set_intersection(&RangeSet::from_iter([*c]), alphabet))
+			}
+			Self::Set(set) => iregex::Atom::Token(set.build_over(alphabet)),
+			Self::Group(g) => iregex::Atom::alternation(g.build_with(alphabet, groups)?),
+			Self::Capture(g) => {
+				let id = iregex::CaptureGroupId(*groups);
+				*groups += 1;
+				iregex::Atom::Capture(id, g.build_with(alphabet, groups)?)
+			}
+			Self::Repeat(atom, repeat) => {
+				for bound in std::iter::once(repeat.min).chain(repeat.max) {
+					if bound > iregex::Repeat::MAX_BOUND {
+						return Err(BuildError::RepeatTooLarge(bound));
+					}
+				}
+
+				iregex::Atom::Repeat(atom.build_with(alphabet, groups)?.into(), repeat.build())
+			}
+		})
 	}
 }
 
 impl Classes {
 	pub fn build(&self) -> iregex::automata::RangeSet<char> {
+		self.to_range_set(false)
+	}
+
+	/// Builds the set of characters of these classes, using the Unicode
+	/// definition of each class if `unicode` is `true`.
+	///
+	/// See [`Class::to_range_set`].
+	pub fn to_range_set(&self, unicode: bool) -> iregex::automata::RangeSet<char> {
 		let mut result = iregex::automata::RangeSet::new();
 
 		for c in self {
-			result.extend(c.build());
+			result.extend(c.to_range_set(unicode));
 		}
 
 		result
@@ -59,9 +278,224 @@ impl Classes {
 }
 
 impl Class {
+	/// Builds the set of characters of this class, following the POSIX
+	/// (ASCII) definition.
 	pub fn build(&self) -> iregex::automata::RangeSet<char> {
-		todo!()
+		self.to_range_set(false)
 	}
+
+	/// Builds the set of characters of this class.
+	///
+	/// If `unicode` is `true`, the class is extended to every Unicode
+	/// character with the corresponding property (e.g. alphabetic or
+	/// whitespace). Classes without such a property (`punct`, `graph`,
+	/// `print` and `xdigit`) keep their ASCII definition.
+	pub fn to_range_set(&self, unicode: bool) -> iregex::automata::RangeSet<char> {
+		if unicode {
+			let predicate: Option<fn(char) -> bool> = match self {
+				Self::Upper => Some(char::is_uppercase),
+				Self::Lower => Some(char::is_lowercase),
+				Self::Alpha => Some(char::is_alphabetic),
+				Self::Alnum => {
+					let mut result = Self::Alpha.to_range_set(true);
+					result.extend(decimal_digits());
+					return result;
+				}
+				Self::Digit => return decimal_digits(),
+				Self::Space => Some(char::is_whitespace),
+				Self::Blank => Some(|c| {
+					c.is_whitespace()
+						&& !matches!(c, '\n'..='\r' | '\u{85}' | '\u{2028}' | '\u{2029}')
+				}),
+				Self::Cntrl => Some(char::is_control),
+				Self::Punct | Self::Graph | Self::Print | Self::Xdigit => None,
+			};
+
+			if let Some(predicate) = predicate {
+				return unicode_set(*self, predicate);
+			}
+		}
+
+		let mut result = iregex::automata::RangeSet::new();
+
+		match self {
+			Self::Upper => result.insert('A'..='Z'),
+			Self::Lower => result.insert('a'..='z'),
+			Self::Alpha => {
+				result.insert('A'..='Z');
+				result.insert('a'..='z');
+			}
+			Self::Alnum => {
+				result.insert('0'..='9');
+				result.insert('A'..='Z');
+				result.insert('a'..='z');
+			}
+			Self::Digit => result.insert('0'..='9'),
+			Self::Xdigit => {
+				result.insert('0'..='9');
+				result.insert('A'..='F');
+				result.insert('a'..='f');
+			}
+			Self::Punct => {
+				result.insert('!'..='/');
+				result.insert(':'..='@');
+				result.insert('['..='`');
+				result.insert('{'..='~');
+			}
+			Self::Blank => {
+				result.insert(' ');
+				result.insert('\t');
+			}
+			Self::Space => {
+				result.insert(' ');
+				result.insert('\t'..='\r');
+			}
+			Self::Cntrl => {
+				result.insert('\0'..='\x1f');
+				result.insert('\x7f');
+			}
+			Self::Graph => result.insert('!'..='~'),
+			Self::Print => result.insert(' '..='~'),
+		}
+
+		result
+	}
+}
+
+/// Unicode decimal digits (general category `Nd`), as of Unicode 15.0.
+const DECIMAL_DIGITS: [(char, char); 64] = [
+	('0', '9'),
+	('\u{660}', '\u{669}'),
+	('\u{6f0}', '\u{6f9}'),
+	('\u{7c0}', '\u{7c9}'),
+	('\u{966}', '\u{96f}'),
+	('\u{9e6}', '\u{9ef}'),
+	('\u{a66}', '\u{a6f}'),
+	('\u{ae6}', '\u{aef}'),
+	('\u{b66}', '\u{b6f}'),
+	('\u{be6}', '\u{bef}'),
+	('\u{c66}', '\u{c6f}'),
+	('\u{ce6}', '\u{cef}'),
+	('\u{d66}', '\u{d6f}'),
+	('\u{de6}', '\u{def}'),
+	('\u{e50}', '\u{e59}'),
+	('\u{ed0}', '\u{ed9}'),
+	('\u{f20}', '\u{f29}'),
+	('\u{1040}', '\u{1049}'),
+	('\u{1090}', '\u{1099}'),
+	('\u{17e0}', '\u{17e9}'),
+	('\u{1810}', '\u{1819}'),
+	('\u{1946}', '\u{194f}'),
+	('\u{19d0}', '\u{19d9}'),
+	('\u{1a80}', '\u{1a89}'),
+	('\u{1a90}', '\u{1a99}'),
+	('\u{1b50}', '\u{1b59}'),
+	('\u{1bb0}', '\u{1bb9}'),
+	('\u{1c40}', '\u{1c49}'),
+	('\u{1c50}', '\u{1c59}'),
+	('\u{a620}', '\u{a629}'),
+	('\u{a8d0}', '\u{a8d9}'),
+	('\u{a900}', '\u{a909}'),
+	('\u{a9d0}', '\u{a9d9}'),
+	('\u{a9f0}', '\u{a9f9}'),
+	('\u{aa50}', '\u{aa59}'),
+	('\u{abf0}', '\u{abf9}'),
+	('\u{ff10}', '\u{ff19}'),
+	('\u{104a0}', '\u{104a9}'),
+	('\u{10d30}', '\u{10d39}'),
+	('\u{11066}', '\u{1106f}'),
+	('\u{110f0}', '\u{110f9}'),
+	('\u{11136}', '\u{1113f}'),
+	('\u{111d0}', '\u{111d9}'),
+	('\u{112f0}', '\u{112f9}'),
+	('\u{11450}', '\u{11459}'),
+	('\u{114d0}', '\u{114d9}'),
+	('\u{11650}', '\u{11659}'),
+	('\u{116c0}', '\u{116c9}'),
+	('\u{11730}', '\u{11739}'),
+	('\u{118e0}', '\u{118e9}'),
+	('\u{11950}', '\u{11959}'),
+	('\u{11c50}', '\u{11c59}'),
+	('\u{11d50}', '\u{11d59}'),
+	('\u{11da0}', '\u{11da9}'),
+	('\u{11f50}', '\u{11f59}'),
+	('\u{16a60}', '\u{16a69}'),
+	('\u{16ac0}', '\u{16ac9}'),
+	('\u{16b50}', '\u{16b59}'),
+	('\u{1d7ce}', '\u{1d7ff}'),
+	('\u{1e140}', '\u{1e149}'),
+	('\u{1e2f0}', '\u{1e2f9}'),
+	('\u{1e4f0}', '\u{1e4f9}'),
+	('\u{1e950}', '\u{1e959}'),
+	('\u{1fbf0}', '\u{1fbf9}'),
+];
+
+/// Builds the set of Unicode decimal digits.
+///
+/// Unlike [`char::is_numeric`], this excludes letter-like numbers (`Nl`,
+/// e.g. `Ⅻ`) and other numbers (`No`, e.g. `²`).
+fn decimal_digits() -> RangeSet<char> {
+	let mut result = RangeSet::new();
+	for (start, end) in DECIMAL_DIGITS {
+		result.insert(start..=end);
+	}
+
+	result
+}
+
+/// Builds the affix of an anchor.
+///
+/// In multiline mode, the affix matches either nothing, or any sequence of
+/// characters ending (for a `start` anchor) or starting (for an end anchor)
+/// with a line feed.
+fn line_affix(alphabet: &RangeSet<char>, multiline: bool, start: bool) -> iregex::Affix<char, ()> {
+	if !multiline || !alphabet.contains('\n') {
+		return iregex::Affix::Anchor;
+	}
+
+	let any = iregex::Atom::star(iregex::Atom::Token(alphabet.clone()).into());
+	let line_feed = iregex::Atom::Token(RangeSet::from_iter(['\n']));
+	let line: iregex::Concatenation = if start {
+		[any, line_feed].into_iter().collect()
+	} else {
+		[line_feed, any].into_iter().collect()
+	};
+
+	iregex::Affix::Alternation([iregex::Concatenation::new(), line].into_iter().collect())
+}
+
+/// Builds the set of characters satisfying the given predicate, defining
+/// the Unicode variant of the given class.
+///
+/// Since every character is checked, the result is cached.
+fn unicode_set(class: Class, predicate: fn(char) -> bool) -> RangeSet<char> {
+	static CACHE: Mutex<BTreeMap<Class, RangeSet<char>>> = Mutex::new(BTreeMap::new());
+
+	let mut cache = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+	cache
+		.entry(class)
+		.or_insert_with(|| {
+			let mut result = RangeSet::new();
+			let mut run: Option<(char, char)> = None;
+
+			for c in '\0'..=char::MAX {
+				if predicate(c) {
+					run = Some(match run {
+						Some((start, _)) => (start, c),
+						None => (c, c),
+					});
+				} else if let Some((start, end)) = run.take() {
+					result.insert(start..=end);
+				}
+			}
+
+			if let Some((start, end)) = run {
+				result.insert(start..=end);
+			}
+
+			result
+		})
+		.clone()
 }
 
 impl Charset {
@@ -70,18 +504,37 @@ impl Charset {
 		result.extend(self.classes.build());
 
 		if self.negative {
-			return result.gaps().map(AnyRange::cloned).collect();
+			negate_char_set(&result)
 		} else {
 			result
 		}
 	}
+
+	pub fn build_over(&self, alphabet: &RangeSet<char>) -> iregex::automata::RangeSet<char> {
+		token_set_intersection(&self.build(), alphabet)
+	}
 }
 
 impl Repeat {
 	pub fn build(&self) -> iregex::Repeat {
-		iregex::Repeat {
-			min: self.min,
-			max: self.max,
+		(*self).into()
+	}
+}
+
+impl From<Repeat> for iregex::Repeat {
+	fn from(value: Repeat) -> Self {
+		Self {
+			min: value.min,
+			max: value.max,
+		}
+	}
+}
+
+impl From<iregex::Repeat> for Repeat {
+	fn from(value: iregex::Repeat) -> Self {
+		Self {
+			min: value.min,
+			max: value.max,
 		}
 	}
 }
@@ -1,11 +1,16 @@
 use core::fmt;
-use iregex::automata::AnyRange;
+use iregex::automata::{AnyRange, EscapedChar};
 use std::fmt::Write;
 
 use crate::{Ast, Atom, Charset, Disjunction, Repeat, Sequence};
 
 impl fmt::Display for Ast {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let multiline = self.start_anchor && self.multiline_start;
+		if multiline {
+			f.write_str("(?m)")?;
+		}
+
 		if self.start_anchor {
 			f.write_char('^')?;
 		}
@@ -13,6 +18,12 @@ impl fmt::Display for Ast {
 		self.disjunction.fmt(f)?;
 
 		if self.end_anchor {
+			match (multiline, self.multiline_end) {
+				(false, true) => f.write_str("(?m)")?,
+				(true, false) => f.write_str("(?-m)")?,
+				_ => (),
+			}
+
 			f.write_char('$')
 		} else {
 			Ok(())
@@ -55,6 +66,11 @@ impl fmt::Display for Atom {
 				repeat.fmt(f)
 			}
 			Self::Group(g) => {
+				f.write_str("(?:")?;
+				g.fmt(f)?;
+				f.write_char(')')
+			}
+			Self::Capture(g) => {
 				f.write_char('(')?;
 				g.fmt(f)?;
 				f.write_char(')')
@@ -65,38 +81,27 @@ impl fmt::Display for Atom {
 
 impl fmt::Display for Charset {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_char('[')?;
+
 		if self.negative {
 			f.write_char('^')?;
 		}
 
+		for class in &self.classes {
+			write!(f, "[:{}:]", class.name())?
+		}
+
 		for &range in &self.set {
 			fmt_range(range, f)?
 		}
 
-		Ok(())
+		f.write_char(']')
 	}
 }
 
 impl fmt::Display for Repeat {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		if self.min == 0 && self.max == Some(1) {
-			f.write_char('?')
-		} else if self.min == 0 && self.max.is_none() {
-			f.write_char('*')
-		} else if self.min == 1 && self.max.is_some() {
-			f.write_char('+')
-		} else {
-			match self.max {
-				Some(max) => {
-					if self.min == max {
-						write!(f, "{{{}}}", self.min)
-					} else {
-						write!(f, "{{{},{}}}", self.min, max)
-					}
-				}
-				None => write!(f, "{{{},}}", self.min),
-			}
-		}
+		self.build().fmt(f)
 	}
 }
 
@@ -115,32 +120,32 @@ pub fn fmt_range(range: AnyRange<char>, f: &mut fmt::Formatter) -> fmt::Result {
 	}
 }
 
-pub fn fmt_char(c: char, f: &mut fmt::Formatter) -> fmt::Result {
-	match c {
-		'(' => write!(f, "\\("),
-		')' => write!(f, "\\)"),
-		'[' => write!(f, "\\["),
-		']' => write!(f, "\\]"),
-		'{' => write!(f, "\\{{"),
-		'}' => write!(f, "\\}}"),
-		'?' => write!(f, "\\?"),
-		'*' => write!(f, "\\*"),
-		'+' => write!(f, "\\+"),
-		'-' => write!(f, "\\-"),
-		'^' => write!(f, "\\^"),
-		'|' => write!(f, "\\|"),
-		'\\' => write!(f, "\\\\"),
-		'\0' => write!(f, "\\0"),
-		'\x07' => write!(f, "\\a"),
-		'\x08' => write!(f, "\\b"),
-		'\t' => write!(f, "\\t"),
-		'\n' => write!(f, "\\n"),
-		'\x0b' => write!(f, "\\v"),
-		'\x0c' => write!(f, "\\f"),
-		'\r' => write!(f, "\\r"),
-		'\x1b' => write!(f, "\\e"),
-		_ => fmt::Display::fmt(&c, f),
+/// Escapes the metacharacters of the given string, so that it can be embedded
+/// in a pattern where it matches literally.
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(iregex_syntax::escape("a.b*"), "a\\.b\\*");
+/// ```
+pub fn escape(s: &str) -> String {
+	struct Escaped<'a>(&'a str);
+
+	impl<'a> fmt::Display for Escaped<'a> {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			for c in self.0.chars() {
+				fmt_char(c, f)?
+			}
+
+			Ok(())
+		}
 	}
+
+	Escaped(s).to_string()
+}
+
+pub fn fmt_char(c: char, f: &mut fmt::Formatter) -> fmt::Result {
+	fmt::Display::fmt(&EscapedChar(c), f)
 }
 
 // #[cfg(test)]
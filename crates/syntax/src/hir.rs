@@ -0,0 +1,169 @@
+use regex_syntax::hir::{self, Hir, HirKind, Look};
+
+use crate::{Ast, Atom, Charset, Disjunction, Repeat, Sequence};
+
+/// Error returned when converting a [`regex-syntax`] HIR into an [`Ast`].
+///
+/// [`regex-syntax`]: <https://crates.io/crates/regex-syntax>
+#[derive(Debug, thiserror::Error)]
+pub enum ConvertError {
+	#[error("unsupported assertion `{0:?}`")]
+	UnsupportedLook(Look),
+
+	#[error("invalid UTF-8 literal")]
+	InvalidLiteral,
+
+	#[error("non-ASCII byte class")]
+	ByteClass,
+}
+
+impl Ast {
+	/// Converts a [`regex-syntax`] HIR into an abstract syntax tree.
+	///
+	/// Only the start (`\A`) and end (`\z`) assertions, and their multiline
+	/// counterparts (`(?m)^` and `(?m)$`), are supported, at the beginning
+	/// and end of the expression. Non-greedy repetitions are converted into
+	/// greedy ones, which recognize the same language.
+	///
+	/// [`regex-syntax`]: <https://crates.io/crates/regex-syntax>
+	pub fn from_hir(hir: &Hir) -> Result<Self, ConvertError> {
+		let mut items = match hir.kind() {
+			HirKind::Concat(items) => items.as_slice(),
+			_ => std::slice::from_ref(hir),
+		};
+
+		let (start_anchor, multiline_start) = match items.split_first() {
+			Some((first, rest)) if is_look(first, Look::Start) => {
+				items = rest;
+				(true, false)
+			}
+			Some((first, rest)) if is_look(first, Look::StartLF) => {
+				items = rest;
+				(true, true)
+			}
+			_ => (false, false),
+		};
+
+		let (end_anchor, multiline_end) = match items.split_last() {
+			Some((last, rest)) if is_look(last, Look::End) => {
+				items = rest;
+				(true, false)
+			}
+			Some((last, rest)) if is_look(last, Look::EndLF) => {
+				items = rest;
+				(true, true)
+			}
+			_ => (false, false),
+		};
+
+		let disjunction = match items {
+			[hir] => Disjunction::from_hir(hir)?,
+			items => Sequence::from_hirs(items)?.into(),
+		};
+
+		Ok(Self {
+			start_anchor,
+			end_anchor,
+			multiline_start,
+			multiline_end,
+			disjunction,
+		})
+	}
+}
+
+fn is_look(hir: &Hir, look: Look) -> bool {
+	matches!(hir.kind(), HirKind::Look(l) if *l == look)
+}
+
+impl Disjunction {
+	fn from_hir(hir: &Hir) -> Result<Self, ConvertError> {
+		match hir.kind() {
+			HirKind::Alternation(items) => items
+				.iter()
+				.map(Sequence::from_hir)
+				.collect::<Result<_, _>>()
+				.map(Self),
+			_ => Sequence::from_hir(hir).map(Into::into),
+		}
+	}
+}
+
+impl Sequence {
+	fn from_hir(hir: &Hir) -> Result<Self, ConvertError> {
+		match hir.kind() {
+			HirKind::Concat(items) => Self::from_hirs(items),
+			_ => Self::from_hirs(std::slice::from_ref(hir)),
+		}
+	}
+
+	fn from_hirs(items: &[Hir]) -> Result<Self, ConvertError> {
+		let mut result = Self::new();
+
+		for hir in items {
+			match hir.kind() {
+				HirKind::Empty => (),
+				HirKind::Literal(hir::Literal(bytes)) => {
+					for c in literal_chars(bytes)? {
+						result.push(Atom::Char(c))
+					}
+				}
+				_ => result.push(Atom::from_hir(hir)?),
+			}
+		}
+
+		Ok(result)
+	}
+}
+
+impl Atom {
+	fn from_hir(hir: &Hir) -> Result<Self, ConvertError> {
+		match hir.kind() {
+			HirKind::Literal(hir::Literal(bytes)) => match literal_chars(bytes)?.as_slice() {
+				[c] => Ok(Self::Char(*c)),
+				_ => Disjunction::from_hir(hir).map(Self::Group),
+			},
+			HirKind::Class(class) => charset_from_hir(class).map(Self::Set),
+			HirKind::Look(look) => Err(ConvertError::UnsupportedLook(*look)),
+			HirKind::Repetition(r) => Ok(Self::Repeat(
+				Box::new(Self::from_hir(&r.sub)?),
+				Repeat {
+					min: r.min,
+					max: r.max,
+				},
+			)),
+			HirKind::Capture(c) => Disjunction::from_hir(&c.sub).map(Self::Capture),
+			HirKind::Empty | HirKind::Concat(_) | HirKind::Alternation(_) => {
+				Disjunction::from_hir(hir).map(Self::Group)
+			}
+		}
+	}
+}
+
+fn literal_chars(bytes: &[u8]) -> Result<Vec<char>, ConvertError> {
+	std::str::from_utf8(bytes)
+		.map(|s| s.chars().collect())
+		.map_err(|_| ConvertError::InvalidLiteral)
+}
+
+fn charset_from_hir(class: &hir::Class) -> Result<Charset, ConvertError> {
+	let mut result = Charset::new();
+
+	match class {
+		hir::Class::Unicode(class) => {
+			for range in class.ranges() {
+				result.insert_range(range.start()..=range.end())
+			}
+		}
+		hir::Class::Bytes(class) => {
+			for range in class.ranges() {
+				if !range.end().is_ascii() {
+					return Err(ConvertError::ByteClass);
+				}
+
+				result.insert_range(char::from(range.start())..=char::from(range.end()))
+			}
+		}
+	}
+
+	Ok(result)
+}
@@ -4,9 +4,10 @@
 //! ([`char`] type) using the [`iregex-automata`] library.
 //!
 //! [`iregex-automata`]: <https://crates.io/crates/iregex-automata>
-use iregex::automata::RangeSet;
+use iregex::automata::{token_set_intersection, AnyRange, RangeSet};
+use iregex::CaptureGroupId;
 use replace_with::replace_with_or_abort;
-use std::ops::Deref;
+use std::{collections::HashMap, ops::Deref};
 
 mod parsing;
 pub use parsing::*;
@@ -15,12 +16,27 @@ mod display;
 pub use display::*;
 
 mod build;
+pub use build::*;
+
+mod simplify;
+
+#[cfg(feature = "regex-syntax")]
+mod hir;
+#[cfg(feature = "regex-syntax")]
+pub use hir::*;
 
 /// Abstract syntax tree of an Extended Regular Expression.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Ast {
 	pub start_anchor: bool,
 	pub end_anchor: bool,
+
+	/// Whether the start anchor also matches after a line feed (`(?m)^`).
+	pub multiline_start: bool,
+
+	/// Whether the end anchor also matches before a line feed (`(?m)$`).
+	pub multiline_end: bool,
+
 	pub disjunction: Disjunction,
 }
 
@@ -29,6 +45,8 @@ impl Ast {
 		Self {
 			start_anchor: false,
 			end_anchor: false,
+			multiline_start: false,
+			multiline_end: false,
 			disjunction: Disjunction::new(),
 		}
 	}
@@ -37,6 +55,49 @@ impl Ast {
 		self.disjunction.is_empty()
 	}
 
+	/// Returns the minimum and maximum number of characters consumed by any
+	/// match of this regular expression.
+	///
+	/// The maximum is `None` if the length of a match is unbounded.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex_syntax::Ast;
+	/// let ast = Ast::parse("a{2,4}b".chars()).unwrap();
+	/// assert_eq!(ast.match_length_bounds(), (3, Some(5)));
+	/// ```
+	pub fn match_length_bounds(&self) -> (usize, Option<usize>) {
+		self.disjunction.match_length_bounds()
+	}
+
+	/// Returns, for each capture group, whether it participates in every
+	/// match (`true`) or may be skipped (`false`).
+	///
+	/// Capture groups are numbered from `0` in the order of their opening
+	/// parenthesis, as in the regular expression returned by [`Self::build`].
+	/// Non-capturing groups such as `(?:...)` or `(?i:...)` are not numbered.
+	/// A group is optional when it appears under a repetition accepting zero
+	/// occurrences (`?`, `*`, `{0,n}`) or in an alternative of a disjunction
+	/// with more than one alternative.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex::CaptureGroupId;
+	/// # use iregex_syntax::Ast;
+	/// let ast = Ast::parse("(a)(b)?".chars()).unwrap();
+	/// let optionality = ast.group_optionality();
+	/// assert!(optionality[&CaptureGroupId(0)]);
+	/// assert!(!optionality[&CaptureGroupId(1)]);
+	/// ```
+	pub fn group_optionality(&self) -> HashMap<CaptureGroupId, bool> {
+		let mut result = HashMap::new();
+		self.disjunction
+			.collect_group_optionality(false, &mut result);
+		result
+	}
+
 	// /// Checks if this regular expression matches only one value.
 	// pub fn is_singleton(&self) -> bool {
 	// 	match self {
@@ -81,6 +142,38 @@ impl Disjunction {
 	pub fn new() -> Self {
 		Self::default()
 	}
+
+	/// Returns the minimum and maximum number of characters consumed by any
+	/// match of this disjunction.
+	///
+	/// See [`Ast::match_length_bounds`].
+	pub fn match_length_bounds(&self) -> (usize, Option<usize>) {
+		let mut bounds: Option<(usize, Option<usize>)> = None;
+
+		for sequence in self {
+			let (min, max) = sequence.match_length_bounds();
+			bounds = Some(match bounds {
+				Some((other_min, other_max)) => (
+					min.min(other_min),
+					max.zip(other_max).map(|(a, b)| a.max(b)),
+				),
+				None => (min, max),
+			})
+		}
+
+		bounds.unwrap_or((0, Some(0)))
+	}
+
+	fn collect_group_optionality(
+		&self,
+		optional: bool,
+		result: &mut HashMap<CaptureGroupId, bool>,
+	) {
+		let optional = optional || self.len() > 1;
+		for sequence in self {
+			sequence.collect_group_optionality(optional, result)
+		}
+	}
 }
 
 impl Deref for Disjunction {
@@ -131,6 +224,30 @@ impl Sequence {
 	pub fn into_disjunction(self) -> Disjunction {
 		self.into()
 	}
+
+	/// Returns the minimum and maximum number of characters consumed by any
+	/// match of this sequence.
+	///
+	/// See [`Ast::match_length_bounds`].
+	pub fn match_length_bounds(&self) -> (usize, Option<usize>) {
+		self.iter().fold((0, Some(0)), |(min, max), atom| {
+			let (atom_min, atom_max) = atom.match_length_bounds();
+			(
+				min.saturating_add(atom_min),
+				max.zip(atom_max).and_then(|(a, b)| a.checked_add(b)),
+			)
+		})
+	}
+
+	fn collect_group_optionality(
+		&self,
+		optional: bool,
+		result: &mut HashMap<CaptureGroupId, bool>,
+	) {
+		for atom in self {
+			atom.collect_group_optionality(optional, result)
+		}
+	}
 }
 
 impl Deref for Sequence {
@@ -183,14 +300,62 @@ pub enum Atom {
 	/// Repetition.
 	Repeat(Box<Self>, Repeat),
 
-	/// Capture group.
+	/// Non-capturing group.
+	///
+	/// `(?:...)`, or a scoped flag group such as `(?i:...)`.
 	Group(Disjunction),
+
+	/// Capture group.
+	///
+	/// `(...)`
+	Capture(Disjunction),
 }
 
 impl Atom {
 	pub fn repeat(&mut self, r: Repeat) {
 		replace_with_or_abort(self, |this| Self::Repeat(Box::new(this), r))
 	}
+
+	/// Returns the minimum and maximum number of characters consumed by any
+	/// match of this atom.
+	///
+	/// See [`Ast::match_length_bounds`].
+	pub fn match_length_bounds(&self) -> (usize, Option<usize>) {
+		match self {
+			Self::Any | Self::Char(_) | Self::Set(_) => (1, Some(1)),
+			Self::Group(g) | Self::Capture(g) => g.match_length_bounds(),
+			Self::Repeat(atom, repeat) => {
+				let (min, max) = atom.match_length_bounds();
+				let max = match max {
+					_ if repeat.max == Some(0) => Some(0),
+					Some(0) => Some(0),
+					Some(max) => repeat.max.and_then(|n| max.checked_mul(n as usize)),
+					None => None,
+				};
+
+				(min.saturating_mul(repeat.min as usize), max)
+			}
+		}
+	}
+
+	fn collect_group_optionality(
+		&self,
+		optional: bool,
+		result: &mut HashMap<CaptureGroupId, bool>,
+	) {
+		match self {
+			Self::Any | Self::Char(_) | Self::Set(_) => (),
+			Self::Group(g) => g.collect_group_optionality(optional, result),
+			Self::Capture(g) => {
+				let id = CaptureGroupId(result.len() as u32);
+				result.insert(id, !optional);
+				g.collect_group_optionality(optional, result)
+			}
+			Self::Repeat(atom, repeat) => {
+				atom.collect_group_optionality(optional || repeat.min == 0, result)
+			}
+		}
+	}
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -200,6 +365,53 @@ pub struct Charset {
 	set: RangeSet<char>,
 }
 
+impl Charset {
+	/// Creates a new empty (non-negated) character set.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets whether the character set is negated (`[^...]`).
+	pub fn with_negation(mut self, negative: bool) -> Self {
+		self.negative = negative;
+		self
+	}
+
+	/// Checks if the character set is negated.
+	pub fn is_negative(&self) -> bool {
+		self.negative
+	}
+
+	/// Returns the POSIX classes of the character set.
+	pub fn classes(&self) -> &Classes {
+		&self.classes
+	}
+
+	/// Returns the characters and ranges of the character set, without its
+	/// classes.
+	pub fn set(&self) -> &RangeSet<char> {
+		&self.set
+	}
+
+	/// Adds the given character range to the set.
+	pub fn insert_range(&mut self, range: impl Into<AnyRange<char>>) {
+		self.set.insert(range.into())
+	}
+
+	/// Adds the given POSIX class to the set.
+	pub fn insert_class(&mut self, class: Class) {
+		self.classes.insert(class)
+	}
+
+	/// Computes the intersection of two character sets.
+	///
+	/// Negations and POSIX classes are resolved: the result is a
+	/// non-negated set of characters, without classes.
+	pub fn intersection(&self, other: &Self) -> Self {
+		token_set_intersection(&self.build(), &other.build()).into()
+	}
+}
+
 impl From<RangeSet<char>> for Charset {
 	fn from(value: RangeSet<char>) -> Self {
 		Self {
@@ -293,7 +505,7 @@ impl IntoIterator for Classes {
 	}
 }
 
-impl<'a> IntoIterator for &'a Classes {
+impl IntoIterator for &Classes {
 	type IntoIter = ClassesIter;
 	type Item = Class;
 
@@ -317,22 +529,34 @@ classes! {
 	Print:  "print"  (CLASS_PRINT:  0b1000000000000)
 }
 
+/// Repetition.
+///
+/// Converts to and from [`iregex::Repeat`], which uses the same convention.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Repeat {
+	/// Minimum number of repetitions.
 	pub min: u32,
+
+	/// Maximum number of repetitions, `None` if unbounded.
 	pub max: Option<u32>,
 }
 
 #[cfg(test)]
 mod tests {
-	use iregex::automata::nfa::U32StateBuilder;
+	use iregex::automata::{
+		any_char, nfa::U32StateBuilder, token_set_intersection, Automaton, RangeSet,
+	};
+	use iregex::CaptureGroupId;
 
-	use crate::Ast;
+	use crate::{
+		Ast, BuildError, Charset, Class, Classes, Error, ParseIRegEx, ParseOptions, Repeat,
+		Unexpected,
+	};
 
 	#[test]
 	fn test1() {
 		let ast = Ast::parse("^#([^\n#][^\n]*)?$".chars()).unwrap();
-		let exp = ast.build();
+		let exp = ast.build().unwrap();
 		let aut = exp.compile(U32StateBuilder::new()).unwrap();
 
 		assert!(aut.matches_str("#").next().is_some());
@@ -358,4 +582,664 @@ mod tests {
 		// 	}
 		// }
 	}
+
+	#[test]
+	fn ascii_alphabet() {
+		let mut ascii = RangeSet::new();
+		ascii.insert('\0'..='\x7f');
+
+		for pattern in [".", "[^a]"] {
+			let ast = Ast::parse(pattern.chars()).unwrap();
+			let aut = ast
+				.build_over(&ascii)
+				.unwrap()
+				.compile(U32StateBuilder::new())
+				.unwrap();
+
+			assert!(aut.matches_str("b").next().is_some());
+			assert!(aut.matches_str("é").next().is_none());
+		}
+	}
+
+	#[test]
+	fn repeat_conversion() {
+		let repeat = Repeat { min: 1, max: None };
+		let ir: iregex::Repeat = repeat.into();
+		assert_eq!(ir, iregex::Repeat { min: 1, max: None });
+		assert_eq!(Repeat::from(ir), repeat);
+		assert_eq!(repeat.to_string(), ir.to_string());
+	}
+
+	#[test]
+	fn absolute_anchors() {
+		let ast = Ast::parse("\\Aabc\\z".chars()).unwrap();
+		assert!(ast.start_anchor && ast.end_anchor);
+
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		assert!(aut.matches_str("abc").next().is_some());
+		assert!(aut.matches_str("xabc").next().is_none());
+		assert!(aut.matches_str("abc\nabc").next().is_none());
+	}
+
+	#[test]
+	fn negated_charset_with_class() {
+		let ast = Ast::parse("^[^a[:digit:]]$".chars()).unwrap();
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+
+		assert!(aut.matches_str("b").next().is_some());
+		assert!(aut.matches_str("a").next().is_none());
+		assert!(aut.matches_str("5").next().is_none());
+	}
+
+	#[test]
+	fn only_empty() {
+		for (pattern, expected) in [
+			("^a{0}$", true),
+			("^()$", true),
+			("^a?$", false),
+			("^a$", false),
+		] {
+			let nfa = Ast::parse(pattern.chars())
+				.unwrap()
+				.build()
+				.unwrap()
+				.compile_nfa(U32StateBuilder::new())
+				.unwrap();
+
+			assert_eq!(nfa.is_only_empty(), expected, "{pattern}")
+		}
+	}
+
+	#[test]
+	fn charset_builder() {
+		let mut charset = Charset::new().with_negation(true);
+		charset.insert_class(Class::Digit);
+		assert!(charset.is_negative());
+		assert!(charset.classes().contains(Class::Digit));
+
+		let set = charset.build();
+		assert!(set.contains('a'));
+		assert!(!set.contains('5'));
+
+		let mut charset = Charset::new();
+		charset.insert_range('a'..='c');
+		assert!(charset.set().contains('b'));
+	}
+
+	#[test]
+	fn build_ir() {
+		let a = iregex::Atom::Token(RangeSet::from_iter(['a']));
+		let b = iregex::Atom::Token(RangeSet::from_iter(['b']));
+		let expected: iregex::Alternation = iregex::Concatenation::from_iter([a, b]).into();
+
+		assert_eq!(
+			Ast::parse("ab".chars()).unwrap().build().unwrap().root,
+			expected
+		);
+	}
+
+	#[test]
+	fn intersects() {
+		let compile = |pattern: &str| {
+			Ast::parse(pattern.chars())
+				.unwrap()
+				.build()
+				.unwrap()
+				.compile(U32StateBuilder::new())
+				.unwrap()
+		};
+
+		let digits = compile("[0-9]+");
+		assert!(!digits.intersects(&compile("[a-z]+")));
+		assert!(digits.intersects(&compile("[[:digit:]]+")));
+		assert!(digits.intersects(&compile("a|1")));
+	}
+
+	#[test]
+	fn possessive_quantifiers() {
+		for (possessive, greedy) in [
+			("a++b", "a+b"),
+			("a*+b", "a*b"),
+			("a?+b", "a?b"),
+			("a{2}+", "a{2}"),
+		] {
+			assert_eq!(
+				Ast::parse(possessive.chars()).unwrap(),
+				Ast::parse(greedy.chars()).unwrap()
+			);
+		}
+
+		// Possessive semantics are not supported: `a*+a` is parsed as `a*a`.
+		let ast = Ast::parse("^a*+a$".chars()).unwrap();
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		assert_eq!(aut.matches_str("a").next(), Some(0..1));
+
+		let ast = Ast::parse("^a++b$".chars()).unwrap();
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		let mut matches = aut.matches_str("aaab");
+		assert_eq!(matches.next(), Some(0..4));
+		assert_eq!(matches.next(), None);
+	}
+
+	#[test]
+	fn repeat_round_trip() {
+		for (pattern, expected) in [
+			("a{3}", "a{3}"),
+			("a{3,3}", "a{3}"),
+			("a{0,0}", "a{0}"),
+			("a{0}", "a{0}"),
+			("a{2,5}", "a{2,5}"),
+			("a{2,}", "a{2,}"),
+			("a{1,}", "a+"),
+			("[cab]", "[a-c]"),
+			("[^x[:digit:]]", "[^[:digit:]x]"),
+		] {
+			let ast = Ast::parse(pattern.chars()).unwrap();
+			let displayed = ast.to_string();
+			assert_eq!(displayed, expected);
+			assert_eq!(Ast::parse(displayed.chars()).unwrap(), ast);
+		}
+	}
+
+	#[test]
+	fn ir_display_round_trip() {
+		let captures = |pattern: &str| {
+			Ast::parse(pattern.chars())
+				.unwrap()
+				.group_optionality()
+				.len()
+		};
+
+		for pattern in ["(?:a|b)c", "(?:ab)*", "(a|b)(?:c(d))+", "a(?:b|(c))?"] {
+			let ir = Ast::parse(pattern.chars()).unwrap().build().unwrap();
+			let displayed = ir.root.to_string();
+			assert_eq!(captures(&displayed), captures(pattern), "{displayed}");
+		}
+	}
+
+	#[test]
+	fn unicode_classes() {
+		let mut classes = Classes::none();
+		classes.insert(Class::Alpha);
+
+		let ascii = classes.to_range_set(false);
+		let unicode = classes.to_range_set(true);
+		assert_eq!(ascii, Class::Alpha.build());
+		assert_eq!(token_set_intersection(&ascii, &unicode), ascii);
+		assert_ne!(ascii, unicode);
+		assert!(unicode.contains('é') && !ascii.contains('é'));
+		assert!(!unicode.contains('1'));
+
+		let blank = Class::Blank.to_range_set(true);
+		assert!(blank.contains(' ') && blank.contains('\u{a0}') && !blank.contains('\n'));
+
+		// Only decimal digits (`Nd`), unlike `char::is_numeric`.
+		let digit = Class::Digit.to_range_set(true);
+		assert!(digit.contains('7') && digit.contains('\u{663}') && digit.contains('\u{1d7ce}'));
+		assert!(!digit.contains('\u{b2}') && !digit.contains('\u{216b}'));
+		let alnum = Class::Alnum.to_range_set(true);
+		assert!(alnum.contains('\u{663}') && !alnum.contains('\u{b2}'));
+	}
+
+	#[test]
+	fn full_match_dfa() {
+		let ast = Ast::parse("[^@]+@[^@]+".chars()).unwrap();
+
+		let full = ast.full_match_dfa().unwrap();
+		assert!(full.validate("a@b"));
+		assert!(!full.validate("a@b@c"));
+		assert!(!full.validate("@b"));
+
+		let unanchored = ast.compile_dfa().unwrap();
+		assert!(unanchored.validate("a@b"));
+		assert!(unanchored.validate("a@b@c"));
+	}
+
+	#[test]
+	fn parse_options() {
+		let options = ParseOptions {
+			grouping: false,
+			..Default::default()
+		};
+
+		let ast = Ast::parse_with("(a)".chars(), options).unwrap();
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		assert_eq!(aut.matches_str("x(a)y").next(), Some(1..4));
+		assert_eq!(aut.matches_str("a").next(), None);
+
+		let options = ParseOptions {
+			alternation: false,
+			brace_repetition: false,
+			..Default::default()
+		};
+
+		let ast = Ast::parse_with("a|b{2}".chars(), options).unwrap();
+		assert_eq!(ast, Ast::parse(r"a\|b\{2\}".chars()).unwrap());
+
+		assert!(Ast::parse_with("(a".chars(), ParseOptions::default()).is_err());
+	}
+
+	#[test]
+	fn escape() {
+		assert_eq!(crate::escape("a.b*"), "a\\.b\\*");
+
+		let literal = "^(a.b*)|[c]{1}+?$\\";
+		let ast = Ast::parse(format!("^{}$", crate::escape(literal)).chars()).unwrap();
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		assert!(aut.matches_str(literal).next().is_some());
+		assert!(aut.matches_str("^(axb*)|[c]{1}+?$\\").next().is_none());
+	}
+
+	#[test]
+	fn quote() {
+		let dfa = Ast::parse(r"\Qa.b*\E".chars())
+			.unwrap()
+			.full_match_dfa()
+			.unwrap();
+		assert!(dfa.validate("a.b*"));
+		assert!(!dfa.validate("ab"));
+		assert!(!dfa.validate("axbbb"));
+
+		assert_eq!(
+			Ast::parse(r"x\Q(|)\Ey+".chars()).unwrap(),
+			Ast::parse(r"x\(\|\)y+".chars()).unwrap()
+		);
+		assert_eq!(
+			Ast::parse(r"\Q$\\E*".chars()).unwrap(),
+			Ast::parse(r"\$\\*".chars()).unwrap()
+		);
+		assert_eq!(
+			Ast::parse(r"a\Q\Eb\Q[c".chars()).unwrap(),
+			Ast::parse(r"ab\[c".chars()).unwrap()
+		);
+		assert!(Ast::parse(r"\Q\E*".chars()).is_err());
+
+		// An unterminated quote runs to the end of the pattern, `\z` included.
+		let dfa = Ast::parse(r"\Qa\z".chars())
+			.unwrap()
+			.full_match_dfa()
+			.unwrap();
+		assert!(dfa.validate(r"a\z"));
+		assert!(!dfa.validate("a"));
+	}
+
+	#[test]
+	fn group_optionality() {
+		let optionality = |pattern: &str| {
+			let result = Ast::parse(pattern.chars()).unwrap().group_optionality();
+			(0..result.len() as u32)
+				.map(|i| result[&CaptureGroupId(i)])
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(optionality("(a)(b)?"), [true, false]);
+		assert_eq!(optionality("(a)|(b)"), [false, false]);
+		assert_eq!(optionality("((a)|b)+"), [true, false]);
+		assert_eq!(optionality("((a)(b))*c"), [false, false, false]);
+		assert_eq!(optionality("(a{0,2})(b){2}"), [true, true]);
+		assert_eq!(optionality("abc"), []);
+
+		// Non-capturing groups are not numbered.
+		assert_eq!(optionality("(?:a)(b)?"), [false]);
+		assert_eq!(optionality("(?i:(a))?(b)"), [false, true]);
+	}
+
+	#[test]
+	fn build_captures() {
+		let ast = Ast::parse("(a)(?:b)(c)".chars()).unwrap();
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		let (range, captures) = aut.matches_str("xabc").captures().next().unwrap();
+		assert_eq!(range, 1..4);
+		assert_eq!(captures.get(CaptureGroupId(0)), Some(1..2));
+		assert_eq!(captures.get(CaptureGroupId(1)), Some(3..4));
+		assert_eq!(captures.get(CaptureGroupId(2)), None);
+	}
+
+	#[test]
+	fn control_chars() {
+		let ast = Ast::parse("a[\n\x01]\x1b".chars()).unwrap();
+		let displayed = ast.to_string();
+		assert!(!displayed.contains('\n'));
+		assert!(displayed.contains("\\n"));
+		assert!(displayed.contains("\\u{1}"));
+		assert!(displayed.ends_with("\\e"));
+		assert_eq!(Ast::parse(displayed.chars()).unwrap(), ast);
+	}
+
+	#[test]
+	fn code_point_escapes() {
+		assert_eq!(
+			Ast::parse(r"\u{1F600}\u{a}".chars()).unwrap(),
+			Ast::parse("\u{1F600}\n".chars()).unwrap()
+		);
+		assert!(matches!(
+			Ast::parse(r"\u{d800}".chars()),
+			Err(Error::InvalidCodePoint(0xd800))
+		));
+		assert!(Ast::parse(r"\u{}".chars()).is_err());
+		assert!(Ast::parse(r"\u{1234567}".chars()).is_err());
+		assert!(Ast::parse(r"\u12".chars()).is_err());
+	}
+
+	#[test]
+	fn match_length_bounds() {
+		let bounds = |pattern: &str| Ast::parse(pattern.chars()).unwrap().match_length_bounds();
+
+		assert_eq!(bounds("a{2,4}b"), (3, Some(5)));
+		assert_eq!(bounds("a*"), (0, None));
+		assert_eq!(bounds("ab|c"), (1, Some(2)));
+		assert_eq!(bounds("(ab|c)+d"), (2, None));
+		assert_eq!(bounds("()*"), (0, Some(0)));
+		assert_eq!(bounds("(a*){0}"), (0, Some(0)));
+		assert_eq!(bounds("[a-z]{3}"), (3, Some(3)));
+	}
+
+	#[test]
+	fn inline_flags() {
+		let full_match = |pattern: &str, input: &str| {
+			Ast::parse(pattern.chars())
+				.unwrap()
+				.full_match_dfa()
+				.unwrap()
+				.validate(input)
+		};
+
+		assert!(full_match("(?i)abc", "ABC"));
+		assert!(full_match("(?i)abc", "aBc"));
+		assert!(full_match("a(?i)bc", "aBC"));
+		assert!(!full_match("a(?i)bc", "ABC"));
+
+		// Scoped flags.
+		assert!(full_match("a(?i:b)c", "aBc"));
+		assert!(!full_match("a(?i:b)c", "aBC"));
+
+		// Inline flags end with the enclosing group, but not with the
+		// alternative.
+		assert!(full_match("(a(?i)b)c", "aBc"));
+		assert!(!full_match("(a(?i)b)c", "aBC"));
+		assert!(full_match("a(?i)b|c", "C"));
+
+		// Sets and negation.
+		assert!(full_match("(?i)[a-c]+", "AbC"));
+		assert!(!full_match("(?i)[^a]", "A"));
+		assert!(full_match("(?i)(?-i)a|b", "a"));
+		assert!(!full_match("(?i)(?-i)a|b", "A"));
+
+		// Dot matches newlines unless `s` is disabled.
+		assert!(full_match(".", "\n"));
+		assert!(!full_match("(?-s).", "\n"));
+
+		// Case folding of large ranges.
+		assert!(full_match("(?i)[\0-\u{10FFFF}]", "A"));
+		assert!(full_match("(?i)[\\u{2000}-\\u{2200}]", "k"));
+
+		assert!(matches!(
+			Ast::parse("(?x)a".chars()),
+			Err(crate::Error::UnsupportedFlag('x'))
+		));
+	}
+
+	#[test]
+	fn multiline() {
+		let matches = |pattern: &str, input: &str| {
+			Ast::parse(pattern.chars())
+				.unwrap()
+				.compile_dfa()
+				.unwrap()
+				.validate(input)
+		};
+
+		assert!(matches("(?m)^b$", "a\nb\nc"));
+		assert!(matches("(?m)^b$", "b"));
+		assert!(!matches("(?m)^b$", "ab\nc"));
+		assert!(!matches("(?m)^b$", "a\nbc"));
+		assert!(!matches("^b$", "a\nb\nc"));
+
+		// Only the anchors following the flag are affected.
+		assert!(matches("^a(?m)$", "a\nb"));
+		assert!(!matches("^a(?m)$", "b\na"));
+		assert!(!matches("(?m)^a(?-m)$", "a\nb"));
+
+		// Absolute anchors ignore the flag.
+		assert!(!matches("(?m)\\Ab", "a\nb"));
+		assert!(!matches("(?m)b\\z", "b\na"));
+		assert!(!matches("(?m)a$\\z", "a\nb"));
+		assert!(matches("(?m)a$\\z", "b\na"));
+
+		for pattern in ["(?m)^a$", "^a(?m)$", "(?m)^a(?-m)$", "a(?m)$"] {
+			let ast = Ast::parse(pattern.chars()).unwrap();
+			assert_eq!(ast.to_string(), pattern);
+		}
+
+		// Matches span a single line.
+		let aut = Ast::parse("(?m)^b+$".chars())
+			.unwrap()
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		let ranges: Vec<_> = aut.matches_str("a\nbb\nb").collect();
+		assert_eq!(ranges, [2..4, 5..6]);
+
+		// `\z` wins over a multiline `$`.
+		let ast = Ast::parse("(?m)a$\\z".chars()).unwrap();
+		assert!(ast.end_anchor && !ast.multiline_end);
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		assert_eq!(aut.matches_str("a\nb").next(), None);
+	}
+
+	#[test]
+	fn unsupported_construct() {
+		// POSIX classes and capture groups are supported.
+		let ast = Ast::parse("^(a|[[:alpha:][:digit:]])*.{2,3}$".chars()).unwrap();
+		assert!(ast.build().is_ok());
+		assert!(ast.compile_dfa().is_ok());
+
+		// Repetition bounds too large to be represented are reported when
+		// building, instead of panicking or failing later.
+		let ast = Ast::parse("(a{2,1001})".chars()).unwrap();
+		assert!(matches!(ast.build(), Err(BuildError::RepeatTooLarge(1001))));
+		assert!(matches!(
+			ast.compile_dfa(),
+			Err(iregex::CompileError::RepeatTooLarge(1001))
+		));
+		assert!(matches!(
+			iregex::IRegEx::parse("a{1001}"),
+			Err(Error::Build(BuildError::RepeatTooLarge(1001)))
+		));
+	}
+
+	#[test]
+	fn charset_intersection() {
+		let range = |a, b| {
+			let mut charset = Charset::new();
+			charset.insert_range(a..=b);
+			charset
+		};
+
+		assert_eq!(
+			range('a', 'm').intersection(&range('h', 'z')),
+			range('h', 'm')
+		);
+
+		// Negations and classes are resolved.
+		let not_a_m = range('a', 'm').with_negation(true);
+		assert_eq!(not_a_m.intersection(&range('a', 'z')), range('n', 'z'));
+
+		let mut digits = Charset::new();
+		digits.insert_class(Class::Digit);
+		assert_eq!(digits.intersection(&range('5', 'z')), range('5', '9'));
+	}
+
+	#[test]
+	fn simplify() {
+		let simplify = |pattern: &str| Ast::parse(pattern.chars()).unwrap().simplify();
+		let parse = |pattern: &str| Ast::parse(pattern.chars()).unwrap();
+
+		assert_eq!(simplify("(?:(?:a))"), parse("a"));
+		assert_eq!(simplify("(?:(a))"), parse("(a)"));
+		assert_eq!(simplify("((?:a))"), parse("(a)"));
+		assert_eq!(simplify("(?:(?:(a|b)))c"), parse("(a|b)c"));
+		assert_eq!(simplify("(?:a|b)c"), parse("(?:a|b)c"));
+		assert_eq!(simplify("(?:a*)*"), parse("a*"));
+		assert_eq!(simplify("a**"), parse("a*"));
+		assert_eq!(simplify("a++|b??"), parse("a+|b?"));
+		assert_eq!(simplify("a(?:)b(?:)*"), parse("ab"));
+		assert_eq!(simplify("a{1}"), parse("a"));
+
+		// Capture groups are preserved, even if empty.
+		assert_eq!(simplify("((a))"), parse("((a))"));
+		assert_eq!(simplify("(a)"), parse("(a)"));
+		assert_eq!(simplify("a()b()*"), parse("a()b()*"));
+
+		// A capture group blocks the merge of repetitions.
+		assert_eq!(simplify("(a*)*"), parse("(a*)*"));
+		assert_eq!(simplify("(a?)?"), parse("(a?)?"));
+
+		// Not idempotent.
+		assert_eq!(simplify("(a{2}){2}"), parse("(a{2}){2}"));
+		assert_eq!(simplify("(a*)+"), parse("(a*)+"));
+		assert_eq!(simplify("(?:a?)+"), parse("(?:a?)+"));
+	}
+
+	#[test]
+	fn compile_bytes() {
+		let dfa = Ast::parse("[é]+".chars()).unwrap().compile_bytes().unwrap();
+		assert!(dfa.contains("éé".bytes()));
+		assert!(dfa.contains([0xc3, 0xa9, 0xc3, 0xa9]));
+		assert!(dfa.contains("aéb".bytes()));
+		assert!(!dfa.contains("ee".bytes()));
+		assert!(!dfa.contains([0xc3, 0xc3]));
+
+		let dfa = Ast::parse("^[é]+$".chars())
+			.unwrap()
+			.compile_bytes()
+			.unwrap();
+		assert!(dfa.contains("éé".bytes()));
+		assert!(!dfa.contains("éa".bytes()));
+		assert!(!dfa.contains([0xc3, 0xa9, 0xc3]));
+	}
+
+	#[test]
+	fn negate() {
+		let ast = Ast::parse("^foo$".chars()).unwrap();
+
+		let dfa = ast.negate(&any_char()).unwrap();
+		for s in ["bar", "", "fo", "fooo", "afoo", "Foo"] {
+			assert!(dfa.validate(s), "{s:?}")
+		}
+		assert!(!dfa.validate("foo"));
+
+		let mut lowercase = RangeSet::new();
+		lowercase.insert('a'..='z');
+		let dfa = ast.negate(&lowercase).unwrap();
+		assert!(dfa.validate("bar"));
+		assert!(!dfa.validate("foo"));
+		assert!(!dfa.validate("Bar"));
+
+		// Unanchored expressions are negated as if anchored.
+		let unanchored = Ast::parse("foo".chars()).unwrap();
+		assert_eq!(
+			unanchored.negate(&any_char()).unwrap(),
+			ast.negate(&any_char()).unwrap()
+		);
+	}
+
+	#[test]
+	fn repeat_errors() {
+		let parse = |pattern: &str| Ast::parse(pattern.chars());
+
+		for pattern in ["a{", "a{1", "a{1,", "a{1,2"] {
+			assert!(
+				matches!(parse(pattern), Err(Error::UnterminatedRepeat)),
+				"{pattern:?}"
+			)
+		}
+
+		assert!(matches!(parse("a{}"), Err(Error::EmptyRepeat)));
+
+		for pattern in ["a{99999999999}", "a{1,4294967296}"] {
+			assert!(
+				matches!(parse(pattern), Err(Error::RepeatOverflow)),
+				"{pattern:?}"
+			)
+		}
+
+		assert!(matches!(
+			parse("a{,2}"),
+			Err(Error::Unexpected(Unexpected::Char(',')))
+		));
+		assert!(matches!(
+			parse("a{1x}"),
+			Err(Error::Unexpected(Unexpected::Char('x')))
+		));
+
+		assert_eq!(parse("a{4294967295}").unwrap().to_string(), "a{4294967295}");
+		assert_eq!(parse("a{2,}").unwrap().to_string(), "a{2,}");
+	}
+
+	#[cfg(feature = "regex-syntax")]
+	#[test]
+	fn from_hir() {
+		let hir = regex_syntax::parse(r"\A(?:ab|c)+[0-9]?\z").unwrap();
+		let ast = Ast::from_hir(&hir).unwrap();
+		assert!(ast.start_anchor && ast.end_anchor);
+
+		let aut = ast
+			.build()
+			.unwrap()
+			.compile(U32StateBuilder::new())
+			.unwrap();
+		for (haystack, expected) in [
+			("abcab", true),
+			("cc7", true),
+			("ab77", false),
+			("", false),
+			("xab", false),
+		] {
+			assert_eq!(aut.matches_str(haystack).next().is_some(), expected);
+		}
+
+		let hir = regex_syntax::parse(r"(?m)^a$").unwrap();
+		let ast = Ast::from_hir(&hir).unwrap();
+		assert!(ast.multiline_start && ast.multiline_end);
+
+		let hir = regex_syntax::parse(r"a\b").unwrap();
+		assert!(matches!(
+			Ast::from_hir(&hir),
+			Err(crate::ConvertError::UnsupportedLook(_))
+		));
+	}
 }
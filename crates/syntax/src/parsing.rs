@@ -1,8 +1,14 @@
-use std::{borrow::Borrow, iter::Peekable, ops::Bound, str::FromStr};
+use std::{
+	borrow::Borrow,
+	iter::Peekable,
+	ops::Bound,
+	str::FromStr,
+	sync::{Mutex, PoisonError},
+};
 
 use iregex::automata::{AnyRange, RangeSet};
 
-use crate::{Ast, Atom, Charset, Class, Classes, Disjunction, Repeat, Sequence};
+use crate::{Ast, Atom, BuildError, Charset, Class, Classes, Disjunction, Repeat, Sequence};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -12,11 +18,38 @@ pub enum Error {
 	#[error("unexpected metacharacter `{0}`")]
 	UnexpectedMetacharacter(char),
 
+	/// Absolute anchor (`\A` or `\z`) that is not at the start or end of
+	/// the pattern. This includes bracket expressions, where `[\A]` used to
+	/// stand for the literal `A`.
+	#[error("unexpected anchor `\\{0}`")]
+	UnexpectedAnchor(char),
+
 	#[error("invalid class name `{0}`")]
 	InvalidClassName(String),
 
+	#[error("unsupported flag `{0}`")]
+	UnsupportedFlag(char),
+
+	/// Repetition bound overflow.
+	#[deprecated(note = "repetition bound overflows are reported as `RepeatOverflow`")]
 	#[error("overflow")]
 	Overflow,
+
+	#[error("unterminated repetition")]
+	UnterminatedRepeat,
+
+	#[error("empty repetition")]
+	EmptyRepeat,
+
+	#[error("repetition bound overflow")]
+	RepeatOverflow,
+
+	#[error("invalid code point `{0:x}`")]
+	InvalidCodePoint(u32),
+
+	/// The parsed regular expression cannot be built, see [`Ast::build`].
+	#[error(transparent)]
+	Build(BuildError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -37,6 +70,208 @@ impl From<Option<char>> for Unexpected {
 	}
 }
 
+/// Parsing options.
+///
+/// Each option enables a metacharacter. When disabled, the metacharacter is
+/// parsed as a literal character. The default options enable every
+/// metacharacter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+	/// Whether `(` and `)` delimit groups.
+	pub grouping: bool,
+
+	/// Whether `|` separates alternatives.
+	pub alternation: bool,
+
+	/// Whether `{` and `}` delimit bounded repetitions.
+	pub brace_repetition: bool,
+}
+
+impl Default for ParseOptions {
+	fn default() -> Self {
+		Self {
+			grouping: true,
+			alternation: true,
+			brace_repetition: true,
+		}
+	}
+}
+
+/// Inline flags, set with `(?flags)` for the rest of the enclosing group, or
+/// with `(?flags:...)` for the inner group only.
+///
+/// Flags are resolved while parsing: the parsed atoms already account for
+/// them.
+#[derive(Debug, Clone, Copy)]
+struct Flags {
+	/// Case-insensitive matching (`i`).
+	case_insensitive: bool,
+
+	/// Whether `.` matches `\n` (`s`).
+	dot_all: bool,
+
+	/// Whether `^` and `$` also match after and before `\n` (`m`).
+	multiline: bool,
+}
+
+impl Default for Flags {
+	fn default() -> Self {
+		Self {
+			case_insensitive: false,
+			dot_all: true,
+			multiline: false,
+		}
+	}
+}
+
+impl Flags {
+	/// Parses the flags of a `(?flags)` or `(?flags:` group, after the `(?`.
+	///
+	/// Flags following a `-` are disabled. Returns the updated flags, and
+	/// whether they are scoped to an inner group (`:`).
+	fn parse(mut self, chars: &mut impl Iterator<Item = char>) -> Result<(Self, bool), Error> {
+		let mut enable = true;
+
+		loop {
+			match chars.next() {
+				Some('i') => self.case_insensitive = enable,
+				Some('s') => self.dot_all = enable,
+				Some('m') => self.multiline = enable,
+				Some('-') if enable => enable = false,
+				Some(':') => break Ok((self, true)),
+				Some(')') => break Ok((self, false)),
+				Some(c) => break Err(Error::UnsupportedFlag(c)),
+				None => break Err(Error::Unexpected(Unexpected::EndOfStream)),
+			}
+		}
+	}
+
+	fn any(&self) -> Atom {
+		if self.dot_all {
+			Atom::Any
+		} else {
+			Atom::Set(Charset::from(RangeSet::from_iter(['\n'])).with_negation(true))
+		}
+	}
+
+	fn char(&self, c: char) -> Atom {
+		if self.case_insensitive {
+			let mut variants = case_variants(c).filter(|v| *v != c).peekable();
+			if variants.peek().is_some() {
+				let mut set = RangeSet::from_iter([c]);
+				set.extend(variants);
+				return Atom::Set(set.into());
+			}
+		}
+
+		Atom::Char(c)
+	}
+
+	fn set(&self, mut charset: Charset) -> Atom {
+		if self.case_insensitive {
+			let variants = set_case_variants(&charset.set);
+			charset.set.extend(variants);
+
+			if charset.classes.contains(Class::Upper) || charset.classes.contains(Class::Lower) {
+				charset.classes.insert(Class::Upper);
+				charset.classes.insert(Class::Lower);
+			}
+		}
+
+		Atom::Set(charset)
+	}
+}
+
+/// Returns the simple (single character) lowercase and uppercase variants of
+/// the given character.
+fn case_variants(c: char) -> impl Iterator<Item = char> {
+	fn simple(mut mapping: impl Iterator<Item = char>) -> Option<char> {
+		match (mapping.next(), mapping.next()) {
+			(Some(c), None) => Some(c),
+			_ => None,
+		}
+	}
+
+	let lower = simple(c.to_lowercase());
+	let upper = simple(c.to_uppercase());
+
+	[
+		lower,
+		upper,
+		lower.and_then(|l| simple(l.to_uppercase())),
+		upper.and_then(|u| simple(u.to_lowercase())),
+	]
+	.into_iter()
+	.flatten()
+}
+
+/// Returns the simple case variants of the characters of the given set.
+///
+/// Every character having a variant is listed once, then cached, so that
+/// each range of the set is looked up instead of enumerated.
+fn set_case_variants(set: &RangeSet<char>) -> Vec<char> {
+	static CACHE: Mutex<Vec<(char, char)>> = Mutex::new(Vec::new());
+
+	let mut pairs = CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+	if pairs.is_empty() {
+		*pairs = ('\0'..=char::MAX)
+			.flat_map(|c| {
+				case_variants(c)
+					.filter(move |v| *v != c)
+					.map(move |v| (c, v))
+			})
+			.collect();
+	}
+
+	let mut result = Vec::new();
+	for range in set.iter() {
+		if let (Some(first), Some(last)) = (range.first(), range.last()) {
+			let start = pairs.partition_point(|(c, _)| *c < first);
+			let end = pairs.partition_point(|(c, _)| *c <= last);
+			result.extend(pairs[start..end].iter().map(|(_, v)| *v));
+		}
+	}
+
+	result
+}
+
+/// Parses a group, starting with `(`.
+///
+/// Groups starting with `(?` are non-capturing. Returns `None` if the group
+/// only sets inline flags (`(?flags)`), in which case `flags` is updated.
+fn parse_group(
+	chars: &mut Peekable<impl Iterator<Item = char>>,
+	options: ParseOptions,
+	flags: &mut Flags,
+) -> Result<Option<Atom>, Error> {
+	chars.next();
+
+	let capturing = chars.peek() != Some(&'?');
+	let mut group_flags = if !capturing {
+		chars.next();
+		let (new_flags, scoped) = flags.parse(chars)?;
+		if !scoped {
+			*flags = new_flags;
+			return Ok(None);
+		}
+
+		new_flags
+	} else {
+		*flags
+	};
+
+	let (group, end_of_input) = Disjunction::parse_flagged(chars, options, &mut group_flags)?;
+	if end_of_input {
+		return Err(Error::UnexpectedAnchor('z'));
+	}
+
+	match chars.next() {
+		Some(')') if capturing => Ok(Some(Atom::Capture(group))),
+		Some(')') => Ok(Some(Atom::Group(group))),
+		other => Err(Error::Unexpected(other.into())),
+	}
+}
+
 enum AtomOrRepeat {
 	Atom(Atom),
 	Repeat(Repeat),
@@ -44,35 +279,51 @@ enum AtomOrRepeat {
 
 impl Atom {
 	pub fn parse(chars: &mut Peekable<impl Iterator<Item = char>>) -> Result<Option<Self>, Error> {
+		Self::parse_with(chars, ParseOptions::default())
+	}
+
+	pub fn parse_with(
+		chars: &mut Peekable<impl Iterator<Item = char>>,
+		options: ParseOptions,
+	) -> Result<Option<Self>, Error> {
+		Self::parse_flagged(chars, options, &mut Flags::default())
+	}
+
+	fn parse_flagged(
+		chars: &mut Peekable<impl Iterator<Item = char>>,
+		options: ParseOptions,
+		flags: &mut Flags,
+	) -> Result<Option<Self>, Error> {
 		let result = match chars.peek().copied() {
-			None | Some(')' | '|' | '$') => return Ok(None),
-			Some(c @ ('^' | ']' | '}' | '?' | '*' | '+')) => {
+			None | Some('$') => return Ok(None),
+			Some(')') if options.grouping => return Ok(None),
+			Some('|') if options.alternation => return Ok(None),
+			Some('}') if options.brace_repetition => {
+				return Err(Error::UnexpectedMetacharacter('}'))
+			}
+			Some(c @ ('^' | ']' | '?' | '*' | '+')) => {
 				return Err(Error::UnexpectedMetacharacter(c))
 			}
 			Some('.') => {
 				chars.next();
-				Self::Any
+				flags.any()
 			}
 			Some('[') => {
 				let charset = Charset::parse(chars)?;
-				Self::Set(charset)
-			}
-			Some('(') => {
-				chars.next();
-				let group = Disjunction::parse(chars)?;
-				match chars.next() {
-					Some(')') => Self::Group(group),
-					other => return Err(Error::Unexpected(other.into())),
-				}
+				flags.set(charset)
 			}
+			Some('(') if options.grouping => match parse_group(chars, options, flags)? {
+				Some(group) => group,
+				None => return Self::parse_flagged(chars, options, flags),
+			},
 			Some('\\') => {
 				chars.next();
 				let c = parse_escaped_char(chars)?;
-				Self::Char(c)
+				flags.char(c)
 			}
 			Some(c) => {
 				chars.next();
-				Self::Char(c)
+				flags.char(c)
 			}
 		};
 
@@ -81,27 +332,41 @@ impl Atom {
 }
 
 impl AtomOrRepeat {
-	pub fn parse(chars: &mut Peekable<impl Iterator<Item = char>>) -> Result<Option<Self>, Error> {
+	/// Parses an atom or a repetition operator.
+	///
+	/// A repetition operator may be followed by `+`, making it possessive
+	/// (e.g. `a++`, `a*+` or `a?+`). Possessive semantics are not supported:
+	/// such quantifiers are accepted so that Perl patterns parse, but are
+	/// parsed as their greedy counterpart. The resulting regular expression
+	/// may accept a larger language than the possessive one would. For
+	/// instance, `a*+a` never matches with possessive semantics, whereas it is
+	/// parsed as `a*a`, which matches `a`.
+	pub fn parse(
+		chars: &mut Peekable<impl Iterator<Item = char>>,
+		options: ParseOptions,
+		flags: &mut Flags,
+	) -> Result<Option<Self>, Error> {
 		let result = match chars.peek().copied() {
-			None | Some(')' | '|' | '$') => return Ok(None),
-			Some(c @ ('^' | ']' | '}')) => return Err(Error::UnexpectedMetacharacter(c)),
+			None | Some('$') => return Ok(None),
+			Some(')') if options.grouping => return Ok(None),
+			Some('|') if options.alternation => return Ok(None),
+			Some('}') if options.brace_repetition => {
+				return Err(Error::UnexpectedMetacharacter('}'))
+			}
+			Some(c @ ('^' | ']')) => return Err(Error::UnexpectedMetacharacter(c)),
 			Some('.') => {
 				chars.next();
-				Self::Atom(Atom::Any)
+				Self::Atom(flags.any())
 			}
 			Some('[') => {
 				let charset = Charset::parse(chars)?;
-				Self::Atom(Atom::Set(charset))
-			}
-			Some('(') => {
-				chars.next();
-				let group = Disjunction::parse(chars)?;
-				match chars.next() {
-					Some(')') => Self::Atom(Atom::Group(group)),
-					other => return Err(Error::Unexpected(other.into())),
-				}
+				Self::Atom(flags.set(charset))
 			}
-			Some('{') => Self::Repeat(Repeat::parse(chars)?),
+			Some('(') if options.grouping => match parse_group(chars, options, flags)? {
+				Some(group) => Self::Atom(group),
+				None => return Ok(Atom::parse_flagged(chars, options, flags)?.map(Self::Atom)),
+			},
+			Some('{') if options.brace_repetition => Self::Repeat(Repeat::parse(chars)?),
 			Some('?') => {
 				chars.next();
 				Self::Repeat(Repeat {
@@ -120,53 +385,128 @@ impl AtomOrRepeat {
 			Some('\\') => {
 				chars.next();
 				let c = parse_escaped_char(chars)?;
-				Self::Atom(Atom::Char(c))
+				Self::Atom(flags.char(c))
 			}
 			Some(c) => {
 				chars.next();
-				Self::Atom(Atom::Char(c))
+				Self::Atom(flags.char(c))
 			}
 		};
 
+		if matches!(result, Self::Repeat(_)) && chars.peek() == Some(&'+') {
+			chars.next();
+		}
+
 		Ok(Some(result))
 	}
 }
 
 impl Sequence {
 	pub fn parse(chars: &mut Peekable<impl Iterator<Item = char>>) -> Result<Self, Error> {
-		match Atom::parse(chars)? {
-			Some(atom) => {
-				let mut result = vec![atom];
-
-				while let Some(atom_or_repeat) = AtomOrRepeat::parse(chars)? {
-					match atom_or_repeat {
-						AtomOrRepeat::Atom(atom) => result.push(atom),
-						AtomOrRepeat::Repeat(r) => result.last_mut().unwrap().repeat(r),
+		Self::parse_with(chars, ParseOptions::default())
+	}
+
+	pub fn parse_with(
+		chars: &mut Peekable<impl Iterator<Item = char>>,
+		options: ParseOptions,
+	) -> Result<Self, Error> {
+		match Self::parse_flagged(chars, options, &mut Flags::default())? {
+			(_, true) => Err(Error::UnexpectedAnchor('z')),
+			(result, false) => Ok(result),
+		}
+	}
+
+	/// Parses a sequence, stopping at the end of the stream or at the first
+	/// character that cannot start an atom.
+	///
+	/// Also returns whether the sequence ends with the `\z` anchor, which
+	/// must then be the last thing in the stream.
+	fn parse_flagged(
+		chars: &mut Peekable<impl Iterator<Item = char>>,
+		options: ParseOptions,
+		flags: &mut Flags,
+	) -> Result<(Self, bool), Error> {
+		let mut result = Vec::new();
+
+		loop {
+			if chars.peek() == Some(&'\\') {
+				chars.next();
+				if chars.peek() == Some(&'Q') {
+					chars.next();
+					parse_quoted(chars, flags, &mut result);
+				} else if chars.next_if_eq(&'z').is_some() {
+					if chars.peek().is_some() {
+						return Err(Error::UnexpectedAnchor('z'));
 					}
+
+					return Ok((Self(result), true));
+				} else {
+					let c = parse_escaped_char(chars)?;
+					result.push(flags.char(c));
 				}
 
-				Ok(Self(result))
+				continue;
+			}
+
+			let atom_or_repeat = if result.is_empty() {
+				Atom::parse_flagged(chars, options, flags)?.map(AtomOrRepeat::Atom)
+			} else {
+				AtomOrRepeat::parse(chars, options, flags)?
+			};
+
+			match atom_or_repeat {
+				Some(AtomOrRepeat::Atom(atom)) => result.push(atom),
+				Some(AtomOrRepeat::Repeat(r)) => result.last_mut().unwrap().repeat(r),
+				None => break,
 			}
-			None => Ok(Self::new()),
 		}
+
+		Ok((Self(result), false))
 	}
 }
 
 impl Disjunction {
 	pub fn parse(chars: &mut Peekable<impl Iterator<Item = char>>) -> Result<Self, Error> {
-		let mut result = vec![Sequence::parse(chars)?];
+		Self::parse_with(chars, ParseOptions::default())
+	}
+
+	pub fn parse_with(
+		chars: &mut Peekable<impl Iterator<Item = char>>,
+		options: ParseOptions,
+	) -> Result<Self, Error> {
+		match Self::parse_flagged(chars, options, &mut Flags::default())? {
+			(_, true) => Err(Error::UnexpectedAnchor('z')),
+			(result, false) => Ok(result),
+		}
+	}
+
+	/// Parses a disjunction. Inline flags set in one alternative apply to
+	/// the following ones.
+	///
+	/// Also returns whether the last alternative ends with the `\z` anchor
+	/// (see [`Sequence::parse_flagged`]).
+	fn parse_flagged(
+		chars: &mut Peekable<impl Iterator<Item = char>>,
+		options: ParseOptions,
+		flags: &mut Flags,
+	) -> Result<(Self, bool), Error> {
+		let (sequence, mut end_of_input) = Sequence::parse_flagged(chars, options, flags)?;
+		let mut result = vec![sequence];
 		while let Some(c) = chars.peek().copied() {
 			match c {
-				'|' => {
+				'|' if options.alternation => {
 					chars.next();
-					result.push(Sequence::parse(chars)?)
+					let sequence;
+					(sequence, end_of_input) = Sequence::parse_flagged(chars, options, flags)?;
+					result.push(sequence)
 				}
-				')' | '$' => break,
+				')' if options.grouping => break,
+				'$' => break,
 				c => return Err(Error::UnexpectedMetacharacter(c)),
 			}
 		}
 
-		Ok(Self(result))
+		Ok((Self(result), end_of_input))
 	}
 }
 
@@ -180,28 +520,110 @@ impl FromStr for Disjunction {
 }
 
 impl Ast {
+	/// Parses a regular expression.
+	///
+	/// The pattern may start with `^` or `\A`, and end with `$` or `\z`, to
+	/// anchor it at the start and end of the input. In multiline mode, `^`
+	/// and `$` also match after and before a line feed, whereas `\A` and
+	/// `\z` only match at the start and end of the input, even when
+	/// following `$` (`$\z`).
+	///
+	/// Inline flags can be set with `(?flags)`, until the end of the
+	/// enclosing group, or `(?flags:...)`, for the inner group only. Flags
+	/// after a `-` are disabled. The supported flags are:
+	///   - `i`: case-insensitive matching;
+	///   - `s`: `.` matches `\n`, enabled by default;
+	///   - `m`: multiline mode, applying to the `^` and `$` anchors. Flags
+	///     set before `^`, as in `(?m)^`, apply to it.
+	///
+	/// Characters between `\Q` and `\E` (or the end of the pattern) are
+	/// matched literally, even metacharacters.
 	pub fn parse(chars: impl IntoIterator<Item = char>) -> Result<Self, Error> {
-		let mut chars = chars.into_iter().peekable();
+		Self::parse_with(chars, ParseOptions::default())
+	}
+
+	/// Parses a regular expression with the given options.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex_syntax::{Ast, ParseOptions};
+	/// let options = ParseOptions {
+	///     grouping: false,
+	///     ..Default::default()
+	/// };
+	///
+	/// assert_eq!(
+	///     Ast::parse_with("(a)".chars(), options).unwrap(),
+	///     Ast::parse(r"\(a\)".chars()).unwrap()
+	/// );
+	/// ```
+	pub fn parse_with(
+		chars: impl IntoIterator<Item = char>,
+		options: ParseOptions,
+	) -> Result<Self, Error> {
+		let mut chars = chars.into_iter().collect::<Vec<_>>().into_iter().peekable();
+
+		let mut flags = Flags::default();
+		if options.grouping {
+			while chars.peek() == Some(&'(') {
+				let mut lookahead = chars.clone();
+				lookahead.next();
+				if lookahead.next() != Some('?') {
+					break;
+				}
+
+				match flags.parse(&mut lookahead) {
+					Ok((new_flags, false)) => {
+						flags = new_flags;
+						chars = lookahead;
+					}
+					_ => break,
+				}
+			}
+		}
 
+		let multiline_start = flags.multiline && chars.peek() == Some(&'^');
 		let start_anchor = match chars.peek().copied() {
 			Some('^') => {
 				chars.next();
 				true
 			}
+			Some('\\') => {
+				let mut lookahead = chars.clone();
+				lookahead.next();
+				if lookahead.next() == Some('A') {
+					chars.next();
+					chars.next();
+					true
+				} else {
+					false
+				}
+			}
 			_ => false,
 		};
 
-		let inner = Disjunction::parse(&mut chars)?;
+		let (inner, end_of_input) = Disjunction::parse_flagged(&mut chars, options, &mut flags)?;
 
-		let end_anchor = match chars.next() {
-			Some('$') => true,
-			None => false,
+		let (end_anchor, multiline_end) = match chars.next() {
+			Some('$') => {
+				// `$\z`: the absolute anchor wins over multiline mode.
+				let absolute = chars.next_if_eq(&'\\').is_some();
+				if (absolute && chars.next() != Some('z')) || chars.peek().is_some() {
+					return Err(Error::UnexpectedMetacharacter('$'));
+				}
+
+				(true, flags.multiline && !absolute)
+			}
 			Some(c) => return Err(Error::UnexpectedMetacharacter(c)),
+			None => (end_of_input, false),
 		};
 
 		Ok(Self {
 			start_anchor,
 			end_anchor,
+			multiline_start,
+			multiline_end,
 			disjunction: inner,
 		})
 	}
@@ -215,6 +637,32 @@ impl FromStr for Ast {
 	}
 }
 
+/// Parses a regular expression directly into its intermediate
+/// representation.
+///
+/// This composes [`Ast::parse`] and [`Ast::build`].
+///
+/// # Example
+///
+/// ```
+/// # use iregex::{automata::nfa::U32StateBuilder, IRegEx};
+/// use iregex_syntax::ParseIRegEx;
+///
+/// let ire = IRegEx::parse("^a(b|c)*$").unwrap();
+/// let aut = ire.compile(U32StateBuilder::new()).unwrap();
+/// assert!(aut.matches_str("abcb").next().is_some());
+/// assert!(aut.matches_str("abd").next().is_none());
+/// ```
+pub trait ParseIRegEx: Sized {
+	fn parse(s: &str) -> Result<Self, Error>;
+}
+
+impl ParseIRegEx for iregex::IRegEx {
+	fn parse(s: &str) -> Result<Self, Error> {
+		Ast::parse(s.chars())?.build().map_err(Error::Build)
+	}
+}
+
 impl<S: Borrow<str>> From<S> for Ast {
 	fn from(s: S) -> Self {
 		let mut seq = Sequence::new();
@@ -226,6 +674,8 @@ impl<S: Borrow<str>> From<S> for Ast {
 		Self {
 			start_anchor: true,
 			end_anchor: true,
+			multiline_start: false,
+			multiline_end: false,
 			disjunction: Disjunction(vec![seq]),
 		}
 	}
@@ -352,50 +802,96 @@ impl Repeat {
 			other => return Err(Error::Unexpected(other.into())),
 		}
 
-		fn parse_number<T, C: Iterator<Item = char>>(
-			chars: &mut Peekable<C>,
-			f: impl FnOnce(&mut Peekable<C>, Option<u32>, char) -> Result<T, Error>,
-		) -> Result<T, Error> {
-			match chars.next() {
-				Some(c) => match c.to_digit(10) {
-					Some(mut value) => loop {
-						match chars.next() {
-							Some(c) => match c.to_digit(10) {
-								Some(d) => {
-									value = value.checked_mul(10).ok_or(Error::Overflow)?;
-									value = value.checked_add(d).ok_or(Error::Overflow)?;
-								}
-								None => break f(chars, Some(value), c),
-							},
-							None => break Err(Error::Unexpected(Unexpected::EndOfStream)),
-						}
-					},
-					None => f(chars, None, c),
-				},
-				None => Err(Error::Unexpected(Unexpected::EndOfStream)),
-			}
-		}
-
-		parse_number(chars, |chars, value, next| match value {
-			Some(min) => match next {
-				',' => parse_number(chars, |_, max, next| {
-					if next == '}' {
-						Ok(Self { min, max })
-					} else {
-						Err(Error::Unexpected(Unexpected::Char(next)))
-					}
-				}),
-				'}' => Ok(Self {
+		let min = parse_repeat_bound(chars)?;
+		match chars.next() {
+			Some('}') => match min {
+				Some(min) => Ok(Self {
 					min,
 					max: Some(min),
 				}),
-				c => Err(Error::Unexpected(Unexpected::Char(c))),
+				None => Err(Error::EmptyRepeat),
 			},
-			None => Err(Error::Unexpected(Unexpected::Char(next))),
-		})
+			Some(',') => {
+				let Some(min) = min else {
+					return Err(Error::Unexpected(Unexpected::Char(',')));
+				};
+
+				let max = parse_repeat_bound(chars)?;
+				match chars.next() {
+					Some('}') => Ok(Self { min, max }),
+					Some(c) => Err(Error::Unexpected(Unexpected::Char(c))),
+					None => Err(Error::UnterminatedRepeat),
+				}
+			}
+			Some(c) => Err(Error::Unexpected(Unexpected::Char(c))),
+			None => Err(Error::UnterminatedRepeat),
+		}
 	}
 }
 
+/// Parses an optional decimal repetition bound.
+fn parse_repeat_bound(
+	chars: &mut Peekable<impl Iterator<Item = char>>,
+) -> Result<Option<u32>, Error> {
+	let mut value = None;
+
+	while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+		chars.next();
+		let bound = value
+			.unwrap_or(0u32)
+			.checked_mul(10)
+			.and_then(|bound| bound.checked_add(d))
+			.ok_or(Error::RepeatOverflow)?;
+		value = Some(bound);
+	}
+
+	Ok(value)
+}
+
+/// Parses the characters of a `\Q...\E` quoted sequence, after the `\Q`.
+///
+/// Every character until `\E`, or the end of the stream, is taken literally.
+fn parse_quoted(
+	chars: &mut Peekable<impl Iterator<Item = char>>,
+	flags: &Flags,
+	result: &mut Vec<Atom>,
+) {
+	while let Some(c) = chars.next() {
+		if c == '\\' && chars.next_if_eq(&'E').is_some() {
+			break;
+		}
+
+		result.push(flags.char(c))
+	}
+}
+
+/// Parses the hexadecimal code point of a `\u{...}` escape sequence, after
+/// the `\u`.
+fn parse_code_point(chars: &mut impl Iterator<Item = char>) -> Result<char, Error> {
+	match chars.next() {
+		Some('{') => (),
+		other => return Err(Error::Unexpected(other.into())),
+	}
+
+	let mut code_point = 0;
+	let mut digits = 0;
+	loop {
+		match chars.next() {
+			Some('}') if digits > 0 => break,
+			Some(c) if digits < 6 => match c.to_digit(16) {
+				Some(d) => {
+					code_point = code_point * 16 + d;
+					digits += 1
+				}
+				None => return Err(Error::Unexpected(Unexpected::Char(c))),
+			},
+			other => return Err(Error::Unexpected(other.into())),
+		}
+	}
+
+	char::from_u32(code_point).ok_or(Error::InvalidCodePoint(code_point))
+}
+
 fn parse_escaped_char(chars: &mut impl Iterator<Item = char>) -> Result<char, Error> {
 	match chars.next() {
 		Some(c) => match c {
@@ -409,6 +905,8 @@ fn parse_escaped_char(chars: &mut impl Iterator<Item = char>) -> Result<char, Er
 			'f' => Ok('\x0c'),
 			'r' => Ok('\r'),
 			'e' => Ok('\x1b'),
+			'u' => parse_code_point(chars),
+			'A' | 'z' => Err(Error::UnexpectedAnchor(c)),
 			c => Ok(c),
 		},
 		None => Err(Error::Unexpected(Unexpected::EndOfStream)),
@@ -421,7 +919,7 @@ mod tests {
 
 	#[test]
 	fn parse_success() {
-		const INPUTS: [&str; 19] = [
+		const INPUTS: [&str; 24] = [
 			"",
 			"abc",
 			"(abc)",
@@ -441,6 +939,11 @@ mod tests {
 			"(abc){12,34}",
 			"(abc){12}",
 			"(abc){4294967295}",
+			"\\Aabc\\z",
+			"^abc\\z",
+			"abc\\\\z",
+			"abc$\\z",
+			"\\Qabc\\z",
 		];
 
 		for input in INPUTS {
@@ -452,7 +955,7 @@ mod tests {
 
 	#[test]
 	fn parse_failure() {
-		const INPUTS: [&str; 13] = [
+		const INPUTS: [&str; 20] = [
 			"?",
 			"(abc",
 			"[[:abc:]]",
@@ -466,6 +969,13 @@ mod tests {
 			"(abc){12,34",
 			"(abc){12",
 			"(abc){4294967296}",
+			"a\\Ab",
+			"a\\zb",
+			"(a\\z)",
+			"a\\z|b",
+			"a\\z$",
+			"a$b",
+			"[\\A]",
 		];
 
 		for input in INPUTS {
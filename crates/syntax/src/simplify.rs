@@ -0,0 +1,139 @@
+use crate::{Ast, Atom, Disjunction, Repeat, Sequence};
+
+impl Ast {
+	/// Simplifies the regular expression, without changing the language it
+	/// recognizes.
+	///
+	/// Non-capturing groups are removed when empty or wrapping a single
+	/// atom. Capture groups are always preserved, so that the numbering of
+	/// capture groups does not change. Nested identical repetitions are
+	/// merged when this does not change the language (e.g. `a**` and
+	/// `(?:a*)*` become `a*`), unless a capture group stands in between
+	/// (`(a*)*` is kept as is, since merging would change what the group
+	/// captures). Repeating exactly once is removed.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex_syntax::Ast;
+	/// let ast = Ast::parse("(?:(a))b{1}(?:)()".chars()).unwrap();
+	/// assert_eq!(ast.simplify().to_string(), "(a)b()");
+	/// ```
+	pub fn simplify(self) -> Self {
+		Self {
+			start_anchor: self.start_anchor,
+			end_anchor: self.end_anchor,
+			multiline_start: self.multiline_start,
+			multiline_end: self.multiline_end,
+			disjunction: self.disjunction.simplify(),
+		}
+	}
+}
+
+impl Disjunction {
+	/// Simplifies every sequence of the disjunction.
+	///
+	/// See [`Ast::simplify`].
+	pub fn simplify(self) -> Self {
+		Self(self.0.into_iter().map(Sequence::simplify).collect())
+	}
+
+	/// Returns the single atom of this disjunction, if it is made of a single
+	/// sequence of one atom.
+	fn as_single_atom(&self) -> Option<&Atom> {
+		match self.0.as_slice() {
+			[sequence] => match sequence.0.as_slice() {
+				[atom] => Some(atom),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+
+	/// Checks if this disjunction only matches the empty string.
+	fn is_empty_sequence(&self) -> bool {
+		matches!(self.0.as_slice(), [sequence] if sequence.is_empty())
+	}
+}
+
+impl Sequence {
+	/// Simplifies every atom of the sequence, removing empty groups.
+	///
+	/// See [`Ast::simplify`].
+	pub fn simplify(self) -> Self {
+		self.0
+			.into_iter()
+			.map(Atom::simplify)
+			.filter(|atom| !atom.is_empty_group())
+			.collect()
+	}
+}
+
+impl Atom {
+	/// Simplifies this atom.
+	///
+	/// See [`Ast::simplify`].
+	pub fn simplify(self) -> Self {
+		match self {
+			Self::Group(disjunction) => {
+				let disjunction = disjunction.simplify();
+				if disjunction.as_single_atom().is_some() {
+					disjunction.0.into_iter().next().unwrap().0.remove(0)
+				} else {
+					Self::Group(disjunction)
+				}
+			}
+			Self::Capture(disjunction) => Self::Capture(disjunction.simplify()),
+			Self::Repeat(atom, repeat) => {
+				let is_group = matches!(*atom, Self::Group(_));
+				let atom = atom.simplify();
+
+				if iregex::Repeat::from(repeat) == iregex::Repeat::ONCE {
+					return atom;
+				}
+
+				if repeat.is_idempotent() {
+					// A capture group in between blocks the merge, since it
+					// would change what the group captures.
+					let inner_repeat = match &atom {
+						Self::Repeat(_, r) => Some(*r),
+						_ => None,
+					};
+
+					if inner_repeat == Some(repeat) {
+						return atom;
+					}
+				}
+
+				// Keep the group of a repeated repetition (e.g. `(?:a?)+`).
+				let atom = match atom {
+					Self::Repeat(..) if is_group => {
+						Self::Group(Disjunction(vec![Sequence(vec![atom])]))
+					}
+					atom => atom,
+				};
+
+				Self::Repeat(Box::new(atom), repeat)
+			}
+			other => other,
+		}
+	}
+
+	/// Checks if this atom is an empty non-capturing group, possibly
+	/// repeated.
+	fn is_empty_group(&self) -> bool {
+		match self {
+			Self::Group(disjunction) => disjunction.is_empty_sequence(),
+			Self::Repeat(atom, _) => atom.is_empty_group(),
+			_ => false,
+		}
+	}
+}
+
+impl Repeat {
+	/// Checks if repeating a repetition with the same bounds does not change
+	/// anything (`?`, `*` and `+`).
+	fn is_idempotent(&self) -> bool {
+		self.min <= 1 && self.max.map_or(true, |max| max == 1)
+	}
+}
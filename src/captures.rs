@@ -0,0 +1,159 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	ops::Range,
+	rc::Rc,
+};
+
+use iregex_automata::{nfa::TaggedNFA, Token};
+
+use crate::{CaptureGroupId, CaptureTag};
+
+/// Capture group spans of a match, given in bytes.
+///
+/// A group that participated in the match has a span, which may be empty
+/// (e.g. `(a?)` when there is no `a`): it is then located at the position
+/// where the group matched. A group that did not participate in the match,
+/// such as a group in a non-taken alternative of `(a)|(b)`, has no span.
+/// When a group is repeated, its span is the one of the last iteration.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Captures(BTreeMap<CaptureGroupId, Range<usize>>);
+
+impl Captures {
+	/// Returns the span of the given group, or `None` if the group did not
+	/// participate in the match.
+	pub fn get(&self, id: CaptureGroupId) -> Option<Range<usize>> {
+		self.0.get(&id).cloned()
+	}
+
+	/// Returns an iterator over the spans of the groups that participated in
+	/// the match.
+	pub fn iter(&self) -> impl '_ + Iterator<Item = (CaptureGroupId, Range<usize>)> {
+		self.0.iter().map(|(id, span)| (*id, span.clone()))
+	}
+}
+
+/// Matching thread, following one path of the automaton.
+///
+/// Threads are shared between the paths they lead to, and only copied when
+/// a capture tag is applied.
+#[derive(Default, Clone)]
+struct Thread {
+	/// Start of the groups opened but not yet closed.
+	open: BTreeMap<CaptureGroupId, usize>,
+
+	/// Spans of the closed groups.
+	captures: Captures,
+}
+
+/// Applies the given capture tags to a thread at the given byte offset.
+fn apply<'t>(thread: &mut Rc<Thread>, tags: impl Iterator<Item = &'t CaptureTag>, offset: usize) {
+	for tag in tags {
+		let thread = Rc::make_mut(thread);
+		match tag {
+			CaptureTag::Begin(id) => {
+				thread.open.insert(*id, offset);
+			}
+			CaptureTag::End(id) => {
+				if let Some(start) = thread.open.remove(id) {
+					debug_assert!(start <= offset);
+					thread.captures.0.insert(*id, start..offset);
+				}
+			}
+		}
+	}
+}
+
+/// Runs the given tagged automaton on the tokens of a match starting at the
+/// given byte offset, returning the capture group spans if the tokens are
+/// accepted.
+///
+/// Threads are kept in priority order, and only the first thread reaching a
+/// state is kept. Epsilon transitions are followed in ascending target state
+/// order, and before the labeled transitions of their source state. The NFA
+/// construction makes this order follow the priority of the regular
+/// expression: alternatives from left to right, and greedy repetitions
+/// preferring one more iteration. When several paths accept the tokens, the
+/// one with the highest priority is used.
+pub(crate) fn captures<Q, T>(
+	aut: &TaggedNFA<Q, T, CaptureTag>,
+	tokens: impl Iterator<Item = T>,
+	mut offset: usize,
+) -> Option<Captures>
+where
+	Q: Copy + Ord,
+	T: Token,
+{
+	let initial = aut.initial_states().iter().map(|q| (*q, Rc::default()));
+	let mut threads = closure(aut, initial.collect(), offset);
+
+	for token in tokens {
+		offset += Token::len(&token);
+
+		let mut next = Vec::new();
+		let mut reached = BTreeSet::new();
+		for (q, thread) in threads {
+			for (label, targets) in aut.successors(&q) {
+				if label.as_ref().map_or(false, |label| label.contains(token)) {
+					for r in targets {
+						if reached.insert(*r) {
+							let mut thread = thread.clone();
+							apply(&mut thread, aut.tags.get(q, *r), offset);
+							next.push((*r, thread))
+						}
+					}
+				}
+			}
+		}
+
+		threads = closure(aut, next, offset);
+	}
+
+	threads
+		.into_iter()
+		.find(|(q, _)| aut.is_final_state(q))
+		.map(|(_, thread)| thread.captures.clone())
+}
+
+/// Follows the epsilon transitions from the given threads, keeping only the
+/// first thread reaching each state.
+///
+/// A state is listed after the states reachable through its epsilon
+/// transitions.
+fn closure<Q, T>(
+	aut: &TaggedNFA<Q, T, CaptureTag>,
+	threads: Vec<(Q, Rc<Thread>)>,
+	offset: usize,
+) -> Vec<(Q, Rc<Thread>)>
+where
+	Q: Copy + Ord,
+	T: Token,
+{
+	let mut visited = BTreeSet::new();
+	let mut result = Vec::new();
+	let mut stack: Vec<_> = threads
+		.into_iter()
+		.rev()
+		.map(|(q, thread)| (q, thread, false))
+		.collect();
+
+	while let Some((q, thread, leaving)) = stack.pop() {
+		if leaving {
+			result.push((q, thread))
+		} else if visited.insert(q) {
+			stack.push((q, thread.clone(), true));
+			for (label, targets) in aut.successors(&q) {
+				if label.is_none() {
+					for r in targets.iter().rev() {
+						if !visited.contains(r) {
+							let mut thread = thread.clone();
+							apply(&mut thread, aut.tags.get(q, *r), offset);
+							stack.push((*r, thread, false))
+						}
+					}
+				}
+			}
+		}
+	}
+
+	result
+}
@@ -1,28 +1,226 @@
-use std::{ops::Range, str::Chars};
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	hash::Hash,
+	marker::PhantomData,
+	ops::Range,
+	str::Chars,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+};
 
 use iregex_automata::{
-	nfa::TaggedNFA, Automaton, Class, Map, MapSource, RangeSet, Token, DFA, NFA,
+	dfa::TaggedDFA,
+	nfa::{TaggedNFA, TooManyStates, U32StateBuilder},
+	Automaton, Class, Map, MapSource, RangeSet, TaggedAutomaton, Token, DFA, NFA,
 };
 
-use crate::CaptureTag;
+use crate::{captures::captures, Atom, CaptureTag, Captures, CompileError, Haystack, IRegEx};
 
 /// Compound automaton, a compiled regular expression.
+///
+/// Matching never mutates the automaton: every call to [`Self::matches`]
+/// (or one of its variants) returns a fresh matcher starting from the
+/// initial state, borrowing the compiled automata. A compound automaton can
+/// hence be compiled once and reused for any number of haystacks. It is
+/// `Send` and `Sync` when its automata are, which is the case of all the
+/// automata of this crate, so it can also be shared between threads.
 pub struct CompoundAutomaton<A = TaggedNFA<u32, char, CaptureTag>, C: MapSource = ()> {
 	pub prefix: A,
+
+	/// Whether the prefix recognizes any sequence of tokens.
+	///
+	/// In this case every position of the haystack is a possible match start,
+	/// and the prefix automaton is not run during matching.
+	pub any_prefix: bool,
+
 	pub root: C::Map<A>,
 	pub suffix: C::Map<A>,
 }
 
+impl CompoundAutomaton {
+	/// Compiles a matcher recognizing exactly the given string.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use iregex::CompoundAutomaton;
+	/// let aut = CompoundAutomaton::literal("foo").unwrap();
+	/// assert_eq!(aut.matches_str("foo").collect::<Vec<_>>(), [0..3]);
+	/// assert_eq!(aut.matches_str("food").next(), None);
+	/// ```
+	pub fn literal(s: &str) -> Result<Self, CompileError<TooManyStates>> {
+		let literal = Atom::<char, ()>::Literal(s.chars().collect());
+		IRegEx::anchored(literal.into()).compile(U32StateBuilder::new())
+	}
+}
+
+impl<T: Token + Hash> CompoundAutomaton<TaggedDFA<T>> {
+	/// Builds a lexer recognizing the given patterns, see [`Self::tokens`].
+	///
+	/// Each token is tagged with the index of the pattern recognizing it,
+	/// the first pattern having priority (see [`NFA::to_tagged_dfa`]). The
+	/// prefix and suffix are anchors.
+	pub fn lexer<Q: Ord + Hash>(patterns: impl IntoIterator<Item = NFA<Q, T>>) -> Self {
+		let mut anchor = DFA::new(0);
+		anchor.add_final_state(0);
+		let anchor = TaggedDFA {
+			untagged: anchor,
+			tags: BTreeMap::new(),
+		};
+
+		Self {
+			prefix: anchor.clone(),
+			any_prefix: false,
+			root: Map::singleton((), NFA::from_patterns(patterns).to_tagged_dfa()),
+			suffix: Map::singleton((), anchor),
+		}
+	}
+}
+
 impl<A, C: MapSource> CompoundAutomaton<A, C> {
-	pub fn matches_str<'a>(&self, haystack: &'a str) -> Matches<A, C, Chars<'a>>
+	pub fn matches_str<'a>(&self, haystack: &'a str) -> Matches<'_, A, C, Chars<'a>>
+	where
+		A: Automaton<char>,
+		C: Default + Class,
+	{
+		self.matches(haystack)
+	}
+
+	/// Returns an iterator over the matches in the given string, reporting
+	/// positions as character indices rather than byte offsets.
+	pub fn matches_char_indices<'a>(
+		&self,
+		haystack: &'a str,
+	) -> CharIndicesMatches<'_, A, C, Chars<'a>>
 	where
 		A: Automaton<char>,
 		C: Default + Class,
 	{
-		self.matches(haystack.chars())
+		self.matches_str(haystack).char_indices()
+	}
+
+	/// Returns an iterator over the matches in the given character sequence,
+	/// reporting positions as character indices.
+	///
+	/// This is useful for haystacks that are not stored as a `str`, such as
+	/// characters decoded on the fly from another encoding.
+	pub fn matches_chars<I>(&self, haystack: I) -> CharIndicesMatches<'_, A, C, I>
+	where
+		I: Clone + Iterator<Item = char>,
+		A: Automaton<char>,
+		C: Default + Class,
+	{
+		self.matches_tokens(haystack).char_indices()
+	}
+
+	/// Returns an iterator over the matches in the given haystack.
+	pub fn matches<H>(&self, haystack: H) -> Matches<'_, A, C, H::Tokens>
+	where
+		H: Haystack,
+		A: Automaton<H::Token>,
+		C: Default + Class<H::Token>,
+	{
+		self.matches_tokens(haystack.tokens())
+	}
+
+	/// Returns an iterator over the matches in the first `max_tokens` tokens
+	/// of the given haystack.
+	///
+	/// At most one token past the bound is read, bounding the matching time
+	/// on untrusted inputs. The iteration stops before the first match that
+	/// depends on what follows the bound, so that every reported match is
+	/// also a match of the whole haystack.
+	pub fn matches_bounded<H>(
+		&self,
+		haystack: H,
+		max_tokens: usize,
+	) -> BoundedMatches<'_, A, C, H::Tokens>
+	where
+		H: Haystack,
+		A: Automaton<H::Token>,
+		C: Default + Class<H::Token>,
+	{
+		let cut = Arc::new(AtomicBool::new(false));
+		let tokens = Bounded {
+			tokens: haystack.tokens(),
+			remaining: max_tokens,
+			cut: cut.clone(),
+		};
+
+		BoundedMatches {
+			matches: self.matches_tokens(tokens),
+			cut,
+		}
+	}
+
+	/// Splits the given haystack into tokens, returning the range and tag of
+	/// each token.
+	///
+	/// Tokens are the longest non-empty words recognized by the tagged root
+	/// automaton, one after the other from the start of the haystack, with
+	/// the tag of the accepting state (e.g. the priority of the recognized
+	/// pattern with [`TaggedDFA`]). Iteration stops at the end of the
+	/// haystack or at the first position where no token is recognized, see
+	/// [`Lexer::offset`].
+	///
+	/// Tokens are read from the start of the haystack, so the automaton must
+	/// be anchored at the start, as the ones built with [`Self::lexer`]. The
+	/// prefix and suffix are read as the empty string.
+	///
+	/// # Panics
+	///
+	/// Panics if the prefix recognizes any sequence of tokens
+	/// ([`Self::any_prefix`]), or if the prefix or a suffix does not
+	/// recognize the empty string.
+	pub fn tokens<H, G>(&self, haystack: H) -> Lexer<'_, A, C, H::Tokens, G>
+	where
+		H: Haystack,
+		A: TaggedAutomaton<H::Token, G>,
+		C: Default + Class<H::Token>,
+	{
+		fn recognizes_empty<T, A: Automaton<T>>(aut: &A) -> bool {
+			aut.initial_state()
+				.map_or(false, |q| aut.is_final_state(&q))
+		}
+
+		assert!(
+			!self.any_prefix && recognizes_empty(&self.prefix),
+			"lexer prefix must be an anchor"
+		);
+		assert!(
+			self.suffix.iter().all(|(_, s)| recognizes_empty(s)),
+			"lexer suffix must recognize the empty string"
+		);
+
+		Lexer {
+			regex: self,
+			haystack: haystack.tokens(),
+			class: C::default(),
+			position: Position::default(),
+			tag: PhantomData,
+		}
+	}
+
+	/// Returns an iterator over the matches in the given haystack, from the
+	/// last one to the first one.
+	///
+	/// The matches are the same as the ones returned by [`Self::matches`],
+	/// in reverse order. Since the boundary classes of a position depend on
+	/// what precedes it, the haystack is still scanned forward, and all the
+	/// matches are collected before the first one is returned.
+	pub fn rmatches<H>(&self, haystack: H) -> RMatches
+	where
+		H: Haystack,
+		A: Automaton<H::Token>,
+		C: Default + Clone + Class<H::Token>,
+	{
+		RMatches(self.matches(haystack).collect::<Vec<_>>().into_iter().rev())
 	}
 
-	pub fn matches<H>(&self, haystack: H) -> Matches<A, C, H>
+	/// Returns an iterator over the matches in the given token sequence.
+	pub fn matches_tokens<H>(&self, haystack: H) -> Matches<'_, A, C, H>
 	where
 		H: Clone + Iterator,
 		H::Item: Clone,
@@ -34,32 +232,626 @@ impl<A, C: MapSource> CompoundAutomaton<A, C> {
 			prefix_state: self.prefix.initial_state(),
 			haystack,
 			class: C::default(),
-			position: 0,
+			position: Position::default(),
 			min: 0,
+			last_end: None,
+			shortest: false,
+		}
+	}
+}
+
+impl<A, C: MapSource> CompoundAutomaton<A, C> {
+	/// Returns the total number of states of the prefix, root and suffix
+	/// automata.
+	pub fn total_states<T, Q>(&self) -> usize
+	where
+		A: AsRef<NFA<Q, T>>,
+	{
+		self.prefix.as_ref().states().count()
+			+ self
+				.root
+				.iter()
+				.chain(self.suffix.iter())
+				.map(|(_, aut)| aut.as_ref().states().count())
+				.sum::<usize>()
+	}
+
+	/// Returns the set of tokens a match can start with, for every class.
+	///
+	/// This is useful to quickly discard positions where no match can start.
+	/// Note that if the root automaton recognizes the empty word, a match
+	/// can also start before any token.
+	pub fn first_set<T, Q>(&self) -> RangeSet<T>
+	where
+		A: AsRef<NFA<Q, T>>,
+		T: Token,
+		Q: Ord,
+	{
+		let mut result = RangeSet::new();
+
+		for (_, aut) in self.root.iter() {
+			result.extend(aut.as_ref().first_set().iter().copied())
+		}
+
+		result
+	}
+
+	/// Checks if this compiled regular expression and the `other` one can
+	/// match the same string.
+	///
+	/// The root automata must recognize a common string, preceded by a
+	/// common string of both prefixes and followed by a common string of both
+	/// suffixes. This accounts for anchoring: `^a` and `a$` overlap (in `a`),
+	/// but not `^a` and `xa` if the prefix of the latter must read `x`.
+	/// Unanchored expressions overlap as soon as their root automata do.
+	pub fn intersects<B, T, Q, R>(&self, other: &CompoundAutomaton<B, C>) -> bool
+	where
+		A: AsRef<NFA<Q, T>>,
+		B: AsRef<NFA<R, T>>,
+		T: Token,
+		Q: Ord + Hash,
+		R: Ord + Hash,
+		C: Default + Clone + Eq + Hash + Class<T>,
+	{
+		let a_prefix = prefix_nfa(self.prefix.as_ref(), self.any_prefix);
+		let b_prefix = prefix_nfa(other.prefix.as_ref(), other.any_prefix);
+
+		common_final_classes(&a_prefix, &b_prefix, [C::default()])
+			.into_iter()
+			.any(|class| {
+				let (Some(a), Some(b)) = (self.root.get(&class), other.root.get(&class)) else {
+					return false;
+				};
+
+				common_final_classes(a.as_ref(), b.as_ref(), [class])
+					.into_iter()
+					.any(|end_class| {
+						match (self.suffix.get(&end_class), other.suffix.get(&end_class)) {
+							(Some(a), Some(b)) => {
+								!common_final_classes(a.as_ref(), b.as_ref(), [end_class])
+									.is_empty()
+							}
+							_ => false,
+						}
+					})
+			})
+	}
+
+	/// Reports which classes reachable during matching have a root and suffix
+	/// automaton.
+	///
+	/// Matching silently ignores match starts (resp. ends) whose class has no
+	/// root (resp. suffix) automaton. Such a class reached at the end of the
+	/// prefix (resp. root) hence denotes an incomplete compilation.
+	pub fn class_coverage<T, Q>(&self) -> ClassCoverage<C>
+	where
+		A: AsRef<NFA<Q, T>>,
+		T: Token,
+		Q: Ord + Hash,
+		C: Default + Clone + Eq + Hash + Class<T>,
+	{
+		let root = final_classes(self.prefix.as_ref(), [C::default()])
+			.into_iter()
+			.map(|class| {
+				let covered = self.root.contains(&class);
+				(class, covered)
+			})
+			.collect();
+
+		let mut end_classes = Vec::new();
+		for (class, aut) in self.root.iter() {
+			for end_class in final_classes(aut.as_ref(), [class.clone()]) {
+				if !end_classes.contains(&end_class) {
+					end_classes.push(end_class)
+				}
+			}
+		}
+
+		let suffix = end_classes
+			.into_iter()
+			.map(|class| {
+				let covered = self.suffix.contains(&class);
+				(class, covered)
+			})
+			.collect();
+
+		ClassCoverage { root, suffix }
+	}
+
+	/// Returns the classes in which a match can end, when they have a suffix
+	/// automaton.
+	///
+	/// These are the classes reached at the end of a root automaton, in
+	/// which matching then runs the suffix automaton. Ends in any other class
+	/// are always rejected (see [`Self::class_coverage`]).
+	pub fn final_classes<T, Q>(&self) -> impl '_ + Iterator<Item = &C>
+	where
+		A: AsRef<NFA<Q, T>>,
+		T: Token,
+		Q: Ord + Hash,
+		C: Clone + Eq + Hash + Class<T>,
+	{
+		let mut end_classes = HashSet::new();
+		for (class, aut) in self.root.iter() {
+			end_classes.extend(final_classes(aut.as_ref(), [class.clone()]))
+		}
+
+		self.suffix
+			.iter()
+			.filter(move |(class, _)| end_classes.contains(class))
+			.map(|(class, _)| class)
+	}
+
+	/// Builds a compound automaton matching the concatenation of this
+	/// pattern and the `other` one.
+	///
+	/// The root automaton of `self` is followed by the root automaton of
+	/// `other` for the class reached at its end. As when concatenating the
+	/// patterns themselves, the prefix of `self` and suffix of `other` are
+	/// kept while the suffix of `self` and prefix of `other`, now in the
+	/// middle of the pattern, are dropped: concatenating the unanchored `a+`
+	/// and `b+` gives the unanchored `a+b+`.
+	///
+	/// Anchors are the exception. An end anchor of `self` means that nothing
+	/// can follow its root, so the root of `other` may only match the empty
+	/// string there, and the suffix of `other` must accept the end of the
+	/// haystack. Likewise, a start anchor of `other` restricts the prefix and
+	/// root of `self` to the empty string. Concatenating `^a$` and `^b$` gives
+	/// the empty language.
+	pub fn concat<B, T, Q, R>(
+		&self,
+		other: &CompoundAutomaton<B, C>,
+	) -> CompoundAutomaton<NFA<u32, T>, C>
+	where
+		A: AsRef<NFA<Q, T>>,
+		B: AsRef<NFA<R, T>>,
+		T: Token,
+		Q: Ord + Hash,
+		R: Ord + Hash,
+		C: Clone + Eq + Hash + Class<T>,
+	{
+		let end_anchor = self.suffix.iter().all(|(_, s)| s.as_ref().is_only_empty());
+		let start_anchor = !other.any_prefix && other.prefix.as_ref().is_only_empty();
+
+		let (prefix, any_prefix) = if !start_anchor {
+			(renumbered(self.prefix.as_ref()), self.any_prefix)
+		} else if self.any_prefix || self.prefix.as_ref().recognizes_empty() {
+			(renumbered(other.prefix.as_ref()), false)
+		} else {
+			(NFA::empty_language(), false)
+		};
+
+		let suffix = if end_anchor {
+			other
+				.suffix
+				.iter()
+				.filter(|(_, b)| b.as_ref().recognizes_empty())
+				.filter_map(|(class, _)| {
+					let s = self.suffix.get(class)?;
+					Some((class.clone(), renumbered(s.as_ref())))
+				})
+				.collect()
+		} else {
+			other
+				.suffix
+				.iter()
+				.map(|(class, b)| (class.clone(), renumbered(b.as_ref())))
+				.collect()
+		};
+
+		CompoundAutomaton {
+			prefix,
+			any_prefix,
+			root: self
+				.root
+				.iter()
+				.map(|(class, a)| {
+					let root =
+						concat_roots(a.as_ref(), class, &other.root, start_anchor, end_anchor);
+					(class.clone(), root)
+				})
+				.collect(),
+			suffix,
+		}
+	}
+
+	/// Turns every automaton of this compound automaton into a minimal DFA.
+	pub fn determinize<T, Q>(&self) -> CompoundAutomaton<DFA<u32, RangeSet<T>>, C>
+	where
+		A: AsRef<NFA<Q, T>>,
+		T: Token + Hash,
+		Q: Ord + Hash,
+		C: Clone,
+	{
+		CompoundAutomaton {
+			prefix: self.prefix.as_ref().to_minimal_dfa(),
+			any_prefix: self.any_prefix,
+			root: self
+				.root
+				.iter()
+				.map(|(class, aut)| (class.clone(), aut.as_ref().to_minimal_dfa()))
+				.collect(),
+			suffix: self
+				.suffix
+				.iter()
+				.map(|(class, aut)| (class.clone(), aut.as_ref().to_minimal_dfa()))
+				.collect(),
+		}
+	}
+}
+
+/// Advances the given class past the given token.
+///
+/// Does nothing for constant classes (see [`Class::CONSTANT`]).
+pub(crate) fn advance_class<T, C: Class<T>>(class: &mut C, token: &T) {
+	if !C::CONSTANT {
+		*class = class.next_class(token)
+	}
+}
+
+/// Copies the given automaton into `result`, numbering its states from
+/// `next_id`.
+///
+/// Returns the identifiers of the initial states of the copy, which are not
+/// made initial in `result`. Final states are kept.
+fn copy_into<Q, T>(result: &mut NFA<u32, T>, aut: &NFA<Q, T>, next_id: &mut u32) -> Vec<u32>
+where
+	Q: Ord + Hash,
+	T: Token,
+{
+	let mut ids = HashMap::new();
+	let mut id = |q| {
+		*ids.entry(q).or_insert_with(|| {
+			let id = *next_id;
+			*next_id += 1;
+			id
+		})
+	};
+
+	for q in aut.states() {
+		result.add_state(id(q));
+	}
+
+	for (source, label, target) in aut.iter_edges() {
+		result.add(id(source), label.cloned(), id(target));
+	}
+
+	for q in aut.final_states() {
+		result.add_final_state(id(q));
+	}
+
+	aut.initial_states().iter().map(id).collect()
+}
+
+/// Returns a copy of the given automaton with states numbered from `0`.
+fn renumbered<Q: Ord + Hash, T: Token>(aut: &NFA<Q, T>) -> NFA<u32, T> {
+	let mut result = NFA::new();
+	for q in copy_into(&mut result, aut, &mut 0) {
+		result.add_initial_state(q);
+	}
+
+	result
+}
+
+/// Concatenates the root automaton `a`, starting in the given class, with
+/// the `others` root automata, selected by the class reached at the end of
+/// `a`.
+///
+/// If `empty_a` (resp. `empty_b`) is set, only the empty string is kept
+/// from `a` (resp. the `others`).
+fn concat_roots<Q, T, C, B, R>(
+	a: &NFA<Q, T>,
+	class: &C,
+	others: &C::Map<B>,
+	empty_a: bool,
+	empty_b: bool,
+) -> NFA<u32, T>
+where
+	Q: Ord + Hash,
+	T: Token,
+	C: Clone + Eq + Hash + Class<T>,
+	B: AsRef<NFA<R, T>>,
+	R: Ord + Hash,
+{
+	let mut result = NFA::new();
+	let mut next_id = 0;
+	let mut ids = HashMap::new();
+	let mut stack = Vec::new();
+	let mut other_initial_states: HashMap<C, Vec<u32>> = HashMap::new();
+
+	let mut id = |q, class: C, stack: &mut Vec<_>| {
+		*ids.entry((q, class.clone())).or_insert_with(|| {
+			let id = next_id;
+			next_id += 1;
+			stack.push((q, class, id));
+			id
+		})
+	};
+
+	for q in a.initial_states() {
+		let q = id(q, class.clone(), &mut stack);
+		result.add_initial_state(q);
+	}
+
+	let mut copies = Vec::new();
+	while let Some((q, class, q_id)) = stack.pop() {
+		result.add_state(q_id);
+
+		if a.is_final_state(q) {
+			if let Some(b) = others.get(&class) {
+				if !empty_b {
+					copies.push((q_id, class.clone(), b.as_ref()));
+				} else if b.as_ref().recognizes_empty() {
+					result.add_final_state(q_id);
+				}
+			}
+		}
+
+		for (label, targets) in a.successors(q) {
+			let pieces: Vec<_> = match label {
+				Some(_) if empty_a => continue,
+				Some(set) => class
+					.classify(set)
+					.into_entries()
+					.map(|(next_class, set)| (Some(set.into_owned()), next_class))
+					.collect(),
+				None => vec![(None, class.clone())],
+			};
+
+			for r in targets {
+				for (label, next_class) in &pieces {
+					let r_id = id(r, next_class.clone(), &mut stack);
+					result.add(q_id, label.clone(), r_id);
+				}
+			}
+		}
+	}
+
+	for (q_id, class, b) in copies {
+		let initial_states = other_initial_states
+			.entry(class)
+			.or_insert_with(|| copy_into(&mut result, b, &mut next_id));
+
+		for r_id in initial_states {
+			result.add(q_id, None, *r_id);
+		}
+	}
+
+	result
+}
+
+/// Returns the prefix automaton of a compound automaton, which is universal
+/// if `any` is set.
+fn prefix_nfa<Q: Ord + Hash, T: Token>(aut: &NFA<Q, T>, any: bool) -> NFA<u32, T> {
+	if any {
+		NFA::universal(T::all())
+	} else {
+		renumbered(aut)
+	}
+}
+
+/// Classes reached at the end of the words recognized by both given
+/// automata, starting in any of the given classes.
+fn common_final_classes<T, Q, R, C>(
+	a: &NFA<Q, T>,
+	b: &NFA<R, T>,
+	classes: impl IntoIterator<Item = C>,
+) -> Vec<C>
+where
+	T: Token,
+	Q: Ord + Hash,
+	R: Ord + Hash,
+	C: Clone + Eq + Hash + Class<T>,
+{
+	let a = a.determinize(|qs| qs.clone());
+	let b = b.determinize(|qs| qs.clone());
+
+	let mut visited = HashSet::new();
+	let mut stack = Vec::new();
+	for class in classes {
+		if visited.insert((a.initial_state(), b.initial_state(), class.clone())) {
+			stack.push((a.initial_state(), b.initial_state(), class))
+		}
+	}
+
+	let mut result = Vec::new();
+	while let Some((p, q, class)) = stack.pop() {
+		if a.is_final_state(p) && b.is_final_state(q) && !result.contains(&class) {
+			result.push(class.clone())
+		}
+
+		for (p_range, p2) in a.transitions_from(p) {
+			for (q_range, q2) in b.transitions_from(q) {
+				if p_range.intersects(q_range) {
+					let mut set = RangeSet::new();
+					set.insert(p_range.intersection(q_range));
+					for (next_class, _) in class.classify(&set).into_entries() {
+						if visited.insert((p2, q2, next_class.clone())) {
+							stack.push((p2, q2, next_class))
+						}
+					}
+				}
+			}
+		}
+	}
+
+	result
+}
+
+/// Classes reached at the final states of the given automaton, starting in
+/// any of the given classes.
+fn final_classes<T, Q, C>(aut: &NFA<Q, T>, classes: impl IntoIterator<Item = C>) -> Vec<C>
+where
+	T: Token,
+	Q: Ord + Hash,
+	C: Clone + Eq + Hash + Class<T>,
+{
+	let mut visited = HashSet::new();
+	let mut stack = Vec::new();
+	for class in classes {
+		for q in aut.initial_states() {
+			if visited.insert((q, class.clone())) {
+				stack.push((q, class.clone()))
+			}
 		}
 	}
+
+	let mut result = Vec::new();
+	while let Some((q, class)) = stack.pop() {
+		if aut.is_final_state(q) && !result.contains(&class) {
+			result.push(class.clone())
+		}
+
+		for (label, targets) in aut.successors(q) {
+			let next_classes: Vec<C> = match label {
+				Some(set) => class
+					.classify(set)
+					.into_entries()
+					.map(|(next_class, _)| next_class)
+					.collect(),
+				None => vec![class.clone()],
+			};
+
+			for r in targets {
+				for next_class in &next_classes {
+					if visited.insert((r, next_class.clone())) {
+						stack.push((r, next_class.clone()))
+					}
+				}
+			}
+		}
+	}
+
+	result
+}
+
+/// Root and suffix automata coverage of the classes reachable during
+/// matching.
+///
+/// See [`CompoundAutomaton::class_coverage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassCoverage<C> {
+	/// Classes in which a match can start, with whether or not they have a
+	/// root automaton.
+	pub root: Vec<(C, bool)>,
+
+	/// Classes in which a match can end, with whether or not they have a
+	/// suffix automaton.
+	pub suffix: Vec<(C, bool)>,
 }
 
-impl<T, Q, C: MapSource> CompoundAutomaton<NFA<Q, T>, C> {
-	pub fn determinize(&self) -> CompoundAutomaton<DFA<Q, RangeSet<T>>, C> {
-		todo!()
+impl<C> ClassCoverage<C> {
+	/// Returns the classes in which a match can start without root automaton.
+	pub fn missing_roots(&self) -> impl '_ + Iterator<Item = &C> {
+		self.root
+			.iter()
+			.filter_map(|(class, covered)| (!covered).then_some(class))
+	}
+
+	/// Returns the classes in which a match can end without suffix automaton.
+	pub fn missing_suffixes(&self) -> impl '_ + Iterator<Item = &C> {
+		self.suffix
+			.iter()
+			.filter_map(|(class, covered)| (!covered).then_some(class))
+	}
+
+	/// Checks that every reachable class has the automata it requires.
+	pub fn is_complete(&self) -> bool {
+		self.root.iter().all(|(_, covered)| *covered)
+			&& self.suffix.iter().all(|(_, covered)| *covered)
+	}
+}
+
+/// Position in a haystack.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+	/// Byte offset.
+	pub offset: usize,
+
+	/// Token index, which is the character index when matching a string.
+	pub index: usize,
+}
+
+impl Position {
+	fn advance<T: Token>(&mut self, token: &T) {
+		self.offset += Token::len(token);
+		self.index += 1;
 	}
 }
 
+/// Iterator over the matches of a compound automaton in a haystack.
+///
+/// Match ranges are given in bytes. Use [`Self::char_indices`] to get them
+/// in characters (tokens) instead.
+///
+/// Matches never overlap, and each match starts strictly after the start of
+/// the previous one, even when the previous match is empty. Iterating over
+/// the matches of a finite haystack hence always terminates, and consumers
+/// advancing by match end (to replace or split) do not need to guard against
+/// empty matches themselves. An empty match is never reported where the
+/// previous non-empty match ends, even at the end of the haystack (as with
+/// the `regex` crate, `a*` only matches `aaa` once).
 pub struct Matches<'a, A: Automaton<H::Item>, C: MapSource, H: Iterator> {
 	regex: &'a CompoundAutomaton<A, C>,
 	prefix_state: Option<A::State<'a>>,
 	haystack: H,
 	class: C,
-	position: usize,
+	position: Position,
+
+	/// Minimum byte offset of the next match.
+	///
+	/// Always greater than the start of the last match, and greater or equal
+	/// to its end.
 	min: usize,
+
+	/// Byte offset of the end of the last non-empty match, where no empty
+	/// match can be reported.
+	last_end: Option<usize>,
+
+	/// Whether to return the shortest match at each start position.
+	shortest: bool,
+}
+
+impl<'a, A: Automaton<H::Item>, C: MapSource, H: Iterator> Matches<'a, A, C, H> {
+	/// Turns this iterator into an iterator reporting match ranges as
+	/// character (token) indices instead of byte offsets.
+	pub fn char_indices(self) -> CharIndicesMatches<'a, A, C, H> {
+		CharIndicesMatches(self)
+	}
+
+	/// Makes this iterator return the shortest match at each start position
+	/// instead of the longest one.
+	///
+	/// This is a global, non-greedy matching policy: each match ends at the
+	/// first position where the regular expression is accepted.
+	pub fn shortest(mut self) -> Self {
+		self.shortest = true;
+		self
+	}
+
+	/// Turns this iterator into an iterator also returning the tag of the
+	/// root automaton state accepting each match.
+	pub fn tagged<G>(self) -> TaggedMatches<'a, A, C, H, G>
+	where
+		A: TaggedAutomaton<H::Item, G>,
+	{
+		TaggedMatches(self, PhantomData)
+	}
+
+	/// Turns this iterator into an iterator also returning the capture group
+	/// spans of each match.
+	///
+	/// See [`Captures`] for the semantics of the returned spans.
+	pub fn captures(self) -> CaptureMatches<'a, A, C, H> {
+		CaptureMatches(self)
+	}
 }
 
 impl<'a, A: Automaton<H::Item>, C: Clone + Class<H::Item>, H: Clone + Iterator> Matches<'a, A, C, H>
 where
 	H::Item: Token,
 {
-	fn next_from_position(&self, mut haystack: H, class: &C) -> Option<usize> {
+	fn next_from_position(&self, mut haystack: H, class: &C) -> Option<Position> {
 		let root = self.regex.root.get(class)?;
 		let mut root_state = root.initial_state()?;
 		let mut end = self.position;
@@ -69,13 +861,16 @@ where
 
 		loop {
 			if root.is_final_state(&root_state) && self.check_suffix(haystack.clone(), &class) {
-				candidate = Some(end)
+				candidate = Some(end);
+				if self.shortest {
+					break;
+				}
 			}
 
 			match haystack.next() {
 				Some(token) => {
-					end += token.len();
-					class = class.next_class(&token);
+					end.advance(&token);
+					advance_class(&mut class, &token);
 					match root.next_state(root_state, token) {
 						Some(next_state) => root_state = next_state,
 						None => break,
@@ -96,6 +891,10 @@ where
 		match suffix.initial_state() {
 			Some(mut suffix_state) => {
 				for token in haystack {
+					if suffix.accepts_any_continuation(&suffix_state) {
+						return true;
+					}
+
 					match suffix.next_state(suffix_state, token) {
 						Some(next_state) => suffix_state = next_state,
 						None => return false,
@@ -107,35 +906,50 @@ where
 			None => false,
 		}
 	}
-}
 
-impl<'a, A: Automaton<H::Item>, C: Clone + Class<H::Item>, H: Clone + Iterator> Iterator
-	for Matches<'a, A, C, H>
-where
-	H::Item: Token,
-{
-	type Item = Range<usize>;
-
-	fn next(&mut self) -> Option<Self::Item> {
+	/// Finds the next match, returning its range along with the result of
+	/// `f` applied to the haystack and class at the start of the match.
+	///
+	/// This lets callers inspect the matched tokens without copying the
+	/// haystack when they don't need to.
+	fn next_match<R>(
+		&mut self,
+		f: impl FnOnce(&Range<Position>, &H, &C) -> R,
+	) -> Option<(Range<Position>, R)> {
 		loop {
 			match self.prefix_state.take() {
 				Some(prefix_state) => {
-					if self.position >= self.min && self.regex.prefix.is_final_state(&prefix_state)
+					if self.position.offset >= self.min
+						&& (self.regex.any_prefix
+							|| self.regex.prefix.is_final_state(&prefix_state))
 					{
-						if let Some(end) =
-							self.next_from_position(self.haystack.clone(), &self.class)
-						{
-							self.min = end.max(self.position + 1);
+						if let Some(end) = self
+							.next_from_position(self.haystack.clone(), &self.class)
+							.filter(|end| {
+								end.offset > self.position.offset
+									|| self.last_end != Some(self.position.offset)
+							}) {
+							debug_assert!(end.offset >= self.position.offset);
+							// Advance at least one token after an empty match.
+							self.min = end.offset.max(self.position.offset + 1);
+							self.last_end =
+								(end.offset > self.position.offset).then_some(end.offset);
 							self.prefix_state = Some(prefix_state);
-							break Some(self.position..end);
+							let range = self.position..end;
+							let value = f(&range, &self.haystack, &self.class);
+							break Some((range, value));
 						}
 					}
 
 					match self.haystack.next() {
 						Some(token) => {
-							self.class = self.class.next_class(&token);
-							self.position += token.len();
-							self.prefix_state = self.regex.prefix.next_state(prefix_state, token);
+							advance_class(&mut self.class, &token);
+							self.position.advance(&token);
+							self.prefix_state = if self.regex.any_prefix {
+								Some(prefix_state)
+							} else {
+								self.regex.prefix.next_state(prefix_state, token)
+							};
 						}
 						None => break None,
 					}
@@ -145,3 +959,240 @@ where
 		}
 	}
 }
+
+impl<'a, A: Automaton<H::Item>, C: Clone + Class<H::Item>, H: Clone + Iterator> Iterator
+	for Matches<'a, A, C, H>
+where
+	H::Item: Token,
+{
+	type Item = Range<usize>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.next_match(|_, _, _| ())
+			.map(|(range, _)| range.start.offset..range.end.offset)
+	}
+}
+
+/// Haystack tokens, ending after a maximum number of tokens.
+///
+/// See [`CompoundAutomaton::matches_bounded`].
+#[derive(Clone)]
+pub struct Bounded<H> {
+	tokens: H,
+	remaining: usize,
+
+	/// Set once a token past the bound has been requested while the haystack
+	/// does not end at the bound. Shared by every copy of the iterator.
+	cut: Arc<AtomicBool>,
+}
+
+impl<H: Clone + Iterator> Iterator for Bounded<H> {
+	type Item = H::Item;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.remaining == 0 {
+			if !self.cut.load(Ordering::Relaxed) && self.tokens.clone().next().is_some() {
+				self.cut.store(true, Ordering::Relaxed)
+			}
+
+			return None;
+		}
+
+		self.remaining -= 1;
+		self.tokens.next()
+	}
+}
+
+/// Iterator over the matches of a compound automaton in the first tokens
+/// of a haystack.
+///
+/// See [`CompoundAutomaton::matches_bounded`].
+pub struct BoundedMatches<'a, A: Automaton<H::Item>, C: MapSource, H: Clone + Iterator> {
+	matches: Matches<'a, A, C, Bounded<H>>,
+	cut: Arc<AtomicBool>,
+}
+
+impl<'a, A: Automaton<H::Item>, C: Clone + Class<H::Item>, H: Clone + Iterator> Iterator
+	for BoundedMatches<'a, A, C, H>
+where
+	H::Item: Token,
+{
+	type Item = Range<usize>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.cut.load(Ordering::Relaxed) {
+			return None;
+		}
+
+		// Drop the match if finding it required to read past the bound.
+		self.matches
+			.next()
+			.filter(|_| !self.cut.load(Ordering::Relaxed))
+	}
+}
+
+/// Iterator over the matches of a compound automaton in a haystack, from the
+/// last one to the first one.
+///
+/// See [`CompoundAutomaton::rmatches`].
+pub struct RMatches(std::iter::Rev<std::vec::IntoIter<Range<usize>>>);
+
+impl Iterator for RMatches {
+	type Item = Range<usize>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.0.next()
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.0.size_hint()
+	}
+}
+
+/// Iterator over the tokens of a haystack, along with their tag.
+///
+/// See [`CompoundAutomaton::tokens`].
+pub struct Lexer<'a, A, C: MapSource, H, G> {
+	regex: &'a CompoundAutomaton<A, C>,
+	haystack: H,
+	class: C,
+	position: Position,
+	tag: PhantomData<G>,
+}
+
+impl<'a, A, C: MapSource, H, G> Lexer<'a, A, C, H, G> {
+	/// Returns the byte offset of the next token.
+	///
+	/// Once the iteration is over, this is the length of the haystack if it
+	/// has been entirely split into tokens.
+	pub fn offset(&self) -> usize {
+		self.position.offset
+	}
+}
+
+impl<'a, A, C, H, G> Iterator for Lexer<'a, A, C, H, G>
+where
+	A: TaggedAutomaton<H::Item, G>,
+	C: Clone + Class<H::Item>,
+	H: Clone + Iterator,
+	H::Item: Token,
+{
+	type Item = (Range<usize>, G);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let root = self.regex.root.get(&self.class)?;
+		let mut state = root.initial_state()?;
+		let mut haystack = self.haystack.clone();
+		let mut class = self.class.clone();
+		let mut end = self.position;
+		let mut candidate = None;
+
+		loop {
+			if end.index > self.position.index && root.is_final_state(&state) {
+				if let Some(tag) = root.get_tag(&state) {
+					candidate = Some((end, tag, haystack.clone(), class.clone()))
+				}
+			}
+
+			match haystack.next() {
+				Some(token) => {
+					end.advance(&token);
+					advance_class(&mut class, &token);
+					match root.next_state(state, token) {
+						Some(next_state) => state = next_state,
+						None => break,
+					}
+				}
+				None => break,
+			}
+		}
+
+		let (end, tag, haystack, class) = candidate?;
+		let range = self.position.offset..end.offset;
+		self.position = end;
+		self.haystack = haystack;
+		self.class = class;
+		Some((range, tag))
+	}
+}
+
+/// Iterator over the matches of a compound automaton in a haystack, with
+/// match ranges given as character (token) indices.
+///
+/// See [`Matches::char_indices`].
+pub struct CharIndicesMatches<'a, A: Automaton<H::Item>, C: MapSource, H: Iterator>(
+	Matches<'a, A, C, H>,
+);
+
+impl<'a, A: Automaton<H::Item>, C: Clone + Class<H::Item>, H: Clone + Iterator> Iterator
+	for CharIndicesMatches<'a, A, C, H>
+where
+	H::Item: Token,
+{
+	type Item = Range<usize>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.0
+			.next_match(|_, _, _| ())
+			.map(|(range, _)| range.start.index..range.end.index)
+	}
+}
+
+/// Iterator over the matches of a compound automaton in a haystack, along
+/// with the tag of the root automaton state accepting each match.
+///
+/// See [`Matches::tagged`].
+pub struct TaggedMatches<'a, A: Automaton<H::Item>, C: MapSource, H: Iterator, G>(
+	Matches<'a, A, C, H>,
+	PhantomData<G>,
+);
+
+impl<'a, A, C, H, G> Iterator for TaggedMatches<'a, A, C, H, G>
+where
+	A: TaggedAutomaton<H::Item, G>,
+	C: Clone + Class<H::Item>,
+	H: Clone + Iterator,
+	H::Item: Token,
+{
+	type Item = (Range<usize>, Option<G>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let regex = self.0.regex;
+		let (range, tag) = self.0.next_match(|range, haystack, class| {
+			let root = regex.root.get(class)?;
+			let tokens = haystack.clone().take(range.end.index - range.start.index);
+			root.get_tag(&root.run(tokens)?)
+		})?;
+
+		Some((range.start.offset..range.end.offset, tag))
+	}
+}
+
+/// Iterator over the matches of a compound automaton in a haystack, along
+/// with their capture group spans.
+///
+/// See [`Matches::captures`].
+pub struct CaptureMatches<'a, A: Automaton<H::Item>, C: MapSource, H: Iterator>(
+	Matches<'a, A, C, H>,
+);
+
+impl<'a, Q, C, H> Iterator for CaptureMatches<'a, TaggedNFA<Q, H::Item, CaptureTag>, C, H>
+where
+	Q: Copy + Ord + Hash,
+	C: Clone + Class<H::Item>,
+	H: Clone + Iterator,
+	H::Item: Token,
+{
+	type Item = (Range<usize>, Captures);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let regex = self.0.regex;
+		let (range, captures) = self.0.next_match(|range, haystack, class| {
+			let root = regex.root.get(class).expect("a match has a root automaton");
+			let tokens = haystack.clone().take(range.end.index - range.start.index);
+			captures(root, tokens, range.start.offset).expect("the root automaton accepts a match")
+		})?;
+
+		Some((range.start.offset..range.end.offset, captures))
+	}
+}
@@ -0,0 +1,33 @@
+use std::{iter::Copied, slice, str::Chars};
+
+use iregex_automata::Token;
+
+/// Haystack, a sequence of tokens in which to search for matches.
+pub trait Haystack {
+	/// Token type.
+	type Token: Token;
+
+	/// Token iterator.
+	type Tokens: Clone + Iterator<Item = Self::Token>;
+
+	/// Returns an iterator over the tokens of the haystack.
+	fn tokens(self) -> Self::Tokens;
+}
+
+impl<'a> Haystack for &'a str {
+	type Token = char;
+	type Tokens = Chars<'a>;
+
+	fn tokens(self) -> Self::Tokens {
+		self.chars()
+	}
+}
+
+impl<'a> Haystack for &'a [u8] {
+	type Token = u8;
+	type Tokens = Copied<slice::Iter<'a, u8>>;
+
+	fn tokens(self) -> Self::Tokens {
+		self.iter().copied()
+	}
+}
@@ -1,13 +1,15 @@
 use std::hash::Hash;
 
+use educe::Educe;
 use iregex_automata::{
 	nfa::{BuildNFA, StateBuilder, Tags},
-	Class, Token, NFA,
+	Class, RangeSet, Token, NFA,
 };
 
 use crate::{Alternation, Atom, Boundary, CaptureTag, Concatenation};
 
-#[derive(Debug)]
+#[derive(Debug, Educe)]
+#[educe(PartialEq(bound(T: Token, B: PartialEq)), Eq)]
 pub enum Affix<T, B> {
 	Any,
 	Anchor,
@@ -15,6 +17,21 @@ pub enum Affix<T, B> {
 }
 
 impl<T, B> Affix<T, B> {
+	/// Creates an affix matching any sequence of tokens from the given
+	/// alphabet.
+	///
+	/// Returns [`Self::Any`] if the alphabet contains every token.
+	pub fn any_over(alphabet: RangeSet<T>) -> Self
+	where
+		T: Token,
+	{
+		if alphabet == T::all() {
+			Self::Any
+		} else {
+			Self::Alternation(Atom::star(Atom::Token(alphabet).into()).into())
+		}
+	}
+
 	pub fn is_any(&self) -> bool {
 		matches!(self, Self::Any)
 	}
@@ -22,6 +39,14 @@ impl<T, B> Affix<T, B> {
 	pub fn is_anchor(&self) -> bool {
 		matches!(self, Self::Anchor)
 	}
+
+	/// Checks if the affix contains capture groups.
+	pub fn has_captures(&self) -> bool {
+		match self {
+			Self::Alternation(alt) => alt.has_captures(),
+			_ => false,
+		}
+	}
 }
 
 impl<T, B, Q, C> BuildNFA<T, Q, C, CaptureTag> for Affix<T, B>
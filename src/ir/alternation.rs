@@ -1,14 +1,21 @@
-use std::{hash::Hash, ops::Deref};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt,
+	hash::Hash,
+	ops::Deref,
+};
 
+use educe::Educe;
 use iregex_automata::{
 	nfa::{BuildNFA, StateBuilder, Tags},
-	Class, Map, Token, NFA,
+	Class, Map, RangeSet, Token, NFA,
 };
 
-use crate::{Atom, Boundary, CaptureTag, Concatenation};
+use crate::{Atom, Boundary, CaptureTag, CompileError, Concatenation};
 
 /// Regular expression sequence disjunction.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Educe)]
+#[educe(PartialEq(bound(T: Token, B: PartialEq)), Eq)]
 pub struct Alternation<T = char, B = ()>(Vec<Concatenation<T, B>>);
 
 impl<T, B> Default for Alternation<T, B> {
@@ -21,6 +28,78 @@ impl<T, B> Alternation<T, B> {
 	pub fn new() -> Self {
 		Self::default()
 	}
+
+	/// Checks that the alternation can be compiled.
+	pub fn validate<E>(&self) -> Result<(), CompileError<E>> {
+		self.0.iter().try_for_each(Concatenation::validate)
+	}
+
+	/// Checks if the alternation matches the empty string.
+	pub fn is_nullable(&self) -> bool {
+		self.0.iter().any(Concatenation::is_nullable)
+	}
+
+	/// Checks if the alternation only matches the empty string, without
+	/// consuming any token.
+	pub fn is_zero_width(&self) -> bool {
+		self.0.iter().all(Concatenation::is_zero_width)
+	}
+
+	/// Checks if the alternation contains capture groups.
+	pub fn has_captures(&self) -> bool {
+		self.0.iter().any(Concatenation::has_captures)
+	}
+}
+
+impl<T: Token, B> Alternation<T, B> {
+	/// Coalesces the literals of every concatenation of the alternation.
+	///
+	/// See [`Concatenation::coalesce_literals`].
+	pub fn coalesce_literals(self) -> Self {
+		self.0
+			.into_iter()
+			.map(Concatenation::coalesce_literals)
+			.collect()
+	}
+}
+
+impl<T: Token, B: PartialEq> Alternation<T, B> {
+	/// Removes the branches structurally identical to a previous branch, so
+	/// that `a|a|b` becomes `a|b`.
+	///
+	/// Only the branches themselves are compared, nested alternations are left
+	/// untouched. Branches capturing different groups are never identical, so
+	/// every capture group of the alternation is preserved.
+	pub fn dedup_branches(self) -> Self {
+		let mut result: Vec<Concatenation<T, B>> = Vec::with_capacity(self.0.len());
+
+		for concat in self.0 {
+			if !result.contains(&concat) {
+				result.push(concat)
+			}
+		}
+
+		Self(result)
+	}
+}
+
+impl<B: Boundary<char>> fmt::Display for Alternation<char, B> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.0.is_empty() {
+			// Empty language.
+			return f.write_str("[]");
+		}
+
+		for (i, concat) in self.0.iter().enumerate() {
+			if i > 0 {
+				f.write_str("|")?;
+			}
+
+			concat.fmt(f)?;
+		}
+
+		Ok(())
+	}
 }
 
 impl<T, B> From<Concatenation<T, B>> for Alternation<T, B> {
@@ -88,6 +167,16 @@ where
 			}
 			[concat] => concat.build_nfa_from(state_builder, nfa, tags, class),
 			list => {
+				if let Some(literals) = list
+					.iter()
+					.map(Concatenation::as_literal)
+					.collect::<Option<Vec<_>>>()
+				{
+					if let Some(continue_first) = trie_priorities(&literals) {
+						return build_trie(&literals, &continue_first, state_builder, nfa, class);
+					}
+				}
+
 				let a = state_builder.next_state(nfa, class.clone())?;
 				let mut output: C::Map<Q> = Default::default();
 
@@ -110,3 +199,107 @@ where
 		}
 	}
 }
+
+/// Returns the prefixes at which a literal ends with a lower priority than
+/// the literals going through it, or `None` if the priority of the literals
+/// cannot be preserved by a trie.
+///
+/// In a trie node, ending takes priority over continuing, as epsilon
+/// transitions are followed before labeled ones. This is wrong for the
+/// returned prefixes (e.g. `a` in `ab|a`), which need an extra state.
+fn trie_priorities<T: Ord>(literals: &[Vec<T>]) -> Option<BTreeSet<&[T]>> {
+	let mut result = BTreeSet::new();
+
+	for (i, literal) in literals.iter().enumerate() {
+		if literals[..i].contains(literal) {
+			continue;
+		}
+
+		let mut continuing = literals
+			.iter()
+			.enumerate()
+			.filter(|(_, other)| other.len() > literal.len() && other.starts_with(literal))
+			.map(|(j, _)| j < i);
+
+		if let Some(before) = continuing.next() {
+			if continuing.any(|other| other != before) {
+				return None;
+			}
+
+			if before {
+				result.insert(literal.as_slice());
+			}
+		}
+	}
+
+	Some(result)
+}
+
+/// Builds an alternation of literals as a trie, sharing the states of common
+/// prefixes.
+///
+/// Each node has an entry state, and a state holding the transitions to its
+/// children. They differ only for the `continue_first` prefixes, where ending
+/// goes through an extra state so that continuing keeps the priority.
+fn build_trie<T, Q, C, S>(
+	literals: &[Vec<T>],
+	continue_first: &BTreeSet<&[T]>,
+	state_builder: &mut S,
+	nfa: &mut NFA<Q, T>,
+	class: &C,
+) -> Result<(Q, C::Map<Q>), S::Error>
+where
+	T: Token,
+	Q: Copy + Ord,
+	C: Clone + Eq + Hash + Class<T>,
+	S: StateBuilder<T, Q, C>,
+{
+	let new_node = |state_builder: &mut S, nfa: &mut NFA<Q, T>, prefix: &[T], class: C| {
+		let entry = state_builder.next_state(nfa, class.clone())?;
+		let mut inner = entry;
+		if continue_first.contains(prefix) {
+			inner = state_builder.next_state(nfa, class.clone())?;
+			nfa.add(entry, None, inner);
+		}
+
+		Ok((entry, inner, class))
+	};
+
+	let root = new_node(state_builder, nfa, &[], class.clone())?;
+	let mut nodes: BTreeMap<&[T], (Q, Q, C)> = BTreeMap::new();
+	let mut output: C::Map<Q> = Default::default();
+
+	for literal in literals {
+		let mut node = root.clone();
+
+		for (i, token) in literal.iter().enumerate() {
+			node = match nodes.get(&literal[..=i]) {
+				Some(node) => node.clone(),
+				None => {
+					let (_, q, q_class) = node;
+					let r_class = q_class.next_class(token);
+					let r = new_node(state_builder, nfa, &literal[..=i], r_class)?;
+					let mut label = RangeSet::new();
+					label.insert(*token..=*token);
+					nfa.add(q, Some(label), r.0);
+					nodes.insert(&literal[..=i], r.clone());
+					r
+				}
+			};
+		}
+
+		let (mut q, inner, q_class) = node;
+		if q != inner {
+			let e = state_builder.next_state(nfa, q_class.clone())?;
+			nfa.add(q, None, e);
+			q = e;
+		}
+
+		let b = *output
+			.get_or_try_insert_with(&q_class, || state_builder.next_state(nfa, q_class.clone()))?;
+
+		nfa.add(q, None, b);
+	}
+
+	Ok((root.0, output))
+}
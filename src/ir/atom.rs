@@ -1,15 +1,21 @@
-use std::hash::Hash;
+use std::{
+	fmt::{self, Display, Write},
+	hash::Hash,
+};
 
+use educe::Educe;
 use iregex_automata::{
+	negate_char_set,
 	nfa::{BuildNFA, StateBuilder, Tags},
-	Class, Map, RangeSet, Token, NFA,
+	Class, EscapedChar, Map, RangeSet, Token, NFA,
 };
 
-use crate::{Boundary, CaptureTag};
+use crate::{Boundary, CaptureTag, CompileError};
 
 use super::{Alternation, CaptureGroupId, Repeat};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Educe)]
+#[educe(PartialEq(bound(T: Token, B: PartialEq)), Eq)]
 pub enum Atom<T = char, B = ()> {
 	/// Boundary.
 	Boundary(B),
@@ -17,6 +23,13 @@ pub enum Atom<T = char, B = ()> {
 	/// Token.
 	Token(RangeSet<T>),
 
+	/// Literal token sequence.
+	///
+	/// Equivalent to a concatenation of single token atoms, but compiled
+	/// into a linear chain of states. See
+	/// [`Concatenation::coalesce_literals`](super::Concatenation::coalesce_literals).
+	Literal(Vec<T>),
+
 	/// Repetition.
 	Repeat(Alternation<T, B>, Repeat),
 
@@ -32,6 +45,161 @@ impl<T, B> Atom<T, B> {
 	pub fn star(inner: Alternation<T, B>) -> Self {
 		Self::Repeat(inner, Repeat::STAR)
 	}
+
+	/// Repeats the given alternation exactly `n` times.
+	pub fn repeat_n(inner: Alternation<T, B>, n: u32) -> Self {
+		Self::Repeat(inner, Repeat::exact(n))
+	}
+
+	/// Checks that the atom can be compiled.
+	pub fn validate<E>(&self) -> Result<(), CompileError<E>> {
+		match self {
+			Self::Boundary(_) | Self::Token(_) | Self::Literal(_) => Ok(()),
+			Self::Repeat(alt, r) => {
+				r.validate()?;
+				alt.validate()
+			}
+			Self::Capture(_, alt) => alt.validate(),
+		}
+	}
+
+	/// Checks if the atom matches the empty string.
+	///
+	/// Boundaries do not consume any token, and hence are considered
+	/// nullable.
+	pub fn is_nullable(&self) -> bool {
+		match self {
+			Self::Boundary(_) => true,
+			Self::Token(_) => false,
+			Self::Literal(tokens) => tokens.is_empty(),
+			Self::Repeat(alt, r) => r.is_nullable_for(alt),
+			Self::Capture(_, alt) => alt.is_nullable(),
+		}
+	}
+
+	/// Checks if the atom only matches the empty string, without consuming
+	/// any token.
+	pub fn is_zero_width(&self) -> bool {
+		match self {
+			Self::Boundary(_) => true,
+			Self::Token(_) => false,
+			Self::Literal(tokens) => tokens.is_empty(),
+			Self::Repeat(alt, r) => r.is_zero() || alt.is_zero_width(),
+			Self::Capture(_, alt) => alt.is_zero_width(),
+		}
+	}
+
+	/// Checks if the atom is or contains a capture group.
+	pub fn has_captures(&self) -> bool {
+		match self {
+			Self::Boundary(_) | Self::Token(_) | Self::Literal(_) => false,
+			Self::Repeat(alt, _) => alt.has_captures(),
+			Self::Capture(_, _) => true,
+		}
+	}
+}
+
+impl<T: Token, B> Atom<T, B> {
+	/// Coalesces the literals of the nested alternations.
+	///
+	/// See [`Concatenation::coalesce_literals`](super::Concatenation::coalesce_literals).
+	pub fn coalesce_literals(self) -> Self {
+		match self {
+			Self::Repeat(alt, r) => Self::Repeat(alt.coalesce_literals(), r),
+			Self::Capture(id, alt) => Self::Capture(id, alt.coalesce_literals()),
+			other => other,
+		}
+	}
+
+	/// Returns the token set matched by this atom, if it is a token atom.
+	pub fn as_token(&self) -> Option<&RangeSet<T>> {
+		match self {
+			Self::Token(set) => Some(set),
+			_ => None,
+		}
+	}
+
+	/// Returns the token matched by this atom, if it is a token atom
+	/// matching exactly one token.
+	pub fn as_single_token(&self) -> Option<T> {
+		match self {
+			Self::Token(set) => {
+				let mut ranges = set.iter();
+				match (ranges.next(), ranges.next()) {
+					(Some(range), None) if range.first() == range.last() => range.first(),
+					_ => None,
+				}
+			}
+			_ => None,
+		}
+	}
+}
+
+impl<B: Boundary<char>> fmt::Display for Atom<char, B> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Boundary(b) => b.fmt_boundary(f),
+			Self::Token(set) => fmt_token_set(set, f),
+			Self::Literal(tokens) => tokens.iter().try_for_each(|c| EscapedChar(*c).fmt(f)),
+			Self::Repeat(alt, r) if *r == Repeat::ONCE && alt.len() == 1 => alt.fmt(f),
+			Self::Repeat(alt, r) => {
+				match &alt[..] {
+					[concat] if matches!(&concat[..], [Self::Token(_) | Self::Capture(..)]) => {
+						concat.fmt(f)?
+					}
+					_ => write!(f, "(?:{alt})")?,
+				}
+
+				if *r != Repeat::ONCE {
+					r.fmt(f)?
+				}
+
+				Ok(())
+			}
+			Self::Capture(_, alt) => write!(f, "({alt})"),
+		}
+	}
+}
+
+/// Formats a token set as a single character, `.` or a bracket expression.
+///
+/// A negated bracket expression is used when it is shorter.
+fn fmt_token_set(set: &RangeSet<char>, f: &mut fmt::Formatter) -> fmt::Result {
+	let complement = negate_char_set(set);
+
+	if complement.is_empty() {
+		return f.write_char('.');
+	}
+
+	let ranges: Vec<_> = set.iter().collect();
+	if let [range] = ranges.as_slice() {
+		if range.first() == range.last() {
+			return EscapedChar(range.first().unwrap()).fmt(f);
+		}
+	}
+
+	f.write_char('[')?;
+
+	let ranges = if complement.iter().count() < ranges.len() {
+		f.write_char('^')?;
+		complement.iter().collect()
+	} else {
+		ranges
+	};
+
+	for range in ranges {
+		let (a, b) = (range.first().unwrap(), range.last().unwrap());
+		EscapedChar(a).fmt(f)?;
+		if a != b {
+			if a as u32 + 1 < b as u32 {
+				f.write_char('-')?;
+			}
+
+			EscapedChar(b).fmt(f)?;
+		}
+	}
+
+	f.write_char(']')
 }
 
 impl<T, B, Q, C> BuildNFA<T, Q, C, CaptureTag> for Atom<T, B>
@@ -54,6 +222,7 @@ where
 				let mut output: C::Map<Q> = Default::default();
 				if let Some(b_class) = boundary.apply(class) {
 					let b = state_builder.next_state(nfa, b_class.clone())?;
+					nfa.add(a, None, b);
 					output.set(b_class, b);
 				}
 				Ok((a, output))
@@ -69,8 +238,73 @@ where
 
 				Ok((a, output))
 			}
-			Self::Repeat(alt, r) => r.build_nfa_for(alt, state_builder, nfa, tags, class),
-			Self::Capture(_, alt) => alt.build_nfa_from(state_builder, nfa, tags, class),
+			Self::Literal(tokens) => {
+				let a = state_builder.next_state(nfa, class.clone())?;
+				let mut q = a;
+				let mut q_class = class.clone();
+				for token in tokens {
+					q_class = q_class.next_class(token);
+					let r = state_builder.next_state(nfa, q_class.clone())?;
+					let mut label = RangeSet::new();
+					label.insert(*token..=*token);
+					nfa.add(q, Some(label), r);
+					q = r;
+				}
+
+				Ok((a, Map::singleton(q_class, q)))
+			}
+			Self::Repeat(alt, r) => {
+				let (alt, r) = collapse_repeat(alt, *r);
+
+				// Repeating a zero-width expression more than once is useless.
+				let r = if alt.is_zero_width() {
+					r.at_most_once()
+				} else {
+					r
+				};
+
+				r.build_nfa_for(alt, state_builder, nfa, tags, class)
+			}
+			Self::Capture(id, alt) => {
+				let a = state_builder.next_state(nfa, class.clone())?;
+				let (b, inner_output) = alt.build_nfa_from(state_builder, nfa, tags, class)?;
+				nfa.add(a, None, b);
+				tags.insert(a, CaptureTag::Begin(*id), b);
+
+				let mut output: C::Map<Q> = Default::default();
+				for (c_class, c) in inner_output.into_entries() {
+					let d = state_builder.next_state(nfa, c_class.clone())?;
+					nfa.add(c, None, d);
+					tags.insert(c, CaptureTag::End(*id), d);
+					output.set(c_class, d);
+				}
+
+				Ok((a, output))
+			}
+		}
+	}
+}
+
+/// Collapses nested unbounded repetitions such as `(x*)*`, `(x*)+` or `(x+)*`
+/// into a single one (here `x*`).
+///
+/// This avoids building redundant epsilon loops.
+fn collapse_repeat<T, B>(
+	mut alt: &Alternation<T, B>,
+	mut r: Repeat,
+) -> (&Alternation<T, B>, Repeat) {
+	while r.min <= 1 && r.max.is_none() {
+		match &**alt {
+			[concatenation] => match &**concatenation {
+				[Atom::Repeat(inner, inner_r)] if inner_r.min <= 1 && inner_r.max.is_none() => {
+					r.min = r.min.min(inner_r.min);
+					alt = inner;
+				}
+				_ => break,
+			},
+			_ => break,
 		}
 	}
+
+	(alt, r)
 }
@@ -1,9 +1,20 @@
+use std::fmt;
+
 use iregex_automata::Class;
 
 pub trait Boundary<T> {
 	type Class: Class<T>;
 
 	fn apply(&self, class: &Self::Class) -> Option<Self::Class>;
+
+	/// Formats the boundary when displaying a regular expression.
+	///
+	/// Writes nothing by default, which is only correct for boundaries
+	/// matching at any position.
+	fn fmt_boundary(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let _ = f;
+		Ok(())
+	}
 }
 
 impl<T> Boundary<T> for () {
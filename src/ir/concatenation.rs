@@ -1,15 +1,17 @@
+use educe::Educe;
 use iregex_automata::{
 	nfa::{BuildNFA, StateBuilder, Tags},
-	Class, Map, Token, NFA,
+	Class, Map, RangeSet, Token, NFA,
 };
-use std::{hash::Hash, ops::Deref};
+use std::{fmt, hash::Hash, ops::Deref};
 
-use crate::{Boundary, CaptureTag};
+use crate::{Boundary, CaptureTag, CompileError};
 
 use super::Atom;
 
 /// Regular expression atom sequence.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Educe)]
+#[educe(PartialEq(bound(T: Token, B: PartialEq)), Eq)]
 pub struct Concatenation<T = char, B = ()>(Vec<Atom<T, B>>);
 
 impl<T, B> Default for Concatenation<T, B> {
@@ -26,6 +28,97 @@ impl<T, B> Concatenation<T, B> {
 	pub fn push(&mut self, atom: Atom<T, B>) {
 		self.0.push(atom)
 	}
+
+	/// Checks that the concatenation can be compiled.
+	pub fn validate<E>(&self) -> Result<(), CompileError<E>> {
+		self.0.iter().try_for_each(Atom::validate)
+	}
+
+	/// Checks if the concatenation matches the empty string.
+	pub fn is_nullable(&self) -> bool {
+		self.0.iter().all(Atom::is_nullable)
+	}
+
+	/// Checks if the concatenation only matches the empty string, without
+	/// consuming any token.
+	pub fn is_zero_width(&self) -> bool {
+		self.0.iter().all(Atom::is_zero_width)
+	}
+
+	/// Checks if the concatenation contains capture groups.
+	pub fn has_captures(&self) -> bool {
+		self.0.iter().any(Atom::has_captures)
+	}
+}
+
+impl<T: Token, B> Concatenation<T, B> {
+	/// Merges the runs of atoms matching a single token into
+	/// [literal atoms](Atom::Literal).
+	///
+	/// A literal compiles into a linear chain of states, without the
+	/// intermediate states and epsilon transitions of a concatenation.
+	/// Nested alternations are coalesced recursively.
+	pub fn coalesce_literals(self) -> Self {
+		let mut result = Vec::with_capacity(self.0.len());
+		let mut run = Vec::new();
+
+		for atom in self.0 {
+			let atom = atom.coalesce_literals();
+			match atom.as_single_token() {
+				Some(token) => run.push(token),
+				None => match atom {
+					Atom::Literal(tokens) => run.extend(tokens),
+					atom => {
+						push_literal(&mut result, std::mem::take(&mut run));
+						result.push(atom)
+					}
+				},
+			}
+		}
+
+		push_literal(&mut result, run);
+		Self(result)
+	}
+}
+
+impl<T: Token, B> Concatenation<T, B> {
+	/// Returns the sequence of tokens matched by this concatenation, if it
+	/// only matches one word.
+	pub fn as_literal(&self) -> Option<Vec<T>> {
+		let mut result = Vec::new();
+
+		for atom in &self.0 {
+			match atom {
+				Atom::Literal(tokens) => result.extend(tokens),
+				atom => result.push(atom.as_single_token()?),
+			}
+		}
+
+		Some(result)
+	}
+}
+
+/// Pushes the given run of tokens as a single atom.
+fn push_literal<T: Token, B>(atoms: &mut Vec<Atom<T, B>>, run: Vec<T>) {
+	match run.as_slice() {
+		[] => (),
+		[token] => {
+			let mut set = RangeSet::new();
+			set.insert(*token..=*token);
+			atoms.push(Atom::Token(set))
+		}
+		_ => atoms.push(Atom::Literal(run)),
+	}
+}
+
+impl<B: Boundary<char>> fmt::Display for Concatenation<char, B> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for atom in &self.0 {
+			atom.fmt(f)?;
+		}
+
+		Ok(())
+	}
 }
 
 impl<T, B> From<Atom<T, B>> for Concatenation<T, B> {
@@ -1,4 +1,4 @@
-use std::hash::Hash;
+use std::{fmt, hash::Hash};
 
 mod boundary;
 pub use boundary::*;
@@ -10,15 +10,17 @@ mod alternation;
 pub use alternation::*;
 mod affix;
 pub use affix::*;
+use educe::Educe;
 use iregex_automata::{
-	nfa::{BuildNFA, StateBuilder, TaggedNFA, Tags},
-	Class, Map, MapSource, Token, NFA,
+	nfa::{BuildNFA, StateBuilder, TaggedNFA, Tags, TooManyStates, U32StateBuilder},
+	Class, Map, MapSource, RangeSet, Token, DFA, NFA,
 };
 
 use crate::CompoundAutomaton;
 
 /// Intermediate Regular Expression.
-#[derive(Debug)]
+#[derive(Debug, Educe)]
+#[educe(PartialEq(bound(T: Token, B: PartialEq)), Eq)]
 pub struct IRegEx<T = char, B = ()> {
 	pub root: Alternation<T, B>,
 	pub prefix: Affix<T, B>,
@@ -42,6 +44,16 @@ impl<T, B> IRegEx<T, B> {
 		}
 	}
 
+	/// Creates a regular expression whose matches can start anywhere, but
+	/// must reach the end of the haystack (like `root$`).
+	pub fn anchored_end(root: Alternation<T, B>) -> Self {
+		Self {
+			root,
+			prefix: Affix::Any,
+			suffix: Affix::Anchor,
+		}
+	}
+
 	pub fn into_anchored(self) -> Result<Alternation<T, B>, Self> {
 		if self.prefix.is_anchor() && self.suffix.is_anchor() {
 			Ok(self.root)
@@ -58,8 +70,34 @@ impl<T, B> IRegEx<T, B> {
 		}
 	}
 
+	/// Checks that the regular expression can be compiled.
+	pub fn validate<E>(&self) -> Result<(), CompileError<E>> {
+		if let Affix::Alternation(prefix) = &self.prefix {
+			prefix.validate()?;
+		}
+
+		self.root.validate()?;
+
+		if let Affix::Alternation(suffix) = &self.suffix {
+			suffix.validate()?;
+		}
+
+		Ok(())
+	}
+
+	/// Checks if the regular expression contains capture groups.
+	pub fn has_captures(&self) -> bool {
+		self.prefix.has_captures() || self.root.has_captures() || self.suffix.has_captures()
+	}
+
 	/// Compiles the regular expression.
-	pub fn compile<Q, S>(&self, mut state_builder: S) -> Result<CompiledRegEx<T, B, Q>, S::Error>
+	///
+	/// Tags are only constructed when the regular expression has capture
+	/// groups (see [`Self::has_captures`]).
+	pub fn compile<Q, S>(
+		&self,
+		state_builder: S,
+	) -> Result<CompiledRegEx<T, B, Q>, CompileError<S::Error>>
 	where
 		T: Token,
 		B: Boundary<T>,
@@ -67,26 +105,87 @@ impl<T, B> IRegEx<T, B> {
 		Q: Copy + Ord,
 		S: StateBuilder<T, Q, B::Class>,
 	{
-		let prefix = self
-			.prefix
-			.build_nfa(&mut state_builder, Default::default())?;
+		self.compile_with(state_builder, self.has_captures(), |aut| aut)
+	}
 
-		let mut root: <B::Class as MapSource>::Map<TaggedNFA<Q, T, CaptureTag>> =
-			Default::default();
-		for q in prefix.final_states() {
+	/// Compiles the regular expression without capture tags.
+	///
+	/// Tags are not constructed at all, and the resulting automata are plain
+	/// NFAs. This is all that is needed when captures are not used.
+	pub fn compile_untagged<Q, S>(
+		&self,
+		state_builder: S,
+	) -> Result<UntaggedCompiledRegEx<T, B, Q>, CompileError<S::Error>>
+	where
+		T: Token,
+		B: Boundary<T>,
+		B::Class: Default + Clone + Eq + Hash,
+		Q: Copy + Ord,
+		S: StateBuilder<T, Q, B::Class>,
+	{
+		self.compile_with(state_builder, false, TaggedNFA::into_untagged)
+	}
+
+	/// Compiles the regular expression, along with its deterministic form.
+	///
+	/// The first automaton keeps the capture tags, while the second one,
+	/// obtained with [`CompoundAutomaton::determinize`], is faster to run
+	/// when only the match ranges are needed.
+	pub fn compile_both<Q, S>(
+		&self,
+		state_builder: S,
+	) -> Result<CompiledRegExPair<T, B, Q>, CompileError<S::Error>>
+	where
+		T: Token + Hash,
+		B: Boundary<T>,
+		B::Class: Default + Clone + Eq + Hash,
+		Q: Copy + Ord + Hash,
+		S: StateBuilder<T, Q, B::Class>,
+	{
+		let aut = self.compile(state_builder)?;
+		let dfa = aut.determinize();
+		Ok((aut, dfa))
+	}
+
+	/// Compiles the prefix, root and suffix automata, constructing their tags
+	/// only if `tagged` is `true`.
+	fn compile_with<Q, S, A>(
+		&self,
+		mut state_builder: S,
+		tagged: bool,
+		f: impl Fn(TaggedNFA<Q, T, CaptureTag>) -> A,
+	) -> Result<CompoundAutomaton<A, B::Class>, CompileError<S::Error>>
+	where
+		T: Token,
+		B: Boundary<T>,
+		B::Class: Default + Clone + Eq + Hash,
+		Q: Copy + Ord,
+		S: StateBuilder<T, Q, B::Class>,
+		A: AsRef<NFA<Q, T>>,
+	{
+		self.validate()?;
+
+		let prefix = f(build_part(
+			&self.prefix,
+			&mut state_builder,
+			Default::default(),
+			tagged,
+		)?);
+
+		let mut root: <B::Class as MapSource>::Map<A> = Default::default();
+		for q in prefix.as_ref().final_states() {
 			let q_class = state_builder.class_of(q).unwrap().clone();
 			root.get_or_try_insert_with(&q_class, || {
-				self.root.build_nfa(&mut state_builder, q_class.clone())
+				build_part(&self.root, &mut state_builder, q_class.clone(), tagged).map(&f)
 			})?;
 		}
 
-		let mut suffix: <B::Class as MapSource>::Map<TaggedNFA<Q, T, CaptureTag>> =
-			Default::default();
+		let mut suffix: <B::Class as MapSource>::Map<A> = Default::default();
 		for (_, aut) in root.iter() {
-			for q in aut.final_states() {
+			for q in aut.as_ref().final_states() {
 				let q_class = state_builder.class_of(q).unwrap().clone();
 				suffix.get_or_try_insert_with(&q_class, || {
-					self.suffix.build_nfa(&mut state_builder, q_class.clone())
+					build_part(&self.suffix, &mut state_builder, q_class.clone(), tagged).map(&f)
 				})?;
 			}
 		}
@@ -94,18 +193,181 @@ impl<T, B> IRegEx<T, B> {
 		Ok(CompoundAutomaton {
 			root,
 			prefix,
+			any_prefix: self.prefix.is_any(),
 			suffix,
 		})
 	}
+
+	/// Compiles the regular expression into a single untagged NFA recognizing
+	/// the concatenation of the prefix, root and suffix.
+	pub fn compile_nfa<Q, S>(
+		&self,
+		mut state_builder: S,
+	) -> Result<NFA<Q, T>, CompileError<S::Error>>
+	where
+		T: Token,
+		B: Boundary<T>,
+		B::Class: Default + Clone + Eq + Hash,
+		Q: Copy + Ord,
+		S: StateBuilder<T, Q, B::Class>,
+	{
+		self.validate()?;
+
+		let mut nfa = NFA::new();
+		let mut tags = Tags::ignored();
+
+		let (a, prefix_output) = self.prefix.build_nfa_from(
+			&mut state_builder,
+			&mut nfa,
+			&mut tags,
+			&Default::default(),
+		)?;
+		nfa.add_initial_state(a);
+
+		let mut roots: <B::Class as MapSource>::Map<Q> = Default::default();
+		let mut suffixes: <B::Class as MapSource>::Map<Q> = Default::default();
+		for (b_class, b) in prefix_output.into_entries() {
+			let c = match roots.get(&b_class) {
+				Some(c) => *c,
+				None => {
+					let (c, root_output) = self.root.build_nfa_from(
+						&mut state_builder,
+						&mut nfa,
+						&mut tags,
+						&b_class,
+					)?;
+
+					for (d_class, d) in root_output.into_entries() {
+						let e = *suffixes.get_or_try_insert_with(&d_class, || {
+							let (e, suffix_output) = self.suffix.build_nfa_from(
+								&mut state_builder,
+								&mut nfa,
+								&mut tags,
+								&d_class,
+							)?;
+
+							for (_, f) in suffix_output.into_entries() {
+								nfa.add_final_state(f);
+							}
+
+							Ok::<_, S::Error>(e)
+						})?;
+
+						nfa.add(d, None, e);
+					}
+
+					roots.set(b_class.clone(), c);
+					c
+				}
+			};
+
+			nfa.add(b, None, c);
+		}
+
+		Ok(nfa)
+	}
+
+	/// Checks that the regular expression matches at least one string.
+	///
+	/// This compiles the expression with [`Self::compile_nfa`], and is a cheap
+	/// way to reject patterns that can never match, such as an empty
+	/// character set.
+	///
+	/// Returns an error if the expression cannot be compiled, either because
+	/// a repetition bound exceeds [`Repeat::MAX_BOUND`] or because it needs
+	/// more than `u32::MAX` states.
+	pub fn is_satisfiable(&self) -> Result<bool, CompileError>
+	where
+		T: Token,
+		B: Boundary<T>,
+		B::Class: Default + Clone + Eq + Hash,
+	{
+		let nfa = self.compile_nfa(U32StateBuilder::new())?;
+		Ok(!nfa.is_empty_language())
+	}
+}
+
+/// Builds the automaton of a part of a regular expression, constructing its
+/// tags only if `tagged` is `true`.
+fn build_part<T, Q, C, S>(
+	part: &impl BuildNFA<T, Q, C, CaptureTag>,
+	state_builder: &mut S,
+	class: C,
+	tagged: bool,
+) -> Result<TaggedNFA<Q, T, CaptureTag>, S::Error>
+where
+	T: Clone,
+	Q: Ord,
+	C: Class<T>,
+	S: StateBuilder<T, Q, C>,
+{
+	if tagged {
+		part.build_nfa(state_builder, class)
+	} else {
+		Ok(TaggedNFA::new(
+			part.build_untagged_nfa(state_builder, class)?,
+			Tags::new(),
+		))
+	}
+}
+
+impl<T: Token + Hash, B: Boundary<T>> IRegEx<T, B> {
+	/// Compiles a DFA recognizing the strings matched by this regular
+	/// expression but not by the `other` one.
+	///
+	/// Both expressions are compiled with [`Self::compile_nfa`], so their
+	/// prefix and suffix are taken into account. The resulting DFA is
+	/// [canonical](DFA::canonicalize).
+	///
+	/// Returns an error if one of the expressions cannot be compiled (see
+	/// [`Self::is_satisfiable`]).
+	pub fn difference(&self, other: &Self) -> Result<DFA<u32, RangeSet<T>>, CompileError>
+	where
+		B::Class: Default + Clone + Eq + Hash,
+	{
+		let mut state_builder = U32StateBuilder::new();
+		let a = self.compile_nfa(&mut state_builder)?;
+		let b = other.compile_nfa(&mut state_builder)?;
+		Ok(a.difference(&b))
+	}
+}
+
+/// Compilation error.
+#[derive(Debug, thiserror::Error)]
+pub enum CompileError<E = TooManyStates> {
+	/// The state builder failed to create a new state.
+	#[error(transparent)]
+	StateBuilder(E),
+
+	/// A repetition bound exceeds [`Repeat::MAX_BOUND`].
+	#[error("repetition bound {0} is too large (maximum is {max})", max = Repeat::MAX_BOUND)]
+	RepeatTooLarge(u32),
+}
+
+impl<E> From<E> for CompileError<E> {
+	fn from(value: E) -> Self {
+		Self::StateBuilder(value)
+	}
 }
 
 pub type CompiledRegEx<T, B, Q> =
 	CompoundAutomaton<TaggedNFA<Q, T, CaptureTag>, <B as Boundary<T>>::Class>;
 
+pub type UntaggedCompiledRegEx<T, B, Q> = CompoundAutomaton<NFA<Q, T>, <B as Boundary<T>>::Class>;
+
+pub type DeterministicCompiledRegEx<T, B> =
+	CompoundAutomaton<DFA<u32, RangeSet<T>>, <B as Boundary<T>>::Class>;
+
+/// Regular expression compiled into both a tagged and a deterministic
+/// automaton. See [`IRegEx::compile_both`].
+pub type CompiledRegExPair<T, B, Q> = (CompiledRegEx<T, B, Q>, DeterministicCompiledRegEx<T, B>);
+
 /// Capture group identifier.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CaptureGroupId(pub u32);
 
+/// Capture tag, marking the beginning or end of a capture group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum CaptureTag {
 	Begin(CaptureGroupId),
 	End(CaptureGroupId),
@@ -114,7 +376,10 @@ pub enum CaptureTag {
 /// Repetition.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Repeat {
+	/// Minimum number of repetitions.
 	pub min: u32,
+
+	/// Maximum number of repetitions, `None` if unbounded.
 	pub max: Option<u32>,
 }
 
@@ -125,13 +390,87 @@ impl Repeat {
 	};
 	pub const STAR: Self = Self { min: 0, max: None };
 
+	/// Repeats exactly `n` times.
+	///
+	/// ```
+	/// # use iregex::Repeat;
+	/// assert_eq!(Repeat::exact(3), Repeat { min: 3, max: Some(3) });
+	/// ```
+	pub const fn exact(n: u32) -> Self {
+		Self {
+			min: n,
+			max: Some(n),
+		}
+	}
+
+	/// Repeats at least `n` times, without upper bound.
+	///
+	/// ```
+	/// # use iregex::Repeat;
+	/// assert_eq!(Repeat::at_least(1), Repeat { min: 1, max: None });
+	/// assert_eq!(Repeat::at_least(0), Repeat::STAR);
+	/// ```
+	pub const fn at_least(n: u32) -> Self {
+		Self { min: n, max: None }
+	}
+
+	/// Repeats between `min` and `max` times, inclusive.
+	///
+	/// ```
+	/// # use iregex::Repeat;
+	/// assert_eq!(Repeat::between(2, 4), Repeat { min: 2, max: Some(4) });
+	/// assert_eq!(Repeat::between(1, 1), Repeat::ONCE);
+	/// ```
+	pub const fn between(min: u32, max: u32) -> Self {
+		Self {
+			min,
+			max: Some(max),
+		}
+	}
+
+	/// Maximum value of a repetition bound.
+	///
+	/// Bounded repetitions are unrolled during compilation, so larger bounds
+	/// would result in unreasonably large automata.
+	pub const MAX_BOUND: u32 = 1000;
+
+	/// Checks that the repetition bounds do not exceed [`Self::MAX_BOUND`].
+	pub fn validate<E>(&self) -> Result<(), CompileError<E>> {
+		if self.min > Self::MAX_BOUND {
+			return Err(CompileError::RepeatTooLarge(self.min));
+		}
+
+		match self.max {
+			Some(max) if max > Self::MAX_BOUND => Err(CompileError::RepeatTooLarge(max)),
+			_ => Ok(()),
+		}
+	}
+
 	pub fn is_zero(&self) -> bool {
 		match self.max {
-			Some(max) => max < self.min,
+			Some(max) => max == 0 || max < self.min,
 			None => false,
 		}
 	}
 
+	/// Checks if repeating the given alternation matches the empty string.
+	pub fn is_nullable_for<T, B>(&self, value: &Alternation<T, B>) -> bool {
+		self.min == 0 || self.is_zero() || value.is_nullable()
+	}
+
+	/// Bounds the repetition to at most one occurrence, preserving whether
+	/// it is optional.
+	pub fn at_most_once(&self) -> Self {
+		if self.is_zero() {
+			*self
+		} else {
+			Self {
+				min: self.min.min(1),
+				max: Some(1),
+			}
+		}
+	}
+
 	pub fn is_one(&self) -> bool {
 		self.min == 1 && self.max == Some(1)
 	}
@@ -238,6 +577,21 @@ impl Repeat {
 	}
 }
 
+/// Displays the repetition as a regular expression quantifier (`?`, `*`, `+`,
+/// `{n}`, `{m,n}` or `{m,}`).
+impl fmt::Display for Repeat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (self.min, self.max) {
+			(0, Some(1)) => f.write_str("?"),
+			(0, None) => f.write_str("*"),
+			(1, None) => f.write_str("+"),
+			(min, Some(max)) if min == max => write!(f, "{{{min}}}"),
+			(min, Some(max)) => write!(f, "{{{min},{max}}}"),
+			(min, None) => write!(f, "{{{min},}}"),
+		}
+	}
+}
+
 fn kleene_star_closure<T, Q, C, G, S: StateBuilder<T, Q, C>>(
 	map: &mut C::Map<Q>,
 	value: &impl BuildNFA<T, Q, C, G>,
@@ -358,3 +712,335 @@ where
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use iregex_automata::{
+		any_char,
+		nfa::{BuildNFA, U32StateBuilder},
+		Automaton, RangeSet,
+	};
+
+	use super::{Alternation, Atom, CaptureGroupId, CompileError, Concatenation, IRegEx, Repeat};
+
+	#[test]
+	fn repeat_too_large() {
+		let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+		let root = Atom::Repeat(
+			a.into(),
+			Repeat {
+				min: 2,
+				max: Some(Repeat::MAX_BOUND + 1),
+			},
+		);
+
+		let ire = IRegEx::anchored(root.into());
+		assert!(matches!(
+			ire.compile(U32StateBuilder::default()),
+			Err(CompileError::RepeatTooLarge(n)) if n == Repeat::MAX_BOUND + 1
+		))
+	}
+
+	#[test]
+	fn boundary() {
+		let a = Atom::Token(['a'].into_iter().collect());
+		let root: Concatenation = [Atom::Boundary(()), a].into_iter().collect();
+		let aut = IRegEx::anchored(root.into())
+			.compile(U32StateBuilder::default())
+			.unwrap();
+
+		assert_eq!(aut.matches_str("a").next(), Some(0..1));
+	}
+
+	#[test]
+	fn nullable() {
+		let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+		let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+
+		let ab: Concatenation = [a.clone(), b.clone()].into_iter().collect();
+		assert!(!ab.is_nullable());
+
+		let a_star_b_opt: Concatenation = [
+			Atom::star(a.into()),
+			Atom::Repeat(
+				b.into(),
+				Repeat {
+					min: 0,
+					max: Some(1),
+				},
+			),
+		]
+		.into_iter()
+		.collect();
+		assert!(a_star_b_opt.is_nullable());
+		assert!(Concatenation::<char>::new().is_nullable());
+	}
+
+	#[test]
+	fn repeat_display() {
+		let vectors = [
+			(Repeat { min: 2, max: None }, "{2,}"),
+			(Repeat { min: 0, max: None }, "*"),
+			(Repeat { min: 1, max: None }, "+"),
+			(
+				Repeat {
+					min: 0,
+					max: Some(1),
+				},
+				"?",
+			),
+			(
+				Repeat {
+					min: 1,
+					max: Some(3),
+				},
+				"{1,3}",
+			),
+			(
+				Repeat {
+					min: 2,
+					max: Some(2),
+				},
+				"{2}",
+			),
+		];
+
+		for (repeat, expected) in vectors {
+			assert_eq!(repeat.to_string(), expected)
+		}
+	}
+
+	#[test]
+	fn atom_display() {
+		let a_c = Atom::<_, ()>::Token(RangeSet::from_iter(['a'..='c']));
+		assert_eq!(a_c.to_string(), "[a-c]");
+
+		let mut not_a = any_char();
+		not_a.remove('a');
+		assert_eq!(Atom::<_, ()>::Token(not_a).to_string(), "[^a]");
+
+		assert_eq!(Atom::<_, ()>::Token(any_char()).to_string(), ".");
+		assert_eq!(
+			Atom::<_, ()>::Token(RangeSet::from_iter(['+'])).to_string(),
+			"\\+"
+		);
+
+		let a = Atom::<_, ()>::Token(RangeSet::from_iter(['a']));
+		let bc: Concatenation = [
+			Atom::Token(RangeSet::from_iter(['b'])),
+			Atom::Token(RangeSet::from_iter(['b', 'c'])),
+		]
+		.into_iter()
+		.collect();
+		let alt: Alternation = [Concatenation::from(a.clone()), bc].into_iter().collect();
+		assert_eq!(Atom::star(alt.clone()).to_string(), "(?:a|b[bc])*");
+		assert_eq!(Atom::alternation(alt).to_string(), "(?:a|b[bc])");
+		assert_eq!(Atom::star(a.into()).to_string(), "a*");
+	}
+
+	#[test]
+	fn coalesce_literals() {
+		let hello: Concatenation = "hello"
+			.chars()
+			.map(|c| Atom::<_, ()>::Token(RangeSet::from_iter([c])))
+			.collect();
+
+		let literal = hello.clone().coalesce_literals();
+		assert_eq!(&literal[..], [Atom::Literal("hello".chars().collect())]);
+		assert_eq!(literal.to_string(), "hello");
+
+		// The literal compiles into a single chain of states.
+		let nfa = literal.build_nfa(U32StateBuilder::default(), ()).unwrap();
+		assert_eq!(nfa.states().count(), 6);
+		assert!(nfa.iter_edges().all(|(_, label, _)| label.is_some()));
+
+		let concat_nfa = hello.build_nfa(U32StateBuilder::default(), ()).unwrap();
+		assert!(concat_nfa.states().count() > 6);
+		assert!(nfa.intersects(&concat_nfa));
+
+		// Only runs of single tokens are merged, in nested alternations too.
+		let a_b = Atom::<_, ()>::Token(RangeSet::from_iter(['a', 'b']));
+		let concat: Concatenation = [
+			Atom::Token(RangeSet::from_iter(['a'])),
+			a_b.clone(),
+			Atom::star(hello.clone().into()),
+			Atom::Token(RangeSet::from_iter(['c'])),
+		]
+		.into_iter()
+		.collect();
+		assert_eq!(
+			&concat.coalesce_literals()[..],
+			[
+				Atom::Token(RangeSet::from_iter(['a'])),
+				a_b,
+				Atom::star(literal.into()),
+				Atom::Token(RangeSet::from_iter(['c'])),
+			]
+		);
+	}
+
+	#[test]
+	fn difference() {
+		let mut lower = RangeSet::new();
+		lower.insert('a'..='z');
+
+		let words = IRegEx::<_, ()>::anchored(
+			Atom::Repeat(Atom::Token(lower).into(), Repeat { min: 1, max: None }).into(),
+		);
+		let foo = IRegEx::anchored(
+			"foo"
+				.chars()
+				.map(|c| Atom::Token(RangeSet::from_iter([c])))
+				.collect::<Concatenation>()
+				.into(),
+		);
+
+		let dfa = words.difference(&foo).unwrap();
+		assert!(!dfa.validate("foo"));
+		assert!(dfa.validate("fo"));
+		assert!(dfa.validate("food"));
+		assert!(dfa.validate("bar"));
+		assert!(!dfa.validate(""));
+		assert!(!dfa.validate("Foo"));
+
+		let empty = foo.difference(&words).unwrap();
+		assert!(empty.final_states().is_empty());
+	}
+
+	#[test]
+	fn nested_star() {
+		let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+		let a_star = Atom::star(a.clone().into());
+		let a_plus = Atom::Repeat(a.into(), Repeat::at_least(1));
+
+		let compile = |atom: Atom<char, ()>| {
+			IRegEx::anchored(atom.into())
+				.compile_nfa(U32StateBuilder::default())
+				.unwrap()
+		};
+
+		let reference = compile(a_star.clone());
+		let reference_dfa = reference.to_minimal_dfa();
+
+		for nested in [
+			Atom::star(a_star.clone().into()),
+			Atom::Repeat(a_star.into(), Repeat::at_least(1)),
+			Atom::star(a_plus.clone().into()),
+		] {
+			let nfa = compile(nested);
+			assert_eq!(nfa.states().count(), reference.states().count());
+			assert_eq!(nfa.to_minimal_dfa(), reference_dfa);
+		}
+
+		let a_plus_plus = compile(Atom::Repeat(a_plus.clone().into(), Repeat::at_least(1)));
+		let a_plus = compile(a_plus);
+		assert_eq!(a_plus_plus.states().count(), a_plus.states().count());
+		assert_eq!(a_plus_plus.to_minimal_dfa(), a_plus.to_minimal_dfa());
+	}
+
+	#[test]
+	fn literal_trie() {
+		let literal = |word: &str| -> Concatenation {
+			word.chars()
+				.map(|c| Atom::Token([c].into_iter().collect()))
+				.collect()
+		};
+
+		let compile = |words: &[&str]| {
+			let root: Alternation = words.iter().map(|word| literal(word)).collect();
+			IRegEx::anchored(root)
+				.compile_nfa(U32StateBuilder::default())
+				.unwrap()
+		};
+
+		let keywords = compile(&["foo", "foobar", "foobaz"]);
+		let separate: usize = ["foo", "foobar", "foobaz"]
+			.iter()
+			.map(|word| compile(&[word]).states().count())
+			.sum();
+
+		// `foo` and `fooba` are shared.
+		assert_eq!(
+			keywords.states().count(),
+			compile(&["foobar", "foobaz"]).states().count()
+		);
+		assert!(keywords.states().count() < separate);
+
+		for word in ["foo", "foobar", "foobaz"] {
+			assert!(keywords.contains(word.chars()), "{word:?}")
+		}
+
+		for word in ["", "fo", "fooba", "foobarz", "bar"] {
+			assert!(!keywords.contains(word.chars()), "{word:?}")
+		}
+
+		// Coalesced literals are also shared.
+		let coalesced: Alternation = [literal("foo").coalesce_literals(), literal("fox")]
+			.into_iter()
+			.collect();
+		let nfa = IRegEx::anchored(coalesced)
+			.compile_nfa(U32StateBuilder::default())
+			.unwrap();
+		assert_eq!(
+			nfa.states().count(),
+			compile(&["foo", "fox"]).states().count()
+		);
+		assert!(nfa.contains("fox".chars()));
+	}
+
+	#[test]
+	fn dedup_branches() {
+		let char = |c| -> Atom { Atom::Token([c].into_iter().collect()) };
+		let group = |id, c| -> Atom { Atom::Capture(CaptureGroupId(id), char(c).into()) };
+		let alternation = |atoms: Vec<Atom>| -> Alternation {
+			atoms.into_iter().map(Concatenation::from).collect()
+		};
+		let compile = |root: Alternation| {
+			IRegEx::anchored(root)
+				.compile_nfa(U32StateBuilder::default())
+				.unwrap()
+		};
+
+		// Literal branches already share their states, so use `a*|a*|b`.
+		let star = || Atom::Repeat(char('a').into(), Repeat::STAR);
+		let redundant = alternation(vec![star(), star(), char('b')]);
+		let dedup = redundant.clone().dedup_branches();
+		assert_eq!(dedup, alternation(vec![star(), char('b')]));
+
+		let (redundant, dedup) = (compile(redundant), compile(dedup));
+		assert!(dedup.states().count() < redundant.states().count());
+		assert_eq!(redundant.to_minimal_dfa(), dedup.to_minimal_dfa());
+
+		// Branches capturing different groups are kept.
+		let groups = alternation(vec![group(0, 'a'), group(1, 'a'), group(0, 'a')]);
+		assert_eq!(
+			groups.dedup_branches(),
+			alternation(vec![group(0, 'a'), group(1, 'a')])
+		);
+	}
+
+	#[test]
+	fn is_satisfiable() {
+		let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+		let empty = Atom::<_, ()>::Token(RangeSet::new());
+
+		let satisfiable = IRegEx::anchored(Atom::star(empty.clone().into()).into());
+		assert!(satisfiable.is_satisfiable().unwrap());
+
+		let unsatisfiable = IRegEx::unanchored(
+			[a.clone(), empty]
+				.into_iter()
+				.collect::<Concatenation>()
+				.into(),
+		);
+		assert!(!unsatisfiable.is_satisfiable().unwrap());
+
+		// Uncompilable expressions are reported instead of panicking.
+		let too_large = Atom::repeat_n(a.into(), Repeat::MAX_BOUND + 1);
+		let too_large = IRegEx::anchored(Concatenation::from(too_large).into());
+		assert!(matches!(
+			too_large.is_satisfiable(),
+			Err(CompileError::RepeatTooLarge(1001))
+		));
+	}
+}
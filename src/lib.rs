@@ -14,3 +14,12 @@ pub use ir::*;
 
 mod compiled;
 pub use compiled::*;
+
+mod captures;
+pub use captures::*;
+
+mod haystack;
+pub use haystack::*;
+
+mod search;
+pub use search::*;
@@ -0,0 +1,538 @@
+use std::{
+	cmp::Reverse,
+	collections::{HashSet, VecDeque},
+	hash::Hash,
+	ops::{Deref, Range},
+};
+
+use iregex_automata::{AnyRange, Automaton, Class, Map, MapSource, RangeSet, Token, DFA};
+
+use crate::{compiled::advance_class, CompoundAutomaton, Haystack};
+
+impl<T, C> CompoundAutomaton<DFA<u32, RangeSet<T>>, C>
+where
+	T: Token + Hash,
+	C: Default + Clone + Class<T>,
+{
+	/// Builds a searcher for the leftmost-longest matches of this
+	/// automaton.
+	///
+	/// The searcher can be reused to search any number of haystacks.
+	pub fn searcher(&self) -> Searcher<T, C> {
+		Searcher {
+			prefix: self.prefix.split_labels(),
+			any_prefix: self.any_prefix,
+			root: self
+				.root
+				.iter()
+				.map(|(class, root)| (class.clone(), root.split_labels()))
+				.collect(),
+			suffix: self
+				.suffix
+				.iter()
+				.map(|(class, suffix)| {
+					let accepting_sinks = suffix
+						.final_states()
+						.iter()
+						.filter(|q| suffix.accepts_any_continuation(q))
+						.copied()
+						.collect();
+
+					(
+						class.clone(),
+						Suffix {
+							dfa: suffix.split_labels(),
+							accepting_sinks,
+						},
+					)
+				})
+				.collect(),
+		}
+	}
+
+	/// Searches for the leftmost-longest matches in the given haystack.
+	///
+	/// Returns the same matches as [`Self::matches`]. This builds a new
+	/// [`Searcher`] on each call; build it once with [`Self::searcher`] to
+	/// search multiple haystacks.
+	pub fn search<H>(&self, haystack: H) -> SearchMatches<'_, T, C, H::Tokens>
+	where
+		H: Haystack<Token = T>,
+		C: Eq,
+	{
+		self.search_tokens(haystack.tokens())
+	}
+
+	/// Searches for the leftmost-longest matches in the given token sequence.
+	///
+	/// See [`Self::search`].
+	pub fn search_tokens<I>(&self, haystack: I) -> SearchMatches<'_, T, C, I>
+	where
+		I: Iterator<Item = T>,
+		C: Eq,
+	{
+		SearchMatches::new(SearcherRef::Owned(self.searcher()), haystack)
+	}
+
+	/// Checks if the given haystack contains a match.
+	///
+	/// The haystack is scanned once, running the prefix, root and suffix
+	/// DFAs side by side without computing match positions. The scan stops
+	/// as soon as a match is certain, which is when a suffix state accepting
+	/// any continuation is reached (e.g. with an unanchored suffix).
+	pub fn is_match<H>(&self, haystack: H) -> bool
+	where
+		H: Haystack<Token = T>,
+		C: Eq,
+	{
+		self.is_match_tokens(haystack.tokens())
+	}
+
+	/// Checks if the given token sequence contains a match.
+	///
+	/// See [`Self::is_match`].
+	pub fn is_match_tokens(&self, haystack: impl Iterator<Item = T>) -> bool
+	where
+		C: Eq,
+	{
+		let mut haystack = haystack;
+		let mut class = C::default();
+		let mut prefix_state = Some(self.prefix.initial_state());
+		let mut roots: Vec<(C, &u32)> = Vec::new();
+		let mut suffixes: Vec<(C, &u32)> = Vec::new();
+
+		loop {
+			let prefix_accepts =
+				self.any_prefix || prefix_state.map_or(false, |q| self.prefix.is_final_state(q));
+			if prefix_accepts {
+				if let Some(root) = self.root.get(&class) {
+					insert_run(&mut roots, class.clone(), root.initial_state());
+				}
+			}
+
+			if let Some(suffix) = self.suffix.get(&class) {
+				for (root_class, q) in &roots {
+					if self.root.get(root_class).unwrap().is_final_state(q) {
+						insert_run(&mut suffixes, class.clone(), suffix.initial_state());
+					}
+				}
+			}
+
+			for (suffix_class, q) in &suffixes {
+				if self
+					.suffix
+					.get(suffix_class)
+					.unwrap()
+					.accepts_any_continuation(q)
+				{
+					return true;
+				}
+			}
+
+			let Some(token) = haystack.next() else {
+				return suffixes.iter().any(|(suffix_class, q)| {
+					self.suffix.get(suffix_class).unwrap().is_final_state(q)
+				});
+			};
+
+			if !self.any_prefix {
+				prefix_state = prefix_state.and_then(|q| self.prefix.next_state(q, token));
+			}
+
+			roots = step_runs(&self.root, roots, token);
+			suffixes = step_runs(&self.suffix, suffixes, token);
+			advance_class(&mut class, &token);
+
+			if prefix_state.is_none() && roots.is_empty() && suffixes.is_empty() {
+				return false;
+			}
+		}
+	}
+}
+
+/// Adds the given run of a class automaton, if not already present.
+fn insert_run<'a, C: PartialEq>(runs: &mut Vec<(C, &'a u32)>, class: C, q: &'a u32) {
+	if !runs.iter().any(|(c, r)| *c == class && *r == q) {
+		runs.push((class, q))
+	}
+}
+
+/// Advances the given runs of class automata, removing the dead ones.
+fn step_runs<'a, T: 'a + Token, C: 'a + MapSource + PartialEq>(
+	automata: &'a C::Map<DFA<u32, RangeSet<T>>>,
+	runs: Vec<(C, &'a u32)>,
+	token: T,
+) -> Vec<(C, &'a u32)> {
+	let mut result = Vec::with_capacity(runs.len());
+
+	for (class, q) in runs {
+		if let Some(r) = automata.get(&class).unwrap().next_state(q, token) {
+			insert_run(&mut result, class, r)
+		}
+	}
+
+	result
+}
+
+/// Leftmost-longest searcher.
+///
+/// Built from a determinized compound automaton with
+/// [`CompoundAutomaton::searcher`]. Searching reads the haystack once,
+/// running the prefix, root and suffix DFAs side by side. Only the tokens
+/// after the earliest unresolved match candidate are buffered.
+pub struct Searcher<T, C: MapSource> {
+	prefix: DFA<u32, AnyRange<T>>,
+	any_prefix: bool,
+	root: C::Map<DFA<u32, AnyRange<T>>>,
+	suffix: C::Map<Suffix<T>>,
+}
+
+/// Suffix automaton of a searcher.
+struct Suffix<T> {
+	dfa: DFA<u32, AnyRange<T>>,
+
+	/// Final states looping on every token.
+	///
+	/// Once such a state is reached, the suffix accepts the rest of the
+	/// haystack, whatever it is.
+	accepting_sinks: HashSet<u32>,
+}
+
+impl<T, C> Searcher<T, C>
+where
+	T: Token,
+	C: Default + Clone + Eq + Class<T>,
+{
+	/// Searches for the leftmost-longest matches in the given haystack.
+	pub fn search<H>(&self, haystack: H) -> SearchMatches<'_, T, C, H::Tokens>
+	where
+		H: Haystack<Token = T>,
+		C: Eq,
+	{
+		self.search_tokens(haystack.tokens())
+	}
+
+	/// Searches for the leftmost-longest matches in the given token sequence.
+	pub fn search_tokens<I>(&self, haystack: I) -> SearchMatches<'_, T, C, I>
+	where
+		I: Iterator<Item = T>,
+		C: Eq,
+	{
+		SearchMatches::new(SearcherRef::Borrowed(self), haystack)
+	}
+
+	fn prefix_accepts(&self, q: Option<u32>) -> bool {
+		self.any_prefix || q.map_or(false, |q| self.prefix.is_final_state(&q))
+	}
+}
+
+enum SearcherRef<'a, T, C: MapSource> {
+	Borrowed(&'a Searcher<T, C>),
+	Owned(Searcher<T, C>),
+}
+
+impl<'a, T, C: MapSource> Deref for SearcherRef<'a, T, C> {
+	type Target = Searcher<T, C>;
+
+	fn deref(&self) -> &Searcher<T, C> {
+		match self {
+			Self::Borrowed(searcher) => searcher,
+			Self::Owned(searcher) => searcher,
+		}
+	}
+}
+
+/// Run of a root or suffix automaton.
+///
+/// For root runs, `end` is equal to `start`.
+struct Run<C> {
+	/// Start of the match (token index).
+	start: usize,
+
+	/// End of the match (token index).
+	end: usize,
+
+	/// Class selecting the automaton.
+	class: C,
+
+	/// Current state.
+	state: u32,
+}
+
+impl<C: PartialEq> Run<C> {
+	/// Adds the given run, if not already present.
+	///
+	/// Two runs in the same state behave the same from now on, so only the
+	/// preferred one is kept: the leftmost, then the longest.
+	fn insert(runs: &mut Vec<Self>, run: Self) {
+		match runs
+			.iter_mut()
+			.find(|r| r.class == run.class && r.state == run.state)
+		{
+			Some(r) => {
+				if (run.start, Reverse(run.end)) < (r.start, Reverse(r.end)) {
+					*r = run
+				}
+			}
+			None => runs.push(run),
+		}
+	}
+
+	/// Advances the given runs, removing the dead ones.
+	fn step_all<'a, T: 'a + Token>(
+		automata: impl Fn(&C) -> &'a DFA<u32, AnyRange<T>>,
+		runs: Vec<Self>,
+		token: T,
+	) -> Vec<Self> {
+		let mut result = Vec::with_capacity(runs.len());
+
+		for run in runs {
+			if let Some(&state) = automata(&run.class).step(&run.state, token).state() {
+				Self::insert(&mut result, Self { state, ..run })
+			}
+		}
+
+		result
+	}
+}
+
+/// Buffered haystack position.
+struct BufferedPosition<C> {
+	/// Byte offset.
+	offset: usize,
+
+	/// Class.
+	class: C,
+
+	/// Checks if the prefix accepts the tokens before this position.
+	prefix_accepts: bool,
+}
+
+/// Iterator over the leftmost-longest matches found by a [`Searcher`].
+pub struct SearchMatches<'a, T, C: MapSource, I> {
+	searcher: SearcherRef<'a, T, C>,
+	haystack: I,
+
+	/// Checks if the end of the haystack has been reached.
+	ended: bool,
+
+	/// Prefix state after the last read token.
+	prefix_state: Option<u32>,
+
+	/// Index of the first buffered position.
+	base: usize,
+
+	/// Buffered positions, from `base` to the last read position.
+	positions: VecDeque<BufferedPosition<C>>,
+
+	/// Buffered tokens, following each buffered position but the last.
+	tokens: VecDeque<T>,
+
+	/// Minimum position of the next match (token index).
+	min: usize,
+
+	/// End of the last non-empty match, where no empty match can be
+	/// reported.
+	last_end: Option<usize>,
+}
+
+impl<'a, T, C, I> SearchMatches<'a, T, C, I>
+where
+	T: Token,
+	C: Default + Clone + Eq + Class<T>,
+	I: Iterator<Item = T>,
+{
+	fn new(searcher: SearcherRef<'a, T, C>, haystack: I) -> Self {
+		let prefix_state = Some(*searcher.prefix.initial_state());
+		let prefix_accepts = searcher.prefix_accepts(prefix_state);
+
+		Self {
+			searcher,
+			haystack,
+			ended: false,
+			prefix_state,
+			base: 0,
+			positions: [BufferedPosition {
+				offset: 0,
+				class: C::default(),
+				prefix_accepts,
+			}]
+			.into_iter()
+			.collect(),
+			tokens: VecDeque::new(),
+			min: 0,
+			last_end: None,
+		}
+	}
+
+	/// Reads haystack tokens until the given position is buffered.
+	///
+	/// Returns `false` if the haystack ends before this position.
+	fn fill(&mut self, i: usize) -> bool {
+		while self.base + self.tokens.len() < i {
+			if self.ended {
+				return false;
+			}
+
+			let Some(token) = self.haystack.next() else {
+				self.ended = true;
+				return false;
+			};
+
+			let last = self.positions.back().unwrap();
+			let offset = last.offset + Token::len(&token);
+			let mut class = last.class.clone();
+			advance_class(&mut class, &token);
+
+			if !self.searcher.any_prefix {
+				self.prefix_state = self
+					.prefix_state
+					.and_then(|q| self.searcher.prefix.step(&q, token).state().copied());
+			}
+
+			self.positions.push_back(BufferedPosition {
+				offset,
+				class,
+				prefix_accepts: self.searcher.prefix_accepts(self.prefix_state),
+			});
+			self.tokens.push_back(token);
+		}
+
+		true
+	}
+
+	fn buffered(&self, i: usize) -> &BufferedPosition<C> {
+		&self.positions[i - self.base]
+	}
+
+	/// Forgets the buffered positions before `i`.
+	fn release(&mut self, i: usize) {
+		while self.base < i {
+			self.positions.pop_front();
+			self.tokens.pop_front();
+			self.base += 1;
+		}
+	}
+
+	/// Finds the next leftmost-longest match, as token indices.
+	///
+	/// Runs for every possible match start are advanced together, one token
+	/// at a time. A match candidate is found when a root run reaches a final
+	/// state. It is confirmed when its suffix run reaches a state accepting
+	/// any continuation, or a final state at the end of the haystack. The
+	/// search stops once no earlier or longer candidate may be confirmed.
+	fn find(&mut self) -> Option<(usize, usize)> {
+		let mut i = self.min;
+		let mut runs: Vec<Run<C>> = Vec::new();
+		let mut candidates: Vec<Run<C>> = Vec::new();
+		let mut best: Option<(usize, usize)> = None;
+
+		if !self.fill(i) {
+			return None;
+		}
+
+		loop {
+			let earliest = runs
+				.iter()
+				.chain(&candidates)
+				.map(|r| r.start)
+				.chain(best.map(|(start, _)| start))
+				.fold(i, usize::min);
+			self.release(earliest);
+
+			if best.is_some() && runs.is_empty() && candidates.is_empty() {
+				return best;
+			}
+
+			let at_end = !self.fill(i + 1);
+			let searcher = &*self.searcher;
+			let position = self.buffered(i);
+
+			if best.is_none() && position.prefix_accepts {
+				if let Some(root) = searcher.root.get(&position.class) {
+					Run::insert(
+						&mut runs,
+						Run {
+							start: i,
+							end: i,
+							class: position.class.clone(),
+							state: *root.initial_state(),
+						},
+					);
+				}
+			}
+
+			if let Some(suffix) = searcher.suffix.get(&position.class) {
+				for run in &runs {
+					// No empty match where the previous match ends.
+					if searcher
+						.root
+						.get(&run.class)
+						.unwrap()
+						.is_final_state(&run.state)
+						&& (run.start < i || self.last_end != Some(i))
+					{
+						Run::insert(
+							&mut candidates,
+							Run {
+								start: run.start,
+								end: i,
+								class: position.class.clone(),
+								state: *suffix.dfa.initial_state(),
+							},
+						);
+					}
+				}
+			}
+
+			candidates.retain(|c| {
+				let suffix = searcher.suffix.get(&c.class).unwrap();
+				if suffix.accepting_sinks.contains(&c.state)
+					|| (at_end && suffix.dfa.is_final_state(&c.state))
+				{
+					if best.map_or(true, |(start, end)| {
+						(c.start, Reverse(c.end)) < (start, Reverse(end))
+					}) {
+						best = Some((c.start, c.end))
+					}
+
+					false
+				} else {
+					!at_end
+				}
+			});
+
+			if let Some((start, end)) = best {
+				runs.retain(|r| r.start <= start);
+				candidates.retain(|c| c.start < start || (c.start == start && c.end > end));
+			}
+
+			if at_end {
+				return best;
+			}
+
+			let token = self.tokens[i - self.base];
+			runs = Run::step_all(|c| searcher.root.get(c).unwrap(), runs, token);
+			candidates = Run::step_all(|c| &searcher.suffix.get(c).unwrap().dfa, candidates, token);
+			i += 1;
+		}
+	}
+}
+
+impl<'a, T, C, I> Iterator for SearchMatches<'a, T, C, I>
+where
+	T: Token,
+	C: Default + Clone + Eq + Class<T>,
+	I: Iterator<Item = T>,
+{
+	type Item = Range<usize>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (start, end) = self.find()?;
+
+		// Advance at least one token after an empty match.
+		self.min = end.max(start + 1);
+		self.last_end = (end > start).then_some(end);
+		Some(self.buffered(start).offset..self.buffered(end).offset)
+	}
+}
@@ -0,0 +1,69 @@
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+use iregex_automata::{nfa::VisitingState, Automaton, RangeSet, NFA};
+
+/// Allocator counting the number of allocations.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+	let before = ALLOCATIONS.load(Ordering::Relaxed);
+	let result = f();
+	(result, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn pooled_visiting_state() {
+	// `(a|b)*b`
+	let mut aut: NFA<u32, char> = NFA::new();
+	aut.add_initial_state(0);
+	aut.add(0, Some(RangeSet::from_iter(['a', 'b'])), 0);
+	aut.add(0, None, 1);
+	aut.add(1, Some(RangeSet::from_iter(['b'])), 2);
+	aut.add_final_state(2);
+
+	let haystack: Vec<char> = "abbaabab".repeat(16).chars().collect();
+
+	let (expected, unpooled) = allocations(|| {
+		(0..haystack.len())
+			.map(|i| aut.contains(haystack[i..].iter().copied()))
+			.collect::<Vec<_>>()
+	});
+
+	let (pooled, pooled_allocations) = allocations(|| {
+		let mut state = VisitingState::default();
+		(0..haystack.len())
+			.map(|i| {
+				aut.initial_state_into(&mut state)
+					&& haystack[i..]
+						.iter()
+						.all(|&c| aut.next_state_into(&mut state, c))
+					&& Automaton::is_final_state(&aut, &state)
+			})
+			.collect::<Vec<_>>()
+	});
+
+	assert_eq!(pooled, expected);
+	assert!(
+		pooled_allocations < unpooled,
+		"{pooled_allocations} >= {unpooled}"
+	);
+}
@@ -1,7 +1,16 @@
-use std::{fs, ops::Range};
+use std::{cell::Cell, fs, ops::Range};
 
-use iregex::{Alternation, Atom, CompoundAutomaton, Concatenation, IRegEx};
-use iregex_automata::{any_char, dot::DotDisplay, nfa::U32StateBuilder, Map, RangeSet, NFA};
+use iregex::{
+	Affix, Alternation, Atom, Boundary, CaptureGroupId, CompoundAutomaton, Concatenation, IRegEx,
+	Repeat,
+};
+use iregex_automata::{
+	any_char,
+	dot::DotDisplay,
+	nfa::{TaggedNFA, Tags, U32StateBuilder},
+	token_set_intersection, Automaton, Class, Map, MapSource, RangeSet, NFA,
+};
+use mown::Mown;
 
 #[test]
 fn no_matches_anchored() {
@@ -13,7 +22,7 @@ fn no_matches_anchored() {
 	for (root, haystack) in vectors {
 		let ire = IRegEx::anchored(root);
 		let aut = ire.compile(U32StateBuilder::default()).unwrap();
-		let mut matches = aut.matches(haystack.chars());
+		let mut matches = aut.matches(haystack);
 		assert_eq!(matches.next(), None);
 	}
 }
@@ -29,7 +38,7 @@ fn single_match_anchored() {
 	for (root, haystack) in vectors {
 		let ire = IRegEx::anchored(root);
 		let aut = ire.compile(U32StateBuilder::default()).unwrap();
-		let mut matches = aut.matches(haystack.chars());
+		let mut matches = aut.matches(haystack);
 		assert_eq!(matches.next(), Some(0..haystack.len()));
 		assert_eq!(matches.next(), None);
 	}
@@ -42,7 +51,7 @@ fn single_match_unanchored() {
 	let vectors = [
 		(Atom::Token(b.clone()).into(), "aba", 1..2),
 		(
-			[Atom::Token(b.clone()).into(), Atom::Token(b).into()]
+			[Atom::Token(b.clone()), Atom::Token(b)]
 				.into_iter()
 				.collect::<Concatenation>()
 				.into(),
@@ -54,7 +63,7 @@ fn single_match_unanchored() {
 	for (root, haystack, expected) in vectors {
 		let ire = IRegEx::unanchored(root);
 		let aut = ire.compile(U32StateBuilder::default()).unwrap();
-		let mut matches = aut.matches(haystack.chars());
+		let mut matches = aut.matches(haystack);
 		assert_eq!(matches.next(), Some(expected));
 		assert_eq!(matches.next(), None);
 	}
@@ -84,7 +93,7 @@ fn many_matches_unanchored() {
 	for (i, (root, haystack, expected)) in vectors.into_iter().enumerate() {
 		let ire = IRegEx::unanchored(root);
 		let aut = ire.compile(U32StateBuilder::default()).unwrap();
-		let matches: Vec<_> = aut.matches(haystack.chars()).collect();
+		let matches: Vec<_> = aut.matches(haystack).collect();
 
 		if matches != expected {
 			write_compound_automaton(format!("many_matches_unanchored_{i}"), &aut);
@@ -94,12 +103,1035 @@ fn many_matches_unanchored() {
 	}
 }
 
+#[test]
+fn empty_matches_terminate() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let ire = IRegEx::unanchored(Atom::star(a.into()).into());
+	let aut = ire.compile(U32StateBuilder::default()).unwrap();
+
+	let dfa = aut.determinize();
+
+	let vectors: [(&str, &[Range<usize>]); 4] = [
+		// No empty match where a non-empty one ends, even at the end.
+		("aaab", &[0..3, 4..4]),
+		("aba", &[0..1, 2..3]),
+		("baab", &[0..0, 1..3, 4..4]),
+		("bbb", &[0..0, 1..1, 2..2, 3..3]),
+	];
+
+	for (haystack, expected) in vectors {
+		let matches: Vec<_> = aut.matches_str(haystack).take(16).collect();
+		assert_eq!(matches, expected);
+		assert_eq!(dfa.search(haystack).collect::<Vec<_>>(), expected);
+	}
+}
+
+#[test]
+fn str_and_bytes_haystacks() {
+	let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+	let aut = IRegEx::unanchored(b.into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+	let matches: Vec<_> = aut.matches("abba").collect();
+	assert_eq!(matches, [1..2, 2..3]);
+
+	let b = Atom::<_, ()>::Token([b'b'].into_iter().collect());
+	let aut = IRegEx::unanchored(b.into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+	let matches: Vec<_> = aut.matches(&b"abba"[..]).collect();
+	assert_eq!(matches, [1..2, 2..3]);
+}
+
+#[test]
+fn char_indices_unanchored() {
+	let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+	let ire = IRegEx::unanchored(b.into());
+	let aut = ire.compile(U32StateBuilder::default()).unwrap();
+
+	let haystack = "éb€b";
+	let bytes: Vec<_> = aut.matches_str(haystack).collect();
+	let chars: Vec<_> = aut.matches_char_indices(haystack).collect();
+
+	assert_eq!(bytes, [2..3, 6..7]);
+	assert_eq!(chars, [1..2, 3..4]);
+}
+
+#[test]
+fn matches_chars() {
+	let ire = IRegEx::unanchored(Atom::<_, ()>::Token(['b'].into_iter().collect()).into());
+	let aut = ire.compile(U32StateBuilder::default()).unwrap();
+
+	let haystack: Vec<char> = "éb€b".chars().collect();
+	let chars: Vec<_> = aut.matches_chars(haystack.iter().copied()).collect();
+	assert_eq!(chars, [1..2, 3..4]);
+}
+
+#[test]
+fn dfa_search() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+	let ab: Concatenation = [a.clone(), b.clone()].into_iter().collect();
+
+	let vectors: [(IRegEx, &str); 5] = [
+		(
+			IRegEx::unanchored(Atom::star(a.clone().into()).into()),
+			"aabaa",
+		),
+		(IRegEx::unanchored(ab.clone().into()), "abaabb"),
+		(IRegEx::anchored(Atom::star(ab.into()).into()), "ababab"),
+		(
+			IRegEx::unanchored(
+				[Concatenation::from(a), Concatenation::from(b)]
+					.into_iter()
+					.collect(),
+			),
+			"abcab",
+		),
+		(IRegEx::unanchored(Concatenation::new().into()), "éa"),
+	];
+
+	for (ire, haystack) in vectors {
+		let aut = ire.compile(U32StateBuilder::default()).unwrap();
+		let dfa = aut.determinize();
+
+		let searcher = dfa.searcher();
+
+		let expected: Vec<_> = aut.matches(haystack).collect();
+		assert_eq!(dfa.search(haystack).collect::<Vec<_>>(), expected);
+		assert_eq!(searcher.search(haystack).collect::<Vec<_>>(), expected);
+		assert_eq!(dfa.matches(haystack).collect::<Vec<_>>(), expected);
+
+		let reads = Cell::new(0);
+		let tokens = haystack.chars().inspect(|_| reads.set(reads.get() + 1));
+		assert_eq!(searcher.search_tokens(tokens).collect::<Vec<_>>(), expected);
+		assert_eq!(reads.get(), haystack.chars().count());
+	}
+}
+
+#[test]
+fn dfa_search_is_lazy() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+	let ab: Concatenation = [a, b].into_iter().collect();
+	let dfa = IRegEx::unanchored(ab.into())
+		.compile(U32StateBuilder::default())
+		.unwrap()
+		.determinize();
+	let searcher = dfa.searcher();
+
+	let reads = Cell::new(0);
+	let tokens = "xabxxxxxxxxxab"
+		.chars()
+		.inspect(|_| reads.set(reads.get() + 1));
+	let mut matches = searcher.search_tokens(tokens);
+
+	// The first match is found with one token of lookahead.
+	assert_eq!(matches.next(), Some(1..3));
+	assert_eq!(reads.get(), 4);
+
+	assert_eq!(matches.next(), Some(12..14));
+	assert_eq!(matches.next(), None);
+	assert_eq!(reads.get(), 14);
+}
+
+#[test]
+fn is_match() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+	let ab: Concatenation = [a.clone(), Atom::star(b.into())].into_iter().collect();
+
+	for ire in [
+		IRegEx::unanchored(ab.clone().into()),
+		IRegEx::anchored(ab.clone().into()),
+		IRegEx::anchored_end(ab.into()),
+		IRegEx::unanchored(Concatenation::new().into()),
+		IRegEx::anchored(Alternation::new()),
+	] {
+		let dfa = ire
+			.compile(U32StateBuilder::default())
+			.unwrap()
+			.determinize();
+
+		for haystack in ["", "a", "abb", "xabx", "bba", "cab", "xxx"] {
+			assert_eq!(
+				dfa.is_match(haystack),
+				dfa.matches(haystack).next().is_some(),
+				"{haystack:?}"
+			);
+		}
+	}
+
+	let a_b = Concatenation::from_iter([a.clone(), Atom::Token(['b'].into_iter().collect())]);
+	let dfa = IRegEx::unanchored(a_b.into())
+		.compile(U32StateBuilder::default())
+		.unwrap()
+		.determinize();
+
+	let haystack = "xxabxxxxxx";
+	let reads = Cell::new(0);
+	let tokens = haystack.chars().inspect(|_| reads.set(reads.get() + 1));
+	assert!(dfa.is_match_tokens(tokens));
+	assert_eq!(reads.get(), 4);
+
+	let dfa = IRegEx::anchored(a.into())
+		.compile(U32StateBuilder::default())
+		.unwrap()
+		.determinize();
+
+	let reads = Cell::new(0);
+	let tokens = haystack.chars().inspect(|_| reads.set(reads.get() + 1));
+	assert!(!dfa.is_match_tokens(tokens));
+	assert_eq!(reads.get(), 1);
+}
+
+#[test]
+fn compile_both() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let b_or_c = Atom::Token(['b', 'c'].into_iter().collect());
+	let root: Concatenation = [a, Atom::star(b_or_c.into())].into_iter().collect();
+
+	for ire in [
+		IRegEx::unanchored(root.clone().into()),
+		IRegEx::anchored(root.into()),
+	] {
+		let (aut, dfa) = ire.compile_both(U32StateBuilder::default()).unwrap();
+
+		for haystack in ["", "a", "abcb", "xabx", "bca", "abab"] {
+			assert_eq!(
+				dfa.matches(haystack).collect::<Vec<_>>(),
+				aut.matches(haystack).collect::<Vec<_>>()
+			);
+		}
+	}
+}
+
+#[test]
+fn empty_haystack() {
+	let a = || Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let star = || Atom::star(a().into()).into();
+	let plus = || Atom::Repeat(a().into(), Repeat { min: 1, max: None }).into();
+
+	// Regular expressions, and whether they match the empty string.
+	let vectors: [(IRegEx, bool); 5] = [
+		(IRegEx::unanchored(star()), true),
+		(IRegEx::anchored(star()), true),
+		(IRegEx::unanchored(plus()), false),
+		(IRegEx::anchored(plus()), false),
+		(IRegEx::unanchored(Concatenation::new().into()), true),
+	];
+
+	for (ire, nullable) in vectors {
+		let expected: Vec<_> = nullable.then_some(0..0).into_iter().collect();
+		let aut = ire.compile(U32StateBuilder::default()).unwrap();
+		assert_eq!(aut.matches("").collect::<Vec<_>>(), expected);
+		assert_eq!(aut.matches_char_indices("").collect::<Vec<_>>(), expected);
+		assert_eq!(aut.rmatches("").collect::<Vec<_>>(), expected);
+
+		let dfa = aut.determinize();
+		assert_eq!(dfa.matches("").collect::<Vec<_>>(), expected);
+		assert_eq!(dfa.search("").collect::<Vec<_>>(), expected);
+	}
+}
+
+#[test]
+fn repeat_n() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let aut = IRegEx::unanchored(Atom::repeat_n(a.into(), 3).into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+
+	let matches: Vec<_> = aut.matches("aa aaaaaaa").collect();
+	assert_eq!(matches, [3..6, 6..9]);
+}
+
+#[test]
+fn matches_bounded() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let aut = IRegEx::unanchored(Atom::Repeat(a.into(), Repeat::at_least(1)).into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+
+	let haystack = "aa b aaaa";
+	// Matches depending on what follows the bound are not reported.
+	let vectors: [(usize, &[(usize, usize)]); 7] = [
+		(0, &[]),
+		(2, &[]),
+		(3, &[(0, 2)]),
+		(7, &[(0, 2)]),
+		(8, &[(0, 2)]),
+		(9, &[(0, 2), (5, 9)]),
+		(100, &[(0, 2), (5, 9)]),
+	];
+
+	for (max_tokens, expected) in vectors {
+		let matches: Vec<_> = aut
+			.matches_bounded(haystack, max_tokens)
+			.map(|m| (m.start, m.end))
+			.collect();
+		assert_eq!(matches, expected);
+	}
+
+	// Like every other matcher, the iterator can be sent to another thread.
+	let matches = aut.matches_bounded(haystack, 3);
+	let count = std::thread::scope(|s| s.spawn(move || matches.count()).join().unwrap());
+	assert_eq!(count, 1);
+}
+
+#[test]
+fn literal() {
+	let aut = CompoundAutomaton::literal("foo").unwrap();
+
+	let vectors: [(&str, bool); 6] = [
+		("foo", true),
+		("", false),
+		("fo", false),
+		("food", false),
+		("afoo", false),
+		("foofoo", false),
+	];
+
+	for (haystack, expected) in vectors {
+		let matches: Vec<_> = aut.matches_str(haystack).collect();
+		assert_eq!(matches.first() == Some(&(0..haystack.len())), expected);
+		assert_eq!(matches.len(), expected as usize);
+	}
+
+	let empty = CompoundAutomaton::literal("").unwrap();
+	assert_eq!(empty.matches_str("").count(), 1);
+	assert_eq!(empty.matches_str("a").count(), 0);
+}
+
+/// Class of a position, depending on the previous character.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+enum WordClass {
+	#[default]
+	NonWord,
+	Word,
+}
+
+impl WordClass {
+	fn of(c: char) -> Self {
+		if c.is_ascii_alphanumeric() || c == '_' {
+			Self::Word
+		} else {
+			Self::NonWord
+		}
+	}
+}
+
+type ClassMapIter<'a, C, T> =
+	std::iter::Map<std::slice::Iter<'a, (C, T)>, fn(&'a (C, T)) -> (&'a C, &'a T)>;
+
+/// Map from token classes, for tests.
+#[derive(Debug)]
+struct ClassMap<C, T>(Vec<(C, T)>);
+
+impl<C, T> Default for ClassMap<C, T> {
+	fn default() -> Self {
+		Self(Vec::new())
+	}
+}
+
+impl<C: PartialEq, T> FromIterator<(C, T)> for ClassMap<C, T> {
+	fn from_iter<I: IntoIterator<Item = (C, T)>>(iter: I) -> Self {
+		let mut result = Self::default();
+		for (class, t) in iter {
+			result.set(class, t)
+		}
+		result
+	}
+}
+
+impl<C: PartialEq, T> Map<C, T> for ClassMap<C, T> {
+	type Iter<'a> = ClassMapIter<'a, C, T> where C: 'a, T: 'a;
+	type IntoEntries = std::vec::IntoIter<(C, T)>;
+
+	fn get(&self, class: &C) -> Option<&T> {
+		self.0.iter().find(|(c, _)| c == class).map(|(_, t)| t)
+	}
+
+	fn get_mut(&mut self, class: &C) -> Option<&mut T> {
+		self.0.iter_mut().find(|(c, _)| c == class).map(|(_, t)| t)
+	}
+
+	fn set(&mut self, class: C, value: T) {
+		match self.get_mut(&class) {
+			Some(t) => *t = value,
+			None => self.0.push((class, value)),
+		}
+	}
+
+	fn iter(&self) -> Self::Iter<'_> {
+		self.0.iter().map(|(c, t)| (c, t))
+	}
+
+	fn into_entries(self) -> Self::IntoEntries {
+		self.0.into_iter()
+	}
+}
+
+impl MapSource for WordClass {
+	type Map<U> = ClassMap<Self, U>;
+}
+
+impl Class for WordClass {
+	fn classify<'a>(&self, set: &'a RangeSet<char>) -> ClassMap<Self, Mown<'a, RangeSet<char>>> {
+		let mut word_chars = RangeSet::new();
+		word_chars.insert('0'..='9');
+		word_chars.insert('A'..='Z');
+		word_chars.insert('_');
+		word_chars.insert('a'..='z');
+
+		let word = token_set_intersection(set, &word_chars);
+		let mut non_word = set.clone();
+		for range in word_chars.iter() {
+			non_word.remove(*range);
+		}
+
+		[(WordClass::Word, word), (WordClass::NonWord, non_word)]
+			.into_iter()
+			.filter(|(_, set)| !set.is_empty())
+			.map(|(class, set)| (class, Mown::Owned(set)))
+			.collect()
+	}
+
+	fn next_class(&self, token: &char) -> Self {
+		WordClass::of(*token)
+	}
+}
+
+/// Matches after a word character.
+#[derive(Debug)]
+struct AfterWord;
+
+impl Boundary<char> for AfterWord {
+	type Class = WordClass;
+
+	fn apply(&self, class: &WordClass) -> Option<WordClass> {
+		(*class == WordClass::Word).then_some(*class)
+	}
+}
+
+#[test]
+fn class_coverage() {
+	let digit = Atom::<_, AfterWord>::Token(('0'..='9').collect());
+	let ire: IRegEx<char, AfterWord> = IRegEx::unanchored(
+		[Atom::Boundary(AfterWord), digit]
+			.into_iter()
+			.collect::<Concatenation<_, _>>()
+			.into(),
+	);
+	let mut aut = ire.compile(U32StateBuilder::default()).unwrap();
+
+	let coverage = aut.class_coverage();
+	assert!(coverage.is_complete());
+	assert_eq!(coverage.root.len(), 2);
+	assert_eq!(coverage.suffix, [(WordClass::Word, true)]);
+	assert_eq!(aut.matches_str("a1 2b3").collect::<Vec<_>>(), [1..2, 5..6]);
+
+	aut.root = aut
+		.root
+		.into_entries()
+		.filter(|(class, _)| *class == WordClass::NonWord)
+		.collect();
+
+	let coverage = aut.class_coverage();
+	assert!(!coverage.is_complete());
+	assert_eq!(
+		coverage.missing_roots().collect::<Vec<_>>(),
+		[&WordClass::Word]
+	);
+	assert_eq!(coverage.missing_suffixes().count(), 0);
+}
+
+#[test]
+fn final_classes() {
+	let compile = |atoms: Vec<Atom<char, AfterWord>>| {
+		IRegEx::unanchored(atoms.into_iter().collect::<Concatenation<_, _>>().into())
+			.compile(U32StateBuilder::default())
+			.unwrap()
+	};
+
+	let after_word_digit = compile(vec![
+		Atom::Boundary(AfterWord),
+		Atom::Token(('0'..='9').collect()),
+	]);
+	assert_eq!(
+		after_word_digit.final_classes().collect::<Vec<_>>(),
+		[&WordClass::Word]
+	);
+
+	let letter_or_dash = compile(vec![Atom::Token(['a', '-'].into_iter().collect())]);
+	let mut classes: Vec<_> = letter_or_dash.final_classes().collect();
+	classes.sort_by_key(|class| **class == WordClass::Word);
+	assert_eq!(classes, [&WordClass::NonWord, &WordClass::Word]);
+}
+
+#[test]
+fn tokens() {
+	let mut letters = RangeSet::new();
+	letters.insert('a'..='z');
+
+	let keyword = NFA::singleton("if".chars(), |i| i.map_or(0, |i| i as u32 + 1));
+	let mut ident = NFA::new();
+	ident.add(0, Some(letters.clone()), 1);
+	ident.add(1, Some(letters), 1);
+	ident.add_initial_state(0);
+	ident.add_final_state(1);
+	let whitespace = NFA::simple_loop(0, [' '].into_iter().collect());
+
+	let aut = CompoundAutomaton::lexer([keyword, ident, whitespace]);
+
+	let tokens: Vec<_> = aut.tokens("if x").collect();
+	assert_eq!(tokens, [(0..2, 0), (2..3, 2), (3..4, 1)]);
+
+	let tokens: Vec<_> = aut.tokens("iffy  if").collect();
+	assert_eq!(tokens, [(0..4, 1), (4..6, 2), (6..8, 0)]);
+
+	let mut lexer = aut.tokens("if 1");
+	assert_eq!(lexer.by_ref().count(), 2);
+	assert_eq!(lexer.offset(), 3);
+}
+
+#[test]
+#[should_panic]
+fn tokens_unanchored() {
+	let whitespace = NFA::simple_loop(0, [' '].into_iter().collect());
+	let mut aut = CompoundAutomaton::lexer([whitespace]);
+	aut.any_prefix = true;
+	aut.tokens(" ");
+}
+
+#[test]
+fn anchored_end() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let ire = IRegEx::anchored_end(Atom::Repeat(a.into(), Repeat::at_least(1)).into());
+	let aut = ire.compile(U32StateBuilder::default()).unwrap();
+
+	let vectors: [(&str, Option<Range<usize>>); 5] = [
+		("aaa", Some(0..3)),
+		("aab aa", Some(4..6)),
+		("aab", None),
+		("baa", Some(1..3)),
+		("", None),
+	];
+
+	for (haystack, expected) in vectors {
+		let matches: Vec<_> = aut.matches_str(haystack).collect();
+		assert_eq!(matches, Vec::from_iter(expected));
+	}
+}
+
+#[test]
+fn first_set() {
+	let a_to_c = Atom::<_, ()>::Token(('a'..='c').collect());
+	let x = Atom::Token(['x'].into_iter().collect());
+	let d = Atom::Token(['d'].into_iter().collect());
+	assert_eq!(a_to_c.as_token(), Some(&('a'..='c').collect()));
+
+	let root: Alternation = [
+		Concatenation::from_iter([a_to_c, x]),
+		Concatenation::from_iter([d]),
+	]
+	.into_iter()
+	.collect();
+
+	for ire in [
+		IRegEx::anchored(root.clone()),
+		IRegEx::unanchored(root.clone()),
+		IRegEx::unanchored(Atom::star(root).into()),
+	] {
+		let aut = ire.compile(U32StateBuilder::default()).unwrap();
+		let expected: RangeSet<char> = ('a'..='d').collect();
+		assert_eq!(aut.first_set(), expected);
+	}
+}
+
+/// Single class whose next class must never be computed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+struct Constant;
+
+impl MapSource for Constant {
+	type Map<U> = ClassMap<Self, U>;
+}
+
+impl Class for Constant {
+	const CONSTANT: bool = true;
+
+	fn classify<'a>(&self, set: &'a RangeSet<char>) -> ClassMap<Self, Mown<'a, RangeSet<char>>> {
+		Map::singleton(Constant, Mown::Borrowed(set))
+	}
+
+	fn next_class(&self, _token: &char) -> Self {
+		unreachable!("constant class")
+	}
+}
+
+impl Boundary<char> for Constant {
+	type Class = Constant;
+
+	fn apply(&self, class: &Constant) -> Option<Constant> {
+		Some(*class)
+	}
+}
+
+#[test]
+fn constant_class() {
+	fn root<B>() -> Alternation<char, B> {
+		let a = Atom::Token(['a'].into_iter().collect());
+		let b = Atom::Token(['b', 'c'].into_iter().collect());
+		let ab: Concatenation<_, B> = [a, Atom::star(b.into())].into_iter().collect();
+		ab.into()
+	}
+
+	let unit = IRegEx::<char, ()>::unanchored(root())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+	let constant = IRegEx::<char, Constant>::unanchored(root())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+
+	for haystack in ["", "a", "abcb", "xabx", "bca", "abab", "éaéb"] {
+		let expected: Vec<_> = unit.matches(haystack).collect();
+		assert_eq!(constant.matches(haystack).collect::<Vec<_>>(), expected);
+		assert_eq!(
+			constant.matches_char_indices(haystack).collect::<Vec<_>>(),
+			unit.matches_char_indices(haystack).collect::<Vec<_>>()
+		);
+		assert_eq!(
+			constant.determinize().search(haystack).collect::<Vec<_>>(),
+			expected
+		);
+		assert_eq!(
+			constant.determinize().is_match(haystack),
+			!expected.is_empty()
+		);
+	}
+}
+
+#[test]
+fn captures() {
+	let group = |id, atom: Atom| Atom::Capture(CaptureGroupId(id), atom.into());
+	let char = |c| Atom::Token(RangeSet::from_iter([c]));
+	let captures = |ire: IRegEx, haystack| {
+		let aut = ire.compile(U32StateBuilder::default()).unwrap();
+		aut.matches_str(haystack)
+			.captures()
+			.map(|(range, captures)| {
+				let spans = (0..2).map(|id| captures.get(CaptureGroupId(id)));
+				(range, spans.collect::<Vec<_>>())
+			})
+			.collect::<Vec<_>>()
+	};
+
+	// `(a?)b`: the group participates even when it matches the empty string.
+	let optional_a = Atom::Repeat(char('a').into(), Repeat::between(0, 1));
+	let root: Concatenation = [group(0, optional_a), char('b')].into_iter().collect();
+	let ire = || IRegEx::unanchored(root.clone().into());
+	assert_eq!(captures(ire(), "ab"), [(0..2, vec![Some(0..1), None])]);
+	assert_eq!(captures(ire(), "xxb"), [(2..3, vec![Some(2..2), None])]);
+
+	// `(a)|(b)`: the group of the non-taken alternative does not participate.
+	let root: Alternation = [group(0, char('a')), group(1, char('b'))]
+		.into_iter()
+		.map(Concatenation::from)
+		.collect();
+	let ire = || IRegEx::unanchored(root.clone());
+	assert_eq!(
+		captures(ire(), "ba"),
+		[
+			(0..1, vec![None, Some(0..1)]),
+			(1..2, vec![Some(1..2), None])
+		]
+	);
+
+	// `(a)*`: a repeated group reports its last iteration, and does not
+	// participate if never repeated.
+	let root: Alternation = Atom::Repeat(group(0, char('a')).into(), Repeat::STAR).into();
+	let ire = || IRegEx::anchored(root.clone());
+	assert_eq!(captures(ire(), "aaa"), [(0..3, vec![Some(2..3), None])]);
+	assert_eq!(captures(ire(), ""), [(0..0, vec![None, None])]);
+
+	// `(a)|(a)`: the leftmost alternative has priority.
+	let root: Alternation = [group(0, char('a')), group(1, char('a'))]
+		.into_iter()
+		.map(Concatenation::from)
+		.collect();
+	let ire = || IRegEx::anchored(root.clone());
+	assert_eq!(captures(ire(), "a"), [(0..1, vec![Some(0..1), None])]);
+
+	// `(a*)(a*)`: repetitions are greedy.
+	let star_a = || Atom::Repeat(char('a').into(), Repeat::STAR);
+	let root: Concatenation = [group(0, star_a()), group(1, star_a())]
+		.into_iter()
+		.collect();
+	let ire = || IRegEx::anchored(root.clone().into());
+	assert_eq!(
+		captures(ire(), "aa"),
+		[(0..2, vec![Some(0..2), Some(2..2)])]
+	);
+
+	// `(a|ab)(b)?`: the leftmost alternative has priority, even if it is a
+	// prefix of another one.
+	let literal = |s: &str| Concatenation::from(Atom::Literal(s.chars().collect()));
+	let a_or_ab: Alternation = [literal("a"), literal("ab")].into_iter().collect();
+	let optional_b = Atom::Repeat(char('b').into(), Repeat::between(0, 1));
+	let root: Concatenation = [
+		Atom::Capture(CaptureGroupId(0), a_or_ab),
+		group(1, optional_b),
+	]
+	.into_iter()
+	.collect();
+	let ire = || IRegEx::anchored(root.clone().into());
+	assert_eq!(
+		captures(ire(), "ab"),
+		[(0..2, vec![Some(0..1), Some(1..2)])]
+	);
+
+	// `(ab|a)(b)?`
+	let ab_or_a: Alternation = [literal("ab"), literal("a")].into_iter().collect();
+	let optional_b = Atom::Repeat(char('b').into(), Repeat::between(0, 1));
+	let root: Concatenation = [
+		Atom::Capture(CaptureGroupId(0), ab_or_a),
+		group(1, optional_b),
+	]
+	.into_iter()
+	.collect();
+	let ire = || IRegEx::anchored(root.clone().into());
+	assert_eq!(
+		captures(ire(), "ab"),
+		[(0..2, vec![Some(0..2), Some(2..2)])]
+	);
+
+	// `(abc|a|ab)(bc)?`
+	let alt: Alternation = [literal("abc"), literal("a"), literal("ab")]
+		.into_iter()
+		.collect();
+	let optional_bc = Atom::Repeat(Atom::Literal(vec!['b', 'c']).into(), Repeat::between(0, 1));
+	let root: Concatenation = [Atom::Capture(CaptureGroupId(0), alt), group(1, optional_bc)]
+		.into_iter()
+		.collect();
+	let ire = || IRegEx::anchored(root.clone().into());
+	assert_eq!(
+		captures(ire(), "abc"),
+		[(0..3, vec![Some(0..3), Some(3..3)])]
+	);
+}
+
+#[test]
+fn shortest() {
+	let a: Atom = Atom::Token(RangeSet::from_iter(['a']));
+	let aut = IRegEx::unanchored(Atom::Repeat(a.into(), Repeat::at_least(1)).into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+
+	assert_eq!(aut.matches_str("aaa").next(), Some(0..3));
+	assert_eq!(
+		aut.matches_str("aaa").shortest().collect::<Vec<_>>(),
+		[0..1, 1..2, 2..3]
+	);
+	assert_eq!(
+		aut.matches_str("baab")
+			.shortest()
+			.char_indices()
+			.collect::<Vec<_>>(),
+		[1..2, 2..3]
+	);
+}
+
+#[test]
+fn concat() {
+	let plus = |c| {
+		let atom: Atom = Atom::Token(RangeSet::from_iter([c]));
+		IRegEx::unanchored(Atom::Repeat(atom.into(), Repeat::at_least(1)).into())
+			.compile(U32StateBuilder::default())
+			.unwrap()
+	};
+
+	let aut = plus('a').concat(&plus('b'));
+	assert_eq!(aut.matches_str("aabb").next(), Some(0..4));
+	assert_eq!(
+		aut.matches_str("xaby aab ba").collect::<Vec<_>>(),
+		[1..3, 5..8]
+	);
+	assert_eq!(aut.matches_str("aa bb").next(), None);
+
+	// An end anchor of `self` only lets `other` match the empty string, and
+	// a start anchor of `other` only lets `self` match the empty string.
+	let compile = |ire: IRegEx| ire.compile(U32StateBuilder::default()).unwrap();
+	let a: Atom = Atom::Token(RangeSet::from_iter(['a']));
+	let b: Atom = Atom::Token(RangeSet::from_iter(['b']));
+	let a_star = Atom::Repeat(a.clone().into(), Repeat::at_least(0));
+	let b_star = Atom::Repeat(b.clone().into(), Repeat::at_least(0));
+
+	let aut = compile(IRegEx::anchored(a.clone().into()))
+		.concat(&compile(IRegEx::anchored(b.clone().into())));
+	for haystack in ["", "a", "b", "ab", "abb", "xab"] {
+		assert_eq!(aut.matches_str(haystack).next(), None);
+	}
+
+	let aut = compile(IRegEx::anchored_end(a.clone().into()))
+		.concat(&compile(IRegEx::unanchored(b_star.into())));
+	assert_eq!(aut.matches_str("xa").next(), Some(1..2));
+	assert_eq!(aut.matches_str("ab").next(), None);
+
+	let aut = compile(IRegEx::unanchored(a_star.into()))
+		.concat(&compile(IRegEx::anchored(b.clone().into())));
+	assert_eq!(aut.matches_str("b").next(), Some(0..1));
+	assert_eq!(aut.matches_str("ab").next(), None);
+
+	let aut = compile(IRegEx::unanchored(a.into())).concat(&compile(IRegEx::anchored(b.into())));
+	assert_eq!(aut.matches_str("b").next(), None);
+
+	// The root of `other` is selected by the class at the end of `self`.
+	let compile = |atoms: Vec<Atom<char, AfterWord>>| {
+		IRegEx::unanchored(atoms.into_iter().collect::<Concatenation<_, _>>().into())
+			.compile(U32StateBuilder::default())
+			.unwrap()
+	};
+	let letter_or_dash = compile(vec![Atom::Token(['a', '-'].into_iter().collect())]);
+	let after_word_digit = compile(vec![
+		Atom::Boundary(AfterWord),
+		Atom::Token(('0'..='9').collect()),
+	]);
+
+	let aut = letter_or_dash.concat(&after_word_digit);
+	let mut matches = aut.matches_str("-1 a2");
+	assert_eq!(matches.next(), Some(3..5));
+	assert_eq!(matches.next(), None);
+}
+
+#[test]
+fn intersects() {
+	let compile = |ire: IRegEx| ire.compile(U32StateBuilder::default()).unwrap();
+	let a: Atom = Atom::Token(RangeSet::from_iter(['a']));
+	let x: Atom = Atom::Token(RangeSet::from_iter(['x']));
+
+	// `^a$` and `a` both match `a`.
+	let anchored_a = compile(IRegEx::anchored(a.clone().into()));
+	let unanchored_a = compile(IRegEx::unanchored(a.clone().into()));
+	assert!(anchored_a.intersects(&unanchored_a));
+
+	// The matches of `a` preceded by exactly `x` never start at the
+	// beginning of the haystack, unlike those of `^a$`.
+	let after_x = compile(IRegEx {
+		root: a.clone().into(),
+		prefix: Affix::Alternation(x.clone().into()),
+		suffix: Affix::Any,
+	});
+	assert!(!anchored_a.intersects(&after_x));
+	assert!(unanchored_a.intersects(&after_x));
+
+	// Likewise, the matches of `a` followed by exactly `x` never end the
+	// haystack.
+	let before_x = compile(IRegEx {
+		root: a.into(),
+		prefix: Affix::Any,
+		suffix: Affix::Alternation(x.into()),
+	});
+	assert!(!anchored_a.intersects(&before_x));
+	assert!(after_x.intersects(&before_x));
+}
+
+#[test]
+fn shared_between_threads() {
+	fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+	let digit: Atom = Atom::Token(('0'..='9').collect());
+	let aut = IRegEx::anchored(Atom::Repeat(digit.into(), Repeat::at_least(1)).into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+	assert_send_sync(&aut);
+
+	let dfa = aut.determinize();
+	assert_send_sync(&dfa);
+
+	std::thread::scope(|scope| {
+		for t in 0..4 {
+			let (aut, dfa) = (&aut, &dfa);
+			scope.spawn(move || {
+				for i in 0..100 {
+					let number = (t * 100 + i).to_string();
+					assert!(aut.matches_str(&number).next().is_some());
+					assert!(dfa.is_match(number.as_str()));
+
+					let word = format!("{number}x");
+					assert!(aut.matches_str(&word).next().is_none());
+					assert!(!dfa.is_match(word.as_str()));
+				}
+			});
+		}
+	});
+}
+
+#[test]
+fn rmatches() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let aut = IRegEx::unanchored(Atom::Repeat(a.into(), Repeat { min: 1, max: None }).into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+
+	let mut matches = aut.rmatches("aa b aaa");
+	assert_eq!(matches.next(), Some(5..8));
+	assert_eq!(matches.next(), Some(0..2));
+	assert_eq!(matches.next(), None);
+}
+
+#[test]
+fn untagged_matches() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+	let ab: Concatenation = [a.clone(), b].into_iter().collect();
+
+	let vectors: [(IRegEx, &str); 3] = [
+		(IRegEx::unanchored(Atom::star(a.into()).into()), "aabaa"),
+		(IRegEx::unanchored(ab.clone().into()), "abaabb"),
+		(IRegEx::anchored(Atom::star(ab.into()).into()), "ababab"),
+	];
+
+	for (ire, haystack) in vectors {
+		assert!(!ire.has_captures());
+		let tagged = ire.compile(U32StateBuilder::default()).unwrap();
+		let untagged = ire.compile_untagged(U32StateBuilder::default()).unwrap();
+		assert_eq!(
+			untagged.matches(haystack).collect::<Vec<_>>(),
+			tagged.matches(haystack).collect::<Vec<_>>()
+		);
+	}
+}
+
+#[test]
+fn total_states() {
+	let b = Atom::<_, ()>::Token(['b'].into_iter().collect());
+	let aut = IRegEx::anchored(b.into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+	let n = aut.total_states();
+	assert!(n > 0 && n <= 8, "unexpected state count {n}");
+}
+
+#[test]
+fn repeated_boundary() {
+	let a = Atom::<_, ()>::Token(['a'].into_iter().collect());
+	let root: Concatenation = [Atom::star(Atom::Boundary(()).into()), a]
+		.into_iter()
+		.collect();
+
+	let aut = IRegEx::unanchored(root.into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+	let matches: Vec<_> = aut.matches("baa").collect();
+	assert_eq!(matches, [1..2, 2..3]);
+}
+
+#[test]
+fn tagged_matches() {
+	let mut letters = RangeSet::new();
+	letters.insert('a'..='z');
+
+	// `if` (tagged `0`) or `[a-z]+` (tagged `1`).
+	let mut root = NFA::new();
+	root.add_initial_state(0);
+	root.add(0, Some(RangeSet::from_iter(['i'])), 1);
+	root.add(1, Some(RangeSet::from_iter(['f'])), 2);
+	root.add(0, Some(letters.clone()), 3);
+	root.add(3, Some(letters), 3);
+	root.add(2, None, 4);
+	root.add(3, None, 4);
+	root.add_final_state(4);
+
+	let mut tags = Tags::new();
+	tags.insert(2, 0, 4);
+	tags.insert(3, 1, 4);
+
+	let any = || TaggedNFA::new(NFA::universal(any_char()), Tags::new());
+	let aut: CompoundAutomaton<TaggedNFA<u32, char, usize>> = CompoundAutomaton {
+		prefix: any(),
+		any_prefix: true,
+		root: Map::singleton((), TaggedNFA::new(root, tags)),
+		suffix: Map::singleton((), any()),
+	};
+
+	let matches: Vec<_> = aut.matches("if iff x").tagged().collect();
+	assert_eq!(matches, [(0..2, Some(0)), (3..6, Some(1)), (7..8, Some(1))]);
+}
+
+/// Automaton counting the number of steps it performs.
+struct Counting<A> {
+	inner: A,
+	steps: Cell<usize>,
+}
+
+impl<A: Automaton<char>> Automaton<char> for Counting<A> {
+	type State<'a> = A::State<'a> where Self: 'a;
+
+	fn initial_state(&self) -> Option<Self::State<'_>> {
+		self.inner.initial_state()
+	}
+
+	fn next_state<'a>(
+		&'a self,
+		current_state: Self::State<'a>,
+		token: char,
+	) -> Option<Self::State<'a>> {
+		self.steps.set(self.steps.get() + 1);
+		self.inner.next_state(current_state, token)
+	}
+
+	fn is_final_state<'a>(&'a self, state: &Self::State<'a>) -> bool {
+		self.inner.is_final_state(state)
+	}
+}
+
+#[test]
+fn any_prefix_not_stepped() {
+	let ab: Concatenation = [
+		Atom::<_, ()>::Token(['a'].into_iter().collect()),
+		Atom::Token(['b'].into_iter().collect()),
+	]
+	.into_iter()
+	.collect();
+
+	let aut = IRegEx::unanchored(ab.into())
+		.compile(U32StateBuilder::default())
+		.unwrap();
+	assert!(aut.any_prefix);
+
+	let counting = |inner| Counting {
+		inner,
+		steps: Cell::new(0),
+	};
+	let mut aut: CompoundAutomaton<_> = CompoundAutomaton {
+		prefix: counting(aut.prefix),
+		any_prefix: aut.any_prefix,
+		root: aut
+			.root
+			.into_entries()
+			.map(|(c, a)| (c, counting(a)))
+			.collect(),
+		suffix: aut
+			.suffix
+			.into_entries()
+			.map(|(c, a)| (c, counting(a)))
+			.collect(),
+	};
+
+	let matches: Vec<_> = aut.matches("abaabb").collect();
+	assert_eq!(matches, [0..2, 3..5]);
+	assert_eq!(aut.prefix.steps.get(), 0);
+
+	aut.any_prefix = false;
+	assert_eq!(aut.matches("abaabb").collect::<Vec<_>>(), matches);
+	assert!(aut.prefix.steps.get() > 0);
+}
+
 fn write_compound_automaton(basename: String, aut: &CompoundAutomaton) {
 	write_automaton(format!("{basename}_prefix.dot"), &aut.prefix);
-	write_automaton(format!("{basename}_root.dot"), &aut.root.get(&()).unwrap());
+	write_automaton(format!("{basename}_root.dot"), aut.root.get(&()).unwrap());
 	write_automaton(
 		format!("{basename}_suffix.dot"),
-		&aut.suffix.get(&()).unwrap(),
+		aut.suffix.get(&()).unwrap(),
 	);
 }
 